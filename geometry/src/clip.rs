@@ -0,0 +1,279 @@
+// A rectangle that geometry is cut to before it is tessellated, in coordinates normalized to the tile. Tiles carry
+// a buffer of geometry from their neighbours, which would otherwise be drawn twice where tiles meet.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ClipBox {
+    pub min: glm::Vec2,
+    pub max: glm::Vec2,
+}
+
+impl ClipBox {
+    // The tile, from 0 to 1 on both axes, grown by `buffer` on every side.
+    pub fn tile(buffer: f32) -> ClipBox {
+        ClipBox {
+            min: glm::vec2(-buffer, -buffer),
+            max: glm::vec2(1.0 + buffer, 1.0 + buffer),
+        }
+    }
+
+    // Cuts a closed ring with the Sutherland-Hodgman algorithm. Where a concave ring leaves and enters the box
+    // again, the result runs along the edge of the box in between. Empty when the ring lies outside.
+    pub fn clip_ring(&self, ring: &[glm::Vec2]) -> Vec<glm::Vec2> {
+        let mut ring = ring.to_vec();
+        for edge in &self.edges() {
+            let mut clipped = vec![];
+            let mut prev = match ring.last() {
+                Some(prev) => *prev,
+                None => break,
+            };
+            for &curr in &ring {
+                match (edge.keeps(prev), edge.keeps(curr)) {
+                    (true, true) => clipped.push(curr),
+                    (true, false) => clipped.push(edge.intersect(prev, curr)),
+                    (false, true) => {
+                        clipped.push(edge.intersect(prev, curr));
+                        clipped.push(curr);
+                    }
+                    (false, false) => {}
+                }
+                prev = curr;
+            }
+            ring = clipped;
+        }
+
+        // A point lying on an edge comes out twice.
+        ring.dedup();
+        while ring.len() > 1 && ring.first() == ring.last() {
+            ring.pop();
+        }
+        if ring.len() < 3 {
+            return vec![];
+        }
+        ring
+    }
+
+    // Cuts an open line. A line that leaves the box and comes back falls apart into several lines.
+    pub fn clip_line(&self, line: &[glm::Vec2]) -> Vec<Vec<glm::Vec2>> {
+        let mut lines = vec![];
+        let mut current: Vec<glm::Vec2> = vec![];
+        for segment in line.windows(2) {
+            if let Some((start, end)) = self.clip_segment(segment[0], segment[1]) {
+                if current.is_empty() {
+                    current.push(start);
+                }
+                current.push(end);
+                // The line leaves the box here.
+                if end != segment[1] {
+                    lines.push(std::mem::take(&mut current));
+                }
+            }
+        }
+        if current.len() >= 2 {
+            lines.push(current);
+        }
+        lines
+    }
+
+    // The side of the box the segment from a to b lies on, in the order of left, right, top and bottom.
+    pub fn side_along(&self, a: glm::Vec2, b: glm::Vec2) -> Option<usize> {
+        self.edges()
+            .iter()
+            .position(|edge| a[edge.axis] == edge.bound && b[edge.axis] == edge.bound)
+    }
+
+    // The direction pointing out of the box across a side, numbered as in `side_along`.
+    pub fn outward(side: usize) -> glm::Vec2 {
+        [
+            glm::vec2(-1.0, 0.0),
+            glm::vec2(1.0, 0.0),
+            glm::vec2(0.0, -1.0),
+            glm::vec2(0.0, 1.0),
+        ][side]
+    }
+
+    fn clip_segment(&self, start: glm::Vec2, end: glm::Vec2) -> Option<(glm::Vec2, glm::Vec2)> {
+        let (mut start, mut end) = (start, end);
+        for edge in &self.edges() {
+            match (edge.keeps(start), edge.keeps(end)) {
+                (true, true) => {}
+                (true, false) => end = edge.intersect(start, end),
+                (false, true) => start = edge.intersect(start, end),
+                (false, false) => return None,
+            }
+        }
+        Some((start, end))
+    }
+
+    fn edges(&self) -> [Edge; 4] {
+        [
+            Edge {
+                axis: 0,
+                bound: self.min.x,
+                keep_above: true,
+            },
+            Edge {
+                axis: 0,
+                bound: self.max.x,
+                keep_above: false,
+            },
+            Edge {
+                axis: 1,
+                bound: self.min.y,
+                keep_above: true,
+            },
+            Edge {
+                axis: 1,
+                bound: self.max.y,
+                keep_above: false,
+            },
+        ]
+    }
+}
+
+// One side of the box, keeping the points on or above `bound` on `axis` or those on or below it.
+struct Edge {
+    axis: usize,
+    bound: f32,
+    keep_above: bool,
+}
+
+impl Edge {
+    fn keeps(&self, point: glm::Vec2) -> bool {
+        if self.keep_above {
+            point[self.axis] >= self.bound
+        } else {
+            point[self.axis] <= self.bound
+        }
+    }
+
+    // Where the segment from a kept to a dropped point, or the other way round, crosses the edge. The crossing lies
+    // exactly on the edge, so geometry cut from neighbouring tiles meets without a gap. The endpoints are put in a
+    // fixed order first, so rings sharing the segment get the same point whichever way they run along it.
+    fn intersect(&self, a: glm::Vec2, b: glm::Vec2) -> glm::Vec2 {
+        let (a, b) = if (a.x, a.y) <= (b.x, b.y) {
+            (a, b)
+        } else {
+            (b, a)
+        };
+        let t = (self.bound - a[self.axis]) / (b[self.axis] - a[self.axis]);
+        let mut point = a + (b - a) * t;
+        point[self.axis] = self.bound;
+        point
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::test_util;
+    use super::*;
+
+    fn ring_area(ring: &[glm::Vec2]) -> f32 {
+        let mut area = 0.0;
+        let mut prev = ring[ring.len() - 1];
+        for curr in ring {
+            area += prev.x * curr.y - curr.x * prev.y;
+            prev = *curr;
+        }
+        0.5 * area
+    }
+
+    fn square(x0: f32, y0: f32, x1: f32, y1: f32) -> Vec<glm::Vec2> {
+        test_util::square(x0, y0, x1, y1)
+            .into_iter()
+            .map(|(x, y)| glm::vec2(x, y))
+            .collect()
+    }
+
+    #[test]
+    fn rings_inside_are_kept() {
+        let ring = square(0.25, 0.25, 0.75, 0.75);
+        assert_eq!(ClipBox::tile(0.0).clip_ring(&ring), ring);
+    }
+
+    #[test]
+    fn rings_across_the_edge_are_cut() {
+        let clipped = ClipBox::tile(0.0).clip_ring(&square(0.5, 0.5, 1.5, 1.5));
+        assert_eq!(clipped.len(), 4);
+        assert!((ring_area(&clipped) - 0.25).abs() < 1e-6);
+        assert!(clipped.iter().any(|p| p.x == 1.0));
+        assert!(clipped.iter().all(|p| p.x <= 1.0 && p.y <= 1.0));
+    }
+
+    #[test]
+    fn rings_outside_are_dropped() {
+        let box_ = ClipBox::tile(0.0);
+        assert!(box_.clip_ring(&square(1.5, 0.0, 2.0, 1.0)).is_empty());
+        // Only touching the tile leaves a ring without area.
+        assert!(box_.clip_ring(&square(1.0, 0.0, 2.0, 1.0)).is_empty());
+        assert!(box_.clip_ring(&[]).is_empty());
+    }
+
+    #[test]
+    fn the_buffer_grows_the_box() {
+        let ring = square(-0.05, 0.25, 0.75, 0.75);
+        assert_eq!(ClipBox::tile(0.1).clip_ring(&ring), ring);
+        assert!(ClipBox::tile(0.0)
+            .clip_ring(&ring)
+            .iter()
+            .all(|p| p.x >= 0.0));
+    }
+
+    #[test]
+    fn crossings_do_not_depend_on_direction() {
+        let box_ = ClipBox::tile(0.0);
+        let (a, b, c) = (
+            glm::vec2(0.01, 0.1),
+            glm::vec2(1.013, 0.9),
+            glm::vec2(0.01, 0.9),
+        );
+        let forward = box_.clip_ring(&[a, b, c]);
+        let backward = box_.clip_ring(&[c, b, a]);
+        let crossing = |ring: &[glm::Vec2]| *ring.iter().find(|p| p.x == 1.0 && p.y < 0.9).unwrap();
+        assert_eq!(
+            crossing(&forward).y.to_bits(),
+            crossing(&backward).y.to_bits()
+        );
+    }
+
+    #[test]
+    fn lines_are_cut_at_the_edge() {
+        let line = vec![
+            glm::vec2(-0.5, 0.5),
+            glm::vec2(0.5, 0.5),
+            glm::vec2(0.5, 1.5),
+        ];
+        assert_eq!(
+            ClipBox::tile(0.0).clip_line(&line),
+            vec![vec![
+                glm::vec2(0.0, 0.5),
+                glm::vec2(0.5, 0.5),
+                glm::vec2(0.5, 1.0)
+            ]]
+        );
+    }
+
+    #[test]
+    fn lines_leaving_and_returning_fall_apart() {
+        let line = vec![
+            glm::vec2(0.25, 0.5),
+            glm::vec2(1.25, 0.5),
+            glm::vec2(1.25, 0.75),
+            glm::vec2(0.25, 0.75),
+        ];
+        assert_eq!(
+            ClipBox::tile(0.0).clip_line(&line),
+            vec![
+                vec![glm::vec2(0.25, 0.5), glm::vec2(1.0, 0.5)],
+                vec![glm::vec2(1.0, 0.75), glm::vec2(0.25, 0.75)],
+            ]
+        );
+    }
+
+    #[test]
+    fn lines_outside_are_dropped() {
+        let line = vec![glm::vec2(1.5, 0.0), glm::vec2(1.5, 1.0)];
+        assert!(ClipBox::tile(0.0).clip_line(&line).is_empty());
+        assert!(ClipBox::tile(0.0)
+            .clip_line(&[glm::vec2(0.5, 0.5)])
+            .is_empty());
+    }
+}
@@ -0,0 +1,451 @@
+use std::fmt;
+
+// A position in tile coordinates, from 0 to the extent of the layer with Y pointing down. Geometry in the tile
+// buffer lies outside that range.
+pub type TilePoint = (i64, i64);
+
+// The commands a vector tile geometry is made of.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Command {
+    MoveTo,
+    LineTo,
+    ClosePath,
+}
+
+impl Command {
+    fn from_id(id: u32) -> Option<Command> {
+        match id {
+            1 => Some(Command::MoveTo),
+            2 => Some(Command::LineTo),
+            7 => Some(Command::ClosePath),
+            _ => None,
+        }
+    }
+}
+
+// Why a geometry does not follow the vector tile specification. `position` is the index of the offending command
+// integer in the geometry.
+#[derive(Clone, Debug, PartialEq)]
+pub enum GeometryError {
+    UnknownCommand {
+        position: usize,
+        id: u32,
+    },
+    InvalidCount {
+        position: usize,
+        command: Command,
+        count: usize,
+    },
+    UnexpectedCommand {
+        position: usize,
+        expected: Command,
+        found: Command,
+    },
+    UnexpectedEnd {
+        expected: Command,
+    },
+    MissingParameters {
+        position: usize,
+        command: Command,
+        expected: usize,
+        available: usize,
+    },
+    // Point geometries are a single MoveTo, anything after it is invalid.
+    TrailingData {
+        position: usize,
+    },
+}
+
+impl fmt::Display for GeometryError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            GeometryError::UnknownCommand { position, id } => {
+                write!(f, "unknown command id {} at {}", id, position)
+            }
+            GeometryError::InvalidCount {
+                position,
+                command,
+                count,
+            } => write!(
+                f,
+                "invalid count {} for {:?} at {}",
+                count, command, position
+            ),
+            GeometryError::UnexpectedCommand {
+                position,
+                expected,
+                found,
+            } => write!(
+                f,
+                "expected {:?} but found {:?} at {}",
+                expected, found, position
+            ),
+            GeometryError::UnexpectedEnd { expected } => {
+                write!(f, "geometry ends where {:?} was expected", expected)
+            }
+            GeometryError::MissingParameters {
+                position,
+                command,
+                expected,
+                available,
+            } => write!(
+                f,
+                "{:?} at {} needs {} parameters but only {} are left",
+                command, position, expected, available
+            ),
+            GeometryError::TrailingData { position } => {
+                write!(f, "unexpected data after the points at {}", position)
+            }
+        }
+    }
+}
+
+impl std::error::Error for GeometryError {}
+
+// Walks the command and parameter integers of a geometry. The cursor carries over from one command to the next,
+// as every parameter is a delta to the previous position.
+pub struct CommandReader<'a> {
+    geometry: &'a [u32],
+    position: usize,
+    cursor: TilePoint,
+}
+
+impl<'a> CommandReader<'a> {
+    pub fn new(geometry: &'a [u32]) -> CommandReader<'a> {
+        CommandReader {
+            geometry,
+            position: 0,
+            cursor: (0, 0),
+        }
+    }
+
+    pub fn is_done(&self) -> bool {
+        self.position >= self.geometry.len()
+    }
+
+    // Reads the next command, which has to be `expected`, and returns its count. MoveTo and LineTo need at least
+    // one point and ClosePath has a count of exactly 1.
+    pub fn expect(&mut self, expected: Command) -> Result<usize, GeometryError> {
+        let position = self.position;
+        let command_data = match self.geometry.get(position) {
+            Some(command_data) => *command_data,
+            None => return Err(GeometryError::UnexpectedEnd { expected }),
+        };
+        self.position += 1;
+
+        // The 3 lowest bits of the command encode the type, the rest are the repeat count.
+        let id = command_data & 0x7;
+        let count = (command_data >> 3) as usize;
+        let command = match Command::from_id(id) {
+            Some(command) => command,
+            None => return Err(GeometryError::UnknownCommand { position, id }),
+        };
+        if command != expected {
+            return Err(GeometryError::UnexpectedCommand {
+                position,
+                expected,
+                found: command,
+            });
+        }
+
+        let valid_count = match command {
+            Command::MoveTo | Command::LineTo => count > 0,
+            Command::ClosePath => count == 1,
+        };
+        if !valid_count {
+            return Err(GeometryError::InvalidCount {
+                position,
+                command,
+                count,
+            });
+        }
+        if command != Command::ClosePath {
+            // For each MoveTo and LineTo repetition there are 2 parameter integers.
+            let available = self.geometry.len() - self.position;
+            if available < 2 * count {
+                return Err(GeometryError::MissingParameters {
+                    position,
+                    command,
+                    expected: 2 * count,
+                    available,
+                });
+            }
+        }
+        Ok(count)
+    }
+
+    // Moves the cursor by the next parameter pair. Only valid after `expect` returned a count of MoveTo or LineTo
+    // and at most that many times.
+    pub fn point(&mut self) -> TilePoint {
+        let param0 = self.geometry[self.position] as i64;
+        let param1 = self.geometry[self.position + 1] as i64;
+        self.position += 2;
+        // The parameters are zigzag-encoded deltas for x and y of the cursor.
+        self.cursor.0 += (param0 >> 1) ^ (-(param0 & 1));
+        self.cursor.1 += (param1 >> 1) ^ (-(param1 & 1));
+        self.cursor
+    }
+}
+
+// The points of a point geometry, which is a single MoveTo.
+pub fn decode_points(geometry: &[u32]) -> Result<Vec<TilePoint>, GeometryError> {
+    let mut reader = CommandReader::new(geometry);
+    if reader.is_done() {
+        return Ok(vec![]);
+    }
+    let count = reader.expect(Command::MoveTo)?;
+    let points = (0..count).map(|_| reader.point()).collect();
+    if !reader.is_done() {
+        return Err(GeometryError::TrailingData {
+            position: reader.position,
+        });
+    }
+    Ok(points)
+}
+
+// The lines of a line string geometry, each a MoveTo to its first point and a LineTo through the rest.
+pub fn decode_line_strings(geometry: &[u32]) -> Result<Vec<Vec<TilePoint>>, GeometryError> {
+    let mut reader = CommandReader::new(geometry);
+    let mut lines = vec![];
+    while !reader.is_done() {
+        let position = reader.position;
+        let count = reader.expect(Command::MoveTo)?;
+        if count != 1 {
+            return Err(GeometryError::InvalidCount {
+                position,
+                command: Command::MoveTo,
+                count,
+            });
+        }
+        let mut line = vec![reader.point()];
+        let count = reader.expect(Command::LineTo)?;
+        line.extend((0..count).map(|_| reader.point()));
+        lines.push(line);
+    }
+    Ok(lines)
+}
+
+// The rings of a polygon geometry, each a MoveTo, a LineTo through at least two more points and a ClosePath. The
+// rings are returned without repeating their first point at the end.
+pub fn decode_rings(geometry: &[u32]) -> Result<Vec<Vec<TilePoint>>, GeometryError> {
+    let mut reader = CommandReader::new(geometry);
+    let mut rings = vec![];
+    while !reader.is_done() {
+        let position = reader.position;
+        let count = reader.expect(Command::MoveTo)?;
+        if count != 1 {
+            return Err(GeometryError::InvalidCount {
+                position,
+                command: Command::MoveTo,
+                count,
+            });
+        }
+        let mut ring = vec![reader.point()];
+        let position = reader.position;
+        let count = reader.expect(Command::LineTo)?;
+        if count < 2 {
+            return Err(GeometryError::InvalidCount {
+                position,
+                command: Command::LineTo,
+                count,
+            });
+        }
+        ring.extend((0..count).map(|_| reader.point()));
+        reader.expect(Command::ClosePath)?;
+        rings.push(ring);
+    }
+    Ok(rings)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::test_util::{command, zigzag};
+    use super::*;
+
+    const MOVE_TO: u32 = 1;
+    const LINE_TO: u32 = 2;
+    const CLOSE_PATH: u32 = 7;
+
+    #[test]
+    fn points_follow_the_cursor() {
+        let geometry = vec![
+            command(MOVE_TO, 2),
+            zigzag(5),
+            zigzag(7),
+            zigzag(-2),
+            zigzag(3),
+        ];
+        assert_eq!(decode_points(&geometry), Ok(vec![(5, 7), (3, 10)]));
+        assert_eq!(decode_points(&[]), Ok(vec![]));
+    }
+
+    #[test]
+    fn points_are_a_single_move_to() {
+        let geometry = vec![command(MOVE_TO, 1), 2, 2, command(MOVE_TO, 1), 2, 2];
+        assert_eq!(
+            decode_points(&geometry),
+            Err(GeometryError::TrailingData { position: 3 })
+        );
+    }
+
+    #[test]
+    fn the_cursor_carries_over_between_lines() {
+        let geometry = vec![
+            command(MOVE_TO, 1),
+            zigzag(2),
+            zigzag(2),
+            command(LINE_TO, 2),
+            zigzag(0),
+            zigzag(8),
+            zigzag(8),
+            zigzag(0),
+            command(MOVE_TO, 1),
+            zigzag(-10),
+            zigzag(-10),
+            command(LINE_TO, 1),
+            zigzag(1),
+            zigzag(1),
+        ];
+        assert_eq!(
+            decode_line_strings(&geometry),
+            Ok(vec![vec![(2, 2), (2, 10), (10, 10)], vec![(0, 0), (1, 1)]])
+        );
+    }
+
+    #[test]
+    fn lines_need_a_line_to() {
+        let geometry = vec![command(MOVE_TO, 1), 2, 2, command(MOVE_TO, 1), 2, 2];
+        assert_eq!(
+            decode_line_strings(&geometry),
+            Err(GeometryError::UnexpectedCommand {
+                position: 3,
+                expected: Command::LineTo,
+                found: Command::MoveTo,
+            })
+        );
+        assert_eq!(
+            decode_line_strings(&[command(MOVE_TO, 1), 2, 2]),
+            Err(GeometryError::UnexpectedEnd {
+                expected: Command::LineTo
+            })
+        );
+    }
+
+    #[test]
+    fn lines_start_with_a_single_move_to() {
+        let geometry = vec![command(MOVE_TO, 2), 2, 2, 2, 2, command(LINE_TO, 1), 2, 2];
+        assert_eq!(
+            decode_line_strings(&geometry),
+            Err(GeometryError::InvalidCount {
+                position: 0,
+                command: Command::MoveTo,
+                count: 2,
+            })
+        );
+    }
+
+    #[test]
+    fn rings_are_closed() {
+        let ring = vec![
+            command(MOVE_TO, 1),
+            zigzag(0),
+            zigzag(0),
+            command(LINE_TO, 2),
+            zigzag(10),
+            zigzag(0),
+            zigzag(0),
+            zigzag(10),
+            command(CLOSE_PATH, 1),
+        ];
+        let mut geometry = ring.clone();
+        geometry.extend(&ring);
+        assert_eq!(
+            decode_rings(&geometry),
+            Ok(vec![
+                vec![(0, 0), (10, 0), (10, 10)],
+                vec![(10, 10), (20, 10), (20, 20)]
+            ])
+        );
+
+        let unclosed = &ring[..ring.len() - 1];
+        assert_eq!(
+            decode_rings(unclosed),
+            Err(GeometryError::UnexpectedEnd {
+                expected: Command::ClosePath
+            })
+        );
+    }
+
+    #[test]
+    fn rings_have_at_least_three_points() {
+        let geometry = vec![
+            command(MOVE_TO, 1),
+            0,
+            0,
+            command(LINE_TO, 1),
+            2,
+            2,
+            command(CLOSE_PATH, 1),
+        ];
+        assert_eq!(
+            decode_rings(&geometry),
+            Err(GeometryError::InvalidCount {
+                position: 3,
+                command: Command::LineTo,
+                count: 1,
+            })
+        );
+    }
+
+    #[test]
+    fn close_path_has_a_count_of_one() {
+        let geometry = vec![
+            command(MOVE_TO, 1),
+            0,
+            0,
+            command(LINE_TO, 2),
+            2,
+            0,
+            0,
+            2,
+            command(CLOSE_PATH, 2),
+        ];
+        assert_eq!(
+            decode_rings(&geometry),
+            Err(GeometryError::InvalidCount {
+                position: 8,
+                command: Command::ClosePath,
+                count: 2,
+            })
+        );
+    }
+
+    #[test]
+    fn unknown_commands_are_rejected() {
+        assert_eq!(
+            decode_line_strings(&[command(3, 1), 2, 2]),
+            Err(GeometryError::UnknownCommand { position: 0, id: 3 })
+        );
+    }
+
+    #[test]
+    fn truncated_parameters_are_rejected() {
+        assert_eq!(
+            decode_points(&[command(MOVE_TO, 2), 2, 2, 2]),
+            Err(GeometryError::MissingParameters {
+                position: 0,
+                command: Command::MoveTo,
+                expected: 4,
+                available: 3,
+            })
+        );
+        assert_eq!(
+            decode_rings(&[command(MOVE_TO, 0)]),
+            Err(GeometryError::InvalidCount {
+                position: 0,
+                command: Command::MoveTo,
+                count: 0,
+            })
+        );
+    }
+}
@@ -0,0 +1,345 @@
+use super::commands::{self, GeometryError, TilePoint};
+use super::rings::{self, signed_area};
+
+// The geometry types of the vector tile specification, without UNKNOWN, which has no geometry to decode.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum GeometryType {
+    Point,
+    LineString,
+    Polygon,
+}
+
+// One exterior ring and the holes cut into it. Rings do not repeat their first point at the end.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Polygon<P> {
+    pub exterior: Vec<P>,
+    pub interiors: Vec<Vec<P>>,
+}
+
+impl<P> Polygon<P> {
+    // The exterior ring followed by the holes.
+    pub fn rings(&self) -> impl Iterator<Item = &Vec<P>> {
+        std::iter::once(&self.exterior).chain(self.interiors.iter())
+    }
+
+    pub fn map<Q>(&self, f: impl Fn(&P) -> Q) -> Polygon<Q> {
+        Polygon {
+            exterior: self.exterior.iter().map(&f).collect(),
+            interiors: self
+                .interiors
+                .iter()
+                .map(|ring| ring.iter().map(&f).collect())
+                .collect(),
+        }
+    }
+}
+
+// The decoded geometry of a feature, either in tile coordinates (`TilePoint`) or normalized to the tile
+// (`glm::Vec2`).
+#[derive(Clone, Debug, PartialEq)]
+pub enum Geometry<P> {
+    Points(Vec<P>),
+    LineStrings(Vec<Vec<P>>),
+    Polygons(Vec<Polygon<P>>),
+}
+
+impl<P> Geometry<P> {
+    pub fn map<Q>(&self, f: impl Fn(&P) -> Q) -> Geometry<Q> {
+        match self {
+            Geometry::Points(points) => Geometry::Points(points.iter().map(f).collect()),
+            Geometry::LineStrings(lines) => Geometry::LineStrings(
+                lines
+                    .iter()
+                    .map(|line| line.iter().map(&f).collect())
+                    .collect(),
+            ),
+            Geometry::Polygons(polygons) => {
+                Geometry::Polygons(polygons.iter().map(|polygon| polygon.map(&f)).collect())
+            }
+        }
+    }
+}
+
+impl Geometry<TilePoint> {
+    // The same geometry with the tile spanning 0 to 1 on both axes, which is what the mesh builders expect.
+    pub fn normalize(&self, extent: u32) -> Geometry<glm::Vec2> {
+        self.map(|point| normalize_point(*point, extent))
+    }
+}
+
+// The extent is the width of the tile, so a point at the extent lies on the edge shared with the next tile.
+pub fn normalize_point(point: TilePoint, extent: u32) -> glm::Vec2 {
+    let scale = 1.0 / extent as f32;
+    glm::vec2((point.0 as f32) * scale, (point.1 as f32) * scale)
+}
+
+// Decodes the command integers of a feature as the given geometry type, in tile coordinates. `classification` only
+// matters for polygons.
+pub fn decode(
+    geometry_type: GeometryType,
+    geometry: &[u32],
+    classification: RingClassification,
+) -> Result<Geometry<TilePoint>, GeometryError> {
+    Ok(match geometry_type {
+        GeometryType::Point => Geometry::Points(commands::decode_points(geometry)?),
+        GeometryType::LineString => Geometry::LineStrings(commands::decode_line_strings(geometry)?),
+        GeometryType::Polygon => Geometry::Polygons(decode_polygons(geometry, classification)?),
+    })
+}
+
+// How the rings of a polygon are told apart into exterior rings and holes.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum RingClassification {
+    // By their winding order as version 2 of the specification requires: exterior rings are clockwise with Y
+    // pointing down and every hole follows its exterior ring.
+    Winding,
+    // By which rings lie inside which, for version 1 tiles whose winding order was unspecified. A ring inside one
+    // other ring is a hole, a ring inside a hole is an exterior ring again, and so on.
+    Containment,
+}
+
+impl RingClassification {
+    pub fn for_version(version: u32) -> RingClassification {
+        if version >= 2 {
+            RingClassification::Winding
+        } else {
+            RingClassification::Containment
+        }
+    }
+}
+
+// Groups the rings of a polygon geometry into polygons, repairing rings earcut cannot triangulate on the way.
+// Exterior rings come out with a positive area and holes with a negative one, whatever their winding was in the
+// tile. Some generators reverse the winding order of whole tiles, so when the first ring is not an exterior ring
+// the winding is not trusted and the rings are classified by containment.
+pub fn decode_polygons(
+    geometry: &[u32],
+    classification: RingClassification,
+) -> Result<Vec<Polygon<TilePoint>>, GeometryError> {
+    let rings = commands::decode_rings(geometry)?;
+    let winding_is_reliable = match rings.first() {
+        Some(ring) => signed_area(ring) > 0.0,
+        None => true,
+    };
+    Ok(match classification {
+        RingClassification::Winding if winding_is_reliable => classify_by_winding(&rings),
+        _ => classify_by_containment(&rings),
+    })
+}
+
+fn classify_by_winding(rings: &[Vec<TilePoint>]) -> Vec<Polygon<TilePoint>> {
+    let mut polygons: Vec<Polygon<TilePoint>> = vec![];
+    // The polygons made from the last exterior ring, which is more than one when it had to be split.
+    let mut current = 0;
+    for ring in rings {
+        let is_exterior = signed_area(ring) > 0.0;
+        let repaired = rings::repair_ring(ring);
+        if is_exterior {
+            current = polygons.len();
+            polygons.extend(repaired.into_iter().map(|exterior| Polygon {
+                exterior: rings::oriented(exterior, true),
+                interiors: vec![],
+            }));
+            continue;
+        }
+
+        // A hole outside of its exterior ring has nothing to be cut out of and is dropped, as are rings without any
+        // area.
+        let candidates = &mut polygons[current..];
+        for hole in repaired {
+            let parent = candidates
+                .iter_mut()
+                .find(|polygon| rings::contains(&polygon.exterior, &hole));
+            if let Some(parent) = parent {
+                parent.interiors.push(rings::oriented(hole, false));
+            }
+        }
+    }
+    polygons
+}
+
+fn classify_by_containment(rings: &[Vec<TilePoint>]) -> Vec<Polygon<TilePoint>> {
+    let repaired: Vec<Vec<TilePoint>> = rings
+        .iter()
+        .flat_map(|ring| rings::repair_ring(ring))
+        .collect();
+
+    // Larger rings are placed first, so the rings around a ring are placed before it and the last of those is
+    // the smallest one, its parent.
+    let mut order: Vec<usize> = (0..repaired.len()).collect();
+    order.sort_by(|a, b| {
+        let area = |i: &usize| signed_area(&repaired[*i]).abs();
+        area(b).partial_cmp(&area(a)).unwrap()
+    });
+    let mut parents: Vec<Option<usize>> = vec![None; repaired.len()];
+    let mut depths = vec![0; repaired.len()];
+    for (placed, &i) in order.iter().enumerate() {
+        parents[i] = order[..placed]
+            .iter()
+            .rev()
+            .find(|j| rings::contains(&repaired[**j], &repaired[i]))
+            .copied();
+        depths[i] = parents[i].map_or(0, |parent| depths[parent] + 1);
+    }
+
+    // Rings at an even depth are exterior rings, the others holes of their parent. Both keep the order of the tile.
+    let mut polygons: Vec<Polygon<TilePoint>> = vec![];
+    let mut polygon_of = vec![None; repaired.len()];
+    for (i, ring) in repaired.iter().enumerate() {
+        if depths[i] % 2 == 0 {
+            polygon_of[i] = Some(polygons.len());
+            polygons.push(Polygon {
+                exterior: rings::oriented(ring.clone(), true),
+                interiors: vec![],
+            });
+        }
+    }
+    for (i, ring) in repaired.into_iter().enumerate() {
+        if let Some(polygon) = parents[i].and_then(|parent| polygon_of[parent]) {
+            if depths[i] % 2 == 1 {
+                polygons[polygon]
+                    .interiors
+                    .push(rings::oriented(ring, false));
+            }
+        }
+    }
+    polygons
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::test_util::{command, encode_rings, reversed, square};
+    use super::*;
+
+    #[test]
+    fn holes_belong_to_the_exterior_before_them() {
+        let outer = square(0, 0, 10, 10);
+        let hole = reversed(square(2, 2, 4, 4));
+        let other = square(20, 20, 30, 30);
+        let geometry = encode_rings(&[outer.clone(), hole.clone(), other.clone()]);
+        assert_eq!(
+            decode_polygons(&geometry, RingClassification::Winding),
+            Ok(vec![
+                Polygon {
+                    exterior: outer,
+                    interiors: vec![hole],
+                },
+                Polygon {
+                    exterior: other,
+                    interiors: vec![],
+                },
+            ])
+        );
+    }
+
+    #[test]
+    fn reversed_winding_is_classified_by_containment() {
+        // Every ring the wrong way round, which breaks the winding order right from the first ring.
+        let outer = reversed(square(0, 0, 10, 10));
+        let hole = square(2, 2, 4, 4);
+        let geometry = encode_rings(&[outer.clone(), hole.clone()]);
+        assert_eq!(
+            decode_polygons(&geometry, RingClassification::Winding),
+            Ok(vec![Polygon {
+                exterior: reversed(outer),
+                interiors: vec![reversed(hole)],
+            }])
+        );
+    }
+
+    #[test]
+    fn containment_finds_islands_in_holes() {
+        // A version 1 tile with the rings in no particular order or winding: a lake with an island, and the land
+        // around it listed last.
+        let lake = square(2, 2, 8, 8);
+        let island = reversed(square(4, 4, 6, 6));
+        let land = square(0, 0, 10, 10);
+        let geometry = encode_rings(&[lake.clone(), island.clone(), land.clone()]);
+        assert_eq!(
+            decode_polygons(&geometry, RingClassification::Containment),
+            Ok(vec![
+                Polygon {
+                    exterior: reversed(island),
+                    interiors: vec![],
+                },
+                Polygon {
+                    exterior: land,
+                    interiors: vec![reversed(lake)],
+                },
+            ])
+        );
+    }
+
+    #[test]
+    fn stray_and_flat_holes_are_dropped() {
+        let outer = square(0, 0, 10, 10);
+        let flat = vec![(0, 0), (10, 0), (10, 0), (0, 0)];
+        let hole = reversed(square(2, 2, 4, 4));
+        let stray = reversed(square(20, 20, 30, 30));
+        let geometry = encode_rings(&[outer.clone(), flat, hole.clone(), stray]);
+        assert_eq!(
+            decode_polygons(&geometry, RingClassification::Winding),
+            Ok(vec![Polygon {
+                exterior: outer,
+                interiors: vec![hole],
+            }])
+        );
+    }
+
+    #[test]
+    fn crossing_exteriors_become_one_polygon_per_loop() {
+        let bow_tie = vec![(0, 0), (10, 10), (10, 0), (0, 10)];
+        let geometry = encode_rings(&[bow_tie]);
+        for classification in &[RingClassification::Winding, RingClassification::Containment] {
+            let polygons = decode_polygons(&geometry, *classification).unwrap();
+            assert_eq!(polygons.len(), 2);
+            for polygon in polygons {
+                assert_eq!(signed_area(&polygon.exterior), 25.0);
+            }
+        }
+    }
+
+    #[test]
+    fn rings_lists_the_exterior_first() {
+        let polygon = Polygon {
+            exterior: square(0, 0, 10, 10),
+            interiors: vec![reversed(square(2, 2, 4, 4))],
+        };
+        let rings: Vec<_> = polygon.rings().collect();
+        assert_eq!(rings, vec![&polygon.exterior, &polygon.interiors[0]]);
+    }
+
+    #[test]
+    fn decode_follows_the_geometry_type() {
+        // MoveTo(2, 4) LineTo(12, 4).
+        let geometry = vec![command(1, 1), 4, 8, command(2, 1), 20, 0];
+        assert_eq!(
+            decode(
+                GeometryType::LineString,
+                &geometry,
+                RingClassification::Winding
+            ),
+            Ok(Geometry::LineStrings(vec![vec![(2, 4), (12, 4)]]))
+        );
+        assert!(decode(GeometryType::Point, &geometry, RingClassification::Winding).is_err());
+        assert!(decode(
+            GeometryType::Polygon,
+            &geometry,
+            RingClassification::Winding
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn normalize_spans_the_tile() {
+        let geometry = Geometry::Points(vec![(0, 0), (10, 5), (-10, 20)]);
+        assert_eq!(
+            geometry.normalize(10),
+            Geometry::Points(vec![
+                glm::vec2(0.0, 0.0),
+                glm::vec2(1.0, 0.5),
+                glm::vec2(-1.0, 2.0)
+            ])
+        );
+    }
+}
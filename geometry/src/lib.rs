@@ -0,0 +1,16 @@
+// Decoding of Mapbox Vector Tile geometry and its tessellation into triangle meshes, along with the addressing and
+// extents of tiles. Everything here runs on the CPU only, so it can be tested and used without a GL context.
+extern crate nalgebra_glm as glm;
+
+pub mod clip;
+pub mod commands;
+pub mod decoder;
+pub mod line_strings;
+pub mod normals;
+pub mod points;
+pub mod polygons;
+pub mod rings;
+pub mod tile_address;
+
+#[cfg(test)]
+mod test_util;
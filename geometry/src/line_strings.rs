@@ -0,0 +1,214 @@
+use super::clip::ClipBox;
+use super::normals::calculate_normals;
+use super::polygons;
+
+// Widens every line of a decoded line string geometry, in coordinates normalized to the tile, into a flat band.
+// With a clip box only the parts of the lines inside it are built.
+pub fn build_line_strings(
+    lines: &[Vec<glm::Vec2>],
+    thickness: f32,
+    height: f32,
+    clip: Option<ClipBox>,
+) -> polygons::OutputMesh {
+    let lines: Vec<Vec<glm::Vec2>> = match clip {
+        Some(clip) => lines.iter().flat_map(|line| clip.clip_line(line)).collect(),
+        None => lines.to_vec(),
+    };
+
+    let mut output_mesh = polygons::OutputMesh::new();
+    for line in &lines {
+        let (mut vertices, indices, mut uvs, mut normals) = generate_path(line, thickness, height);
+        if vertices.len() >= 3 {
+            output_mesh.add_elements(&mut vertices, &indices, &mut uvs, &mut normals);
+        }
+    }
+    output_mesh
+}
+
+pub fn generate_path(
+    path: &[glm::Vec2],
+    thickness: f32,
+    height: f32,
+) -> (Vec<glm::Vec3>, Vec<u32>, Vec<glm::Vec2>, Vec<glm::Vec3>) {
+    let mut vertices: Vec<glm::Vec3> = Vec::new();
+    let mut uvs: Vec<glm::Vec2> = Vec::new();
+    let mut indices: Vec<u32> = vec![];
+
+    // Repeated points have no direction to widen the line in, and a single point is no line at all.
+    let mut path = path.to_vec();
+    path.dedup();
+    if path.len() < 2 {
+        return (vertices, indices, uvs, vec![]);
+    }
+
+    let mut vertex_index: u32 = 0;
+    let length = path.len();
+
+    let start_pos = path[0];
+    let next_pos = path[1];
+
+    let forward = next_pos - start_pos;
+    let forward = glm::normalize(&forward);
+    let left = glm::vec2(-forward.y, forward.x);
+
+    let point_a = start_pos + left * thickness;
+    let point_b = start_pos - left * thickness;
+
+    vertices.push(glm::vec3(point_a.x, height, point_a.y));
+    vertices.push(glm::vec3(point_b.x, height, point_b.y));
+
+    uvs.push(glm::vec2(0.0, 0.0));
+    uvs.push(glm::vec2(0.0, 1.0));
+
+    for i in 1..length {
+        let start_pos = path[i - 1];
+        let next_pos = path[i];
+
+        let forward = next_pos - start_pos;
+        let forward = glm::normalize(&forward);
+        let left = glm::vec2(-forward.y, forward.x);
+
+        // Each segment ends at its own point, widened sideways to the segment.
+        let point_a = next_pos + left * thickness;
+        let point_b = next_pos - left * thickness;
+
+        vertices.push(glm::vec3(point_a.x, height, point_a.y));
+        vertices.push(glm::vec3(point_b.x, height, point_b.y));
+
+        uvs.push(glm::vec2(i as f32 / (length - 1) as f32, 0.0));
+        uvs.push(glm::vec2(i as f32 / (length - 1) as f32, 1.0));
+
+        indices.push(vertex_index + 3);
+        indices.push(vertex_index + 1);
+        indices.push(vertex_index);
+        indices.push(vertex_index + 2);
+        indices.push(vertex_index + 3);
+        indices.push(vertex_index);
+
+        vertex_index += 2;
+    }
+
+    let normals = calculate_normals(&vertices, &indices);
+    (vertices, indices, uvs, normals)
+}
+
+#[derive(Clone, Debug)]
+pub struct LineOptions {
+    pub width: f32,
+    pub height: f32,
+    // How far lines reach past the edge of the tile, so the ends of lines from neighbouring tiles overlap instead
+    // of leaving a notch where a line crosses the edge at an angle.
+    pub buffer: f32,
+}
+
+impl LineOptions {
+    pub fn new(height: f32, width: f32, buffer: f32) -> Self {
+        LineOptions {
+            height,
+            width,
+            buffer,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::decoder::{decode, Geometry, GeometryType, RingClassification};
+    use super::*;
+
+    fn faces_up(vertices: &[glm::Vec3], indices: &[u32]) -> bool {
+        indices.chunks(3).all(|t| {
+            let (a, b, c) = (
+                vertices[t[0] as usize],
+                vertices[t[1] as usize],
+                vertices[t[2] as usize],
+            );
+            glm::cross(&(b - a), &(c - a)).y > 0.0
+        })
+    }
+
+    #[test]
+    fn straight_line_spans_both_ends() {
+        let path = vec![glm::vec2(0.0, 0.5), glm::vec2(1.0, 0.5)];
+        let (vertices, indices, uvs, normals) = generate_path(&path, 0.1, 0.2);
+        assert_eq!(vertices.len(), 4);
+        assert_eq!(indices.len(), 6);
+        assert_eq!(uvs.len(), vertices.len());
+        assert_eq!(normals.len(), vertices.len());
+        assert!(faces_up(&vertices, &indices));
+
+        let xs: Vec<f32> = vertices.iter().map(|v| v.x).collect();
+        assert_eq!(xs, vec![0.0, 0.0, 1.0, 1.0]);
+        for vertex in &vertices {
+            assert_eq!(vertex.y, 0.2);
+            assert!(((vertex.z - 0.5).abs() - 0.1).abs() < 1e-6);
+        }
+    }
+
+    #[test]
+    fn bent_line_faces_up() {
+        let path = vec![
+            glm::vec2(0.0, 0.0),
+            glm::vec2(1.0, 0.0),
+            glm::vec2(1.0, 1.0),
+            glm::vec2(0.0, 2.0),
+        ];
+        let (vertices, indices, uvs, _) = generate_path(&path, 0.1, 0.0);
+        assert_eq!(vertices.len(), 8);
+        assert_eq!(indices.len(), 18);
+        assert!(faces_up(&vertices, &indices));
+        assert_eq!(uvs.first().unwrap().x, 0.0);
+        assert_eq!(uvs.last().unwrap().x, 1.0);
+    }
+
+    #[test]
+    fn degenerate_lines_build_nothing() {
+        let point = vec![glm::vec2(0.5, 0.5)];
+        assert!(generate_path(&point, 0.1, 0.0).0.is_empty());
+        let repeated = vec![glm::vec2(0.5, 0.5); 3];
+        assert!(generate_path(&repeated, 0.1, 0.0).0.is_empty());
+        assert!(generate_path(&[], 0.1, 0.0).0.is_empty());
+
+        let stutter = vec![
+            glm::vec2(0.0, 0.0),
+            glm::vec2(0.0, 0.0),
+            glm::vec2(1.0, 0.0),
+            glm::vec2(1.0, 0.0),
+        ];
+        let (vertices, _, _, normals) = generate_path(&stutter, 0.1, 0.0);
+        assert_eq!(vertices.len(), 4);
+        assert!(normals.iter().all(|n| !n.x.is_nan()));
+    }
+
+    #[test]
+    fn every_line_string_is_built() {
+        // Two line strings of two points each: MoveTo(0, 0) LineTo(10, 0) MoveTo(0, 5) LineTo(10, 5).
+        let geometry = vec![9, 0, 0, 10, 20, 0, 9, 19, 10, 10, 20, 0];
+        let lines = match decode(
+            GeometryType::LineString,
+            &geometry,
+            RingClassification::Winding,
+        )
+        .unwrap()
+        .normalize(10)
+        {
+            Geometry::LineStrings(lines) => lines,
+            geometry => panic!("decoded {:?}", geometry),
+        };
+        let mesh = build_line_strings(&lines, 0.05, 0.0, None);
+        assert_eq!(mesh.vertices.len(), 8);
+        assert_eq!(mesh.indices.len(), 12);
+    }
+
+    #[test]
+    fn lines_are_clipped_to_the_tile() {
+        let lines = vec![vec![glm::vec2(-1.0, 0.5), glm::vec2(2.0, 0.5)]];
+        let mesh = build_line_strings(&lines, 0.05, 0.0, Some(ClipBox::tile(0.0)));
+        assert_eq!(mesh.vertices.len(), 4);
+        assert!(mesh.vertices.iter().all(|v| v.x >= 0.0 && v.x <= 1.0));
+
+        let mesh = build_line_strings(&lines, 0.05, 0.0, Some(ClipBox::tile(0.1)));
+        let min_x = mesh.vertices.iter().map(|v| v.x).fold(f32::MAX, f32::min);
+        assert!((min_x + 0.1).abs() < 1e-6);
+    }
+}
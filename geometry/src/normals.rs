@@ -0,0 +1,64 @@
+// Smooth vertex normals for an indexed triangle mesh, each face weighted by its area.
+pub fn calculate_normals(vertices: &[glm::Vec3], indices: &[u32]) -> Vec<glm::Vec3> {
+    let mut vertex_normals: Vec<glm::Vec3> = vec![glm::vec3(0.0, 0.0, 0.0); vertices.len()];
+    let mut index = 0;
+    if vertices.len() < 3 {
+        return vertex_normals;
+    }
+    while index < indices.len() {
+        let vertex_1 = indices[index];
+        let vertex_2 = indices[index + 1];
+        let vertex_3 = indices[index + 2];
+
+        let edge_12 = vertices[vertex_2 as usize] - vertices[vertex_1 as usize];
+        let edge_13 = vertices[vertex_3 as usize] - vertices[vertex_1 as usize];
+
+        let area_weighted_normal = glm::cross(&edge_12, &edge_13);
+
+        vertex_normals[vertex_1 as usize] += area_weighted_normal;
+        vertex_normals[vertex_2 as usize] += area_weighted_normal;
+        vertex_normals[vertex_3 as usize] += area_weighted_normal;
+        index += 3;
+    }
+
+    index = 0;
+    while index < vertex_normals.len() {
+        // Vertices only used by zero area triangles, or by none, have no direction and are left at zero.
+        vertex_normals[index] = vertex_normals[index]
+            .try_normalize(0.0)
+            .unwrap_or(glm::vec3(0.0, 0.0, 0.0));
+        index += 1;
+    }
+
+    vertex_normals
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn normals_follow_the_winding() {
+        let vertices = vec![
+            glm::vec3(0.0, 0.0, 0.0),
+            glm::vec3(0.0, 0.0, 1.0),
+            glm::vec3(1.0, 0.0, 0.0),
+        ];
+        let up = calculate_normals(&vertices, &[0, 1, 2]);
+        assert!(up.iter().all(|n| *n == glm::vec3(0.0, 1.0, 0.0)));
+        let down = calculate_normals(&vertices, &[0, 2, 1]);
+        assert!(down.iter().all(|n| *n == glm::vec3(0.0, -1.0, 0.0)));
+    }
+
+    #[test]
+    fn degenerate_and_unused_vertices_get_zero_normals() {
+        let vertices = vec![
+            glm::vec3(0.0, 0.0, 0.0),
+            glm::vec3(1.0, 0.0, 0.0),
+            glm::vec3(2.0, 0.0, 0.0),
+            glm::vec3(5.0, 5.0, 5.0),
+        ];
+        let normals = calculate_normals(&vertices, &[0, 1, 2]);
+        assert!(normals.iter().all(|n| *n == glm::vec3(0.0, 0.0, 0.0)));
+    }
+}
@@ -0,0 +1,193 @@
+use super::normals::calculate_normals;
+use super::polygons::OutputMesh;
+
+// Places a marker mesh on every point. A marker is a prism with `sides` sides, which gives a box for 4 sides,
+// a pillar for many sides and a cone (e.g. a tree) when `top_radius` is 0.
+pub fn generate_markers(points: &Vec<glm::Vec2>, options: &PointOptions) -> OutputMesh {
+    let mut output_mesh = OutputMesh::new();
+    for point in points {
+        let (mut vertices, indices, mut uvs, mut normals) = generate_marker(*point, options);
+        output_mesh.add_elements(&mut vertices, &indices, &mut uvs, &mut normals);
+    }
+    output_mesh
+}
+
+pub fn generate_marker(
+    center: glm::Vec2,
+    options: &PointOptions,
+) -> (Vec<glm::Vec3>, Vec<u32>, Vec<glm::Vec2>, Vec<glm::Vec3>) {
+    let mut vertices: Vec<glm::Vec3> = vec![];
+    let mut uvs: Vec<glm::Vec2> = vec![];
+    let mut indices: Vec<u32> = vec![];
+
+    let sides = options.sides.max(3);
+    let bottom = options.min_height;
+    let top = options.max_height;
+    let ring_point = |i: u32, radius: f32| {
+        let angle = (i as f32 / sides as f32) * std::f32::consts::PI * 2.0;
+        glm::vec2(
+            center.x + angle.cos() * radius,
+            center.y + angle.sin() * radius,
+        )
+    };
+
+    // Every side gets its own vertices so the normals stay flat. The sides of a cone are single triangles, as the
+    // second top vertex of a quad would only be part of a degenerate triangle and get no normal.
+    for i in 0..sides {
+        let b0 = ring_point(i, options.radius);
+        let b1 = ring_point(i + 1, options.radius);
+        let t0 = ring_point(i, options.top_radius);
+        let t1 = ring_point(i + 1, options.top_radius);
+
+        let index_offset = vertices.len() as u32;
+        vertices.push(glm::vec3(b0.x, bottom, b0.y));
+        vertices.push(glm::vec3(b1.x, bottom, b1.y));
+        vertices.push(glm::vec3(t0.x, top, t0.y));
+        uvs.push(glm::vec2(0.0, 0.0));
+        uvs.push(glm::vec2(1.0, 0.0));
+        indices.push(index_offset + 2);
+        indices.push(index_offset + 1);
+        indices.push(index_offset);
+
+        if options.top_radius > 0.0 {
+            vertices.push(glm::vec3(t1.x, top, t1.y));
+            uvs.push(glm::vec2(0.0, 1.0));
+            uvs.push(glm::vec2(1.0, 1.0));
+            indices.push(index_offset + 2);
+            indices.push(index_offset + 3);
+            indices.push(index_offset + 1);
+        } else {
+            uvs.push(glm::vec2(0.5, 1.0));
+        }
+    }
+
+    if options.top_radius > 0.0 {
+        let center_index = vertices.len() as u32;
+        vertices.push(glm::vec3(center.x, top, center.y));
+        uvs.push(glm::vec2(0.5, 0.5));
+        for i in 0..sides {
+            let p = ring_point(i, options.top_radius);
+            vertices.push(glm::vec3(p.x, top, p.y));
+            uvs.push(glm::vec2(
+                0.5 + 0.5 * (p.x - center.x) / options.top_radius,
+                0.5 + 0.5 * (p.y - center.y) / options.top_radius,
+            ));
+        }
+        for i in 0..sides {
+            indices.push(center_index);
+            indices.push(center_index + 1 + (i + 1) % sides);
+            indices.push(center_index + 1 + i);
+        }
+    }
+
+    let normals = calculate_normals(&vertices, &indices);
+    (vertices, indices, uvs, normals)
+}
+
+#[derive(Clone, Debug)]
+pub struct PointOptions {
+    pub radius: f32,
+    pub top_radius: f32,
+    pub max_height: f32,
+    pub min_height: f32,
+    pub sides: u32,
+}
+
+impl PointOptions {
+    pub fn new(radius: f32, top_radius: f32, max_height: f32, sides: u32) -> Self {
+        PointOptions {
+            radius,
+            top_radius,
+            max_height,
+            min_height: 0.0,
+            sides,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn markers(top_radius: f32, sides: u32) -> (Vec<glm::Vec2>, OutputMesh) {
+        let points = vec![glm::vec2(0.0, 0.0), glm::vec2(10.0, -5.0)];
+        let options = PointOptions::new(2.0, top_radius, 6.0, sides);
+        let mesh = generate_markers(&points, &options);
+        (points, mesh)
+    }
+
+    #[test]
+    fn every_marker_gets_its_own_vertices_and_indices() {
+        // Each side is a quad of 4 vertices and 2 triangles, or a single triangle for cones. A flat top adds a
+        // centre and a ring of triangles around it.
+        for (top_radius, sides, vertices, indices) in [
+            (2.0, 4, 4 * 4 + 1 + 4, 4 * 6 + 4 * 3),
+            (1.0, 8, 8 * 4 + 1 + 8, 8 * 6 + 8 * 3),
+            (0.0, 6, 6 * 3, 6 * 3),
+            (0.0, 2, 3 * 3, 3 * 3),
+        ]
+        .iter()
+        {
+            let (points, mesh) = markers(*top_radius, *sides);
+            let count = points.len();
+            assert_eq!(mesh.vertices.len(), vertices * count, "{} sides", sides);
+            assert_eq!(mesh.indices.len(), indices * count, "{} sides", sides);
+            assert_eq!(mesh.uvs.len(), mesh.vertices.len());
+            assert_eq!(mesh.normals.len(), mesh.vertices.len());
+            assert!(mesh
+                .indices
+                .iter()
+                .all(|i| (*i as usize) < mesh.vertices.len()));
+            // The second marker's indices point at its own vertices only.
+            let second = &mesh.indices[*indices..];
+            assert!(second.iter().all(|i| *i as usize >= *vertices));
+        }
+    }
+
+    #[test]
+    fn prisms_have_a_flat_top_and_cones_a_tip() {
+        let (points, prism) = markers(2.0, 4);
+        let (_, cone) = markers(0.0, 4);
+        for (mesh, top_radius) in [(prism, 2.0), (cone, 0.0)].iter() {
+            let first = &mesh.vertices[..mesh.vertices.len() / 2];
+            for vertex in first.iter().filter(|v| v.y == 6.0) {
+                let distance = glm::distance(&glm::vec2(vertex.x, vertex.z), &points[0]);
+                assert!(distance <= *top_radius + 1e-5, "{:?}", vertex);
+            }
+            for vertex in first.iter().filter(|v| v.y == 0.0) {
+                let distance = glm::distance(&glm::vec2(vertex.x, vertex.z), &points[0]);
+                assert!((distance - 2.0).abs() < 1e-5, "{:?}", vertex);
+            }
+            assert!(first.iter().all(|v| v.y == 0.0 || v.y == 6.0));
+        }
+    }
+
+    #[test]
+    fn normals_point_outward_and_up() {
+        for (top_radius, sides) in [(2.0, 4), (1.0, 8), (0.0, 8)].iter() {
+            let (points, mesh) = markers(*top_radius, *sides);
+            let per_marker = mesh.vertices.len() / points.len();
+            for (i, (vertex, normal)) in mesh.vertices.iter().zip(&mesh.normals).enumerate() {
+                let center = points[i / per_marker];
+                assert!((glm::length(normal) - 1.0).abs() < 1e-5, "{:?}", normal);
+                assert!(normal.y >= -1e-5, "{:?} at {:?}", normal, vertex);
+                let outward = glm::vec2(vertex.x - center.x, vertex.z - center.y);
+                if glm::length(&outward) > 1e-5 && normal.y < 1.0 - 1e-5 {
+                    assert!(
+                        glm::dot(&glm::vec2(normal.x, normal.z), &outward) > 0.0,
+                        "{:?} at {:?}",
+                        normal,
+                        vertex
+                    );
+                }
+            }
+            // Cones lean their sides up, prisms keep them level.
+            let side = mesh.normals[0];
+            if *top_radius < 2.0 {
+                assert!(side.y > 0.0, "{:?}", side);
+            } else {
+                assert!(side.y.abs() < 1e-5, "{:?}", side);
+            }
+        }
+    }
+}
@@ -0,0 +1,619 @@
+use super::clip::ClipBox;
+use super::decoder::Polygon;
+use super::normals::calculate_normals;
+use std::collections::HashSet;
+
+pub struct PolygonBuilder {
+    coordinates: Vec<f32>,
+    holes: Vec<usize>,
+    points_in_ring: u32,
+    points_in_polygon: u32,
+
+    last_point: glm::Vec2,
+    extrusion_vertices: Vec<glm::Vec3>,
+    extrusion_uvs: Vec<glm::Vec2>,
+    polygon_uvs: Vec<glm::Vec2>,
+    extrusion_indices: Vec<u32>,
+    u_coordinate_total: f32,
+    options: PolygonOptions,
+    pub output_mesh: OutputMesh,
+    // The sides of the clip box, in the order of `ClipBox::side_along`, that have walls built along them. Those
+    // are real walls as far as the polygons showed, but may still turn out to be cuts once the neighbouring tile
+    // on that side is known.
+    pub edge_walls: [bool; 4],
+}
+
+impl PolygonBuilder {
+    pub fn new(polygon_options: PolygonOptions) -> PolygonBuilder {
+        PolygonBuilder {
+            coordinates: vec![],
+            holes: vec![],
+            points_in_ring: 0,
+            points_in_polygon: 0,
+            last_point: glm::vec2(0.0, 0.0),
+            extrusion_vertices: vec![],
+            extrusion_uvs: vec![],
+            polygon_uvs: vec![],
+            extrusion_indices: vec![],
+            u_coordinate_total: 0.0,
+            options: polygon_options,
+            output_mesh: OutputMesh::new(),
+            edge_walls: [false; 4],
+        }
+    }
+
+    // Builds the roof and walls of one decoded polygon, in coordinates normalized to the tile. With a clip box in
+    // the options only the part of the polygon inside it is built.
+    pub fn add_polygon(&mut self, polygon: &Polygon<glm::Vec2>) {
+        self.add_polygons(std::slice::from_ref(polygon), &[]);
+    }
+
+    // Builds polygons that belong together, like the parts of a building, as one shape: where two of them meet no
+    // walls are built, as those would be inside the shape. Along the edge of the clip box walls are only left out
+    // where the shape goes on past it, either in the buffer the tile carries around it or in `beyond`, the parts of
+    // the shape in neighbouring tiles placed in the same coordinates.
+    pub fn add_polygons(&mut self, polygons: &[Polygon<glm::Vec2>], beyond: &[Polygon<glm::Vec2>]) {
+        let clip = self.options.clip;
+        let mut parts = vec![];
+        for polygon in polygons {
+            match &clip {
+                Some(clip) => {
+                    let exterior = clip.clip_ring(&polygon.exterior);
+                    if exterior.is_empty() {
+                        continue;
+                    }
+                    parts.push(Polygon {
+                        exterior,
+                        interiors: polygon
+                            .interiors
+                            .iter()
+                            .map(|ring| clip.clip_ring(ring))
+                            .filter(|ring| !ring.is_empty())
+                            .collect(),
+                    });
+                }
+                None => parts.push(polygon.clone()),
+            }
+        }
+
+        // Rings run in opposite directions along an edge they share.
+        let mut edges = HashSet::new();
+        for polygon in &parts {
+            for ring in polygon.rings() {
+                for (a, b) in ring_edges(ring) {
+                    edges.insert(edge_key(a, b));
+                }
+            }
+        }
+
+        let goes_on = |point: glm::Vec2| {
+            polygons
+                .iter()
+                .chain(beyond)
+                .any(|polygon| polygon_contains(polygon, point))
+        };
+        for polygon in &parts {
+            self.on_begin_polygon();
+            for ring in polygon.rings() {
+                self.on_begin_linear_ring();
+                for (i, (a, b)) in ring_edges(ring).enumerate() {
+                    if i == 0 {
+                        self.add_point(a, false);
+                    }
+                    let side = clip.and_then(|clip| clip.side_along(a, b));
+                    let wall = if edges.contains(&edge_key(b, a)) {
+                        false
+                    } else if let Some(side) = side {
+                        let past = (a + b) * 0.5 + ClipBox::outward(side) * EDGE_TOLERANCE;
+                        let wall = !goes_on(past);
+                        self.edge_walls[side] |= wall && self.options.build_walls;
+                        wall
+                    } else {
+                        true
+                    };
+                    // The last edge closes the ring, so there is a wall between the last and the first point.
+                    self.add_point(b, wall);
+                }
+            }
+            self.on_end_polygon();
+        }
+    }
+
+    pub fn add_uv(&mut self, uv: glm::Vec2) {
+        self.polygon_uvs.push(uv);
+    }
+
+    pub fn on_point(&mut self, point: glm::Vec2) {
+        self.add_point(point, true);
+    }
+
+    // `wall` is whether a wall is built from the previous point of the ring to this one.
+    fn add_point(&mut self, point: glm::Vec2, wall: bool) {
+        let max_height = self.options.max_height;
+        let min_height = self.options.min_height;
+
+        if self.options.build_walls && wall && self.points_in_ring > 0 {
+            let p0 = self.last_point;
+            let p1 = point;
+
+            let index_offset = self.extrusion_vertices.len();
+            // Increase the u coordinate by the 2D distance between the points.
+            let u_coordinate_next =
+                self.u_coordinate_total + glm::length(&glm::vec2(p1.x - p0.x, p1.y - p0.y));
+
+            let v0 = glm::vec3(p0.x, max_height, p0.y);
+            let v1 = glm::vec3(p1.x, max_height, p1.y);
+            let v2 = glm::vec3(p0.x, min_height, p0.y);
+            let v3 = glm::vec3(p1.x, min_height, p1.y);
+
+            self.extrusion_vertices.push(v0);
+            self.extrusion_vertices.push(v1);
+            self.extrusion_vertices.push(v2);
+            self.extrusion_vertices.push(v3);
+
+            let v_bottom = 0.0;
+            let v_top = 1.0;
+            let u_left = 0.0;
+            let u_right = 1.0;
+
+            self.extrusion_uvs.push(glm::vec2(u_right, v_top));
+            self.extrusion_uvs.push(glm::vec2(u_left, v_top));
+            self.extrusion_uvs.push(glm::vec2(u_right, v_bottom));
+            self.extrusion_uvs.push(glm::vec2(u_left, v_bottom));
+
+            self.extrusion_indices.push(index_offset as u32);
+            self.extrusion_indices.push((index_offset + 1) as u32);
+            self.extrusion_indices.push((index_offset + 3) as u32);
+            self.extrusion_indices.push(index_offset as u32);
+            self.extrusion_indices.push((index_offset + 3) as u32);
+            self.extrusion_indices.push((index_offset + 2) as u32);
+
+            self.u_coordinate_total = u_coordinate_next;
+        }
+
+        self.last_point = point;
+
+        self.coordinates.push(point.x);
+        self.coordinates.push(point.y);
+
+        self.points_in_ring += 1;
+        self.points_in_polygon += 1;
+    }
+
+    pub fn on_begin_linear_ring(&mut self) {
+        self.points_in_ring = 0;
+        self.u_coordinate_total = 0.0;
+        if self.points_in_polygon > 0 {
+            self.holes.push(self.points_in_polygon as usize);
+        }
+    }
+
+    pub fn on_begin_polygon(&mut self) {
+        self.coordinates.clear();
+        self.holes.clear();
+        self.extrusion_vertices.clear();
+        self.extrusion_uvs.clear();
+        self.extrusion_indices.clear();
+        self.polygon_uvs.clear();
+        self.points_in_polygon = 0;
+    }
+
+    pub fn on_end_polygon(&mut self) {
+        // First add vertices and indices for extrusions.
+        if !self.extrusion_vertices.is_empty() {
+            let mut verts = self.extrusion_vertices.clone();
+            let mut uvs = self.extrusion_uvs.clone();
+            let mut normals = calculate_normals(&verts, &self.extrusion_indices);
+            self.output_mesh.add_elements(
+                &mut verts,
+                &self.extrusion_indices,
+                &mut uvs,
+                &mut normals,
+            )
+        }
+
+        if !self.coordinates.is_empty() {
+            let coordinates: Vec<f64> = self.coordinates.iter().map(|n| *n as f64).collect();
+
+            let indices = earcutr::earcut(&coordinates, &self.holes, 2);
+            let mut indices: Vec<u32> = indices.iter().map(|n| *n as u32).collect();
+
+            let mut vertices: Vec<glm::Vec3> = vec![];
+
+            let mut uvs = if !self.polygon_uvs.is_empty() {
+                self.polygon_uvs.clone()
+            } else {
+                let mut uvs: Vec<glm::Vec2> = vec![];
+                let mut i = 0;
+                while i < coordinates.len() {
+                    uvs.push(glm::vec2(coordinates[i] as f32, coordinates[i + 1] as f32));
+                    i += 2;
+                }
+                uvs
+            };
+
+            let mut i = 0;
+            while i < coordinates.len() {
+                let v = glm::vec3(
+                    coordinates[i] as f32,
+                    self.options.max_height,
+                    coordinates[i + 1] as f32,
+                );
+                vertices.push(v);
+                i += 2
+            }
+
+            i = 0;
+            // flip order
+            while i < indices.len() {
+                indices.swap(i, i + 2);
+                i += 3;
+            }
+
+            let mut normals = calculate_normals(&vertices, &indices);
+            self.output_mesh
+                .add_elements(&mut vertices, &indices, &mut uvs, &mut normals)
+        }
+    }
+}
+
+// The edges of a closed ring, from every point to the next one and from the last point back to the first.
+fn ring_edges(ring: &[glm::Vec2]) -> impl Iterator<Item = (glm::Vec2, glm::Vec2)> + '_ {
+    ring.iter()
+        .zip(ring.iter().cycle().skip(1))
+        .map(|(a, b)| (*a, *b))
+}
+
+// How far past the edge of the clip box to look for the rest of a shape, well within one unit of a tile with an
+// extent of 4096.
+const EDGE_TOLERANCE: f32 = 1.0 / 65536.0;
+
+// Whether `point` lies inside the exterior of the polygon and outside its holes.
+fn polygon_contains(polygon: &Polygon<glm::Vec2>, point: glm::Vec2) -> bool {
+    polygon
+        .rings()
+        .enumerate()
+        .all(|(i, ring)| ring_contains(ring, point) == (i == 0))
+}
+
+// Casts a ray from the point towards +X and counts the edges of the ring it crosses.
+fn ring_contains(ring: &[glm::Vec2], point: glm::Vec2) -> bool {
+    let mut inside = false;
+    for (a, b) in ring_edges(ring) {
+        if (a.y > point.y) != (b.y > point.y)
+            && point.x < a.x + (point.y - a.y) / (b.y - a.y) * (b.x - a.x)
+        {
+            inside = !inside;
+        }
+    }
+    inside
+}
+
+// Points shared by two polygons are bit for bit the same, both come from the same tile coordinates or were cut at
+// the same edge of the clip box.
+fn edge_key(a: glm::Vec2, b: glm::Vec2) -> [u32; 4] {
+    [a.x.to_bits(), a.y.to_bits(), b.x.to_bits(), b.y.to_bits()]
+}
+
+#[derive(Clone, Debug)]
+pub struct PolygonOptions {
+    pub max_height: f32,
+    pub min_height: f32,
+    pub build_walls: bool,
+    pub clip: Option<ClipBox>,
+}
+
+#[derive(Default)]
+pub struct OutputMesh {
+    pub vertices: Vec<glm::Vec3>,
+    pub indices: Vec<u32>,
+    pub uvs: Vec<glm::Vec2>,
+    pub normals: Vec<glm::Vec3>,
+    // One colour per vertex. Left empty by the builders and filled in when features are merged.
+    pub colors: Vec<glm::Vec4>,
+}
+
+impl OutputMesh {
+    pub fn new() -> Self {
+        OutputMesh {
+            vertices: vec![],
+            indices: vec![],
+            uvs: vec![],
+            normals: vec![],
+            colors: vec![],
+        }
+    }
+
+    pub fn add_elements(
+        &mut self,
+        vertices: &mut Vec<glm::Vec3>,
+        indices: &Vec<u32>,
+        uvs: &mut Vec<glm::Vec2>,
+        normals: &mut Vec<glm::Vec3>,
+    ) {
+        let offset = self.vertices.len();
+        self.vertices.append(vertices);
+        self.normals.append(normals);
+        self.uvs.append(uvs);
+
+        for index in indices {
+            self.indices.push(offset as u32 + index);
+        }
+    }
+
+    // Appends `mesh` with every one of its vertices painted `color`.
+    pub fn add_colored(&mut self, mut mesh: OutputMesh, color: glm::Vec4) {
+        self.colors.resize(self.vertices.len() + mesh.vertices.len(), color);
+        self.add_elements(
+            &mut mesh.vertices,
+            &mesh.indices,
+            &mut mesh.uvs,
+            &mut mesh.normals,
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::commands::TilePoint;
+    use super::super::decoder::{decode_polygons, normalize_point, RingClassification};
+    use super::super::test_util::{encode_rings, reversed, square};
+    use super::*;
+
+    const HEIGHT: f32 = 0.5;
+
+    // With an extent of 10 the tile coordinates 0 to 10 span 0 to 1.
+    fn build(rings: &[&[TilePoint]], build_walls: bool) -> OutputMesh {
+        let geometry = encode_rings(rings);
+        let mut builder = PolygonBuilder::new(PolygonOptions {
+            max_height: HEIGHT,
+            min_height: 0.0,
+            build_walls,
+            clip: None,
+        });
+        for polygon in decode_polygons(&geometry, RingClassification::Winding).unwrap() {
+            builder.add_polygon(&polygon.map(|point| normalize_point(*point, 10)));
+        }
+        builder.output_mesh
+    }
+
+    fn triangles(mesh: &OutputMesh) -> Vec<[glm::Vec3; 3]> {
+        mesh.indices
+            .chunks(3)
+            .map(|t| {
+                [
+                    mesh.vertices[t[0] as usize],
+                    mesh.vertices[t[1] as usize],
+                    mesh.vertices[t[2] as usize],
+                ]
+            })
+            .collect()
+    }
+
+    fn face_normal(t: &[glm::Vec3; 3]) -> glm::Vec3 {
+        glm::cross(&(t[1] - t[0]), &(t[2] - t[0]))
+    }
+
+    // The area of the roof, which is the triangles lying flat at the top.
+    fn roof_area(mesh: &OutputMesh) -> f32 {
+        triangles(mesh)
+            .iter()
+            .filter(|t| t.iter().all(|v| v.y == HEIGHT))
+            .map(|t| {
+                let normal = face_normal(t);
+                assert!(normal.y > 0.0, "roof triangle {:?} faces down", t);
+                0.5 * normal.y
+            })
+            .sum()
+    }
+
+    fn assert_close(a: f32, b: f32) {
+        assert!((a - b).abs() < 1e-4, "{} != {}", a, b);
+    }
+
+    #[test]
+    fn square_faces_up() {
+        let mesh = build(&[&square(0, 0, 10, 10)], false);
+        assert_eq!(triangles(&mesh).len(), 2);
+        assert_close(roof_area(&mesh), 1.0);
+        for (vertex, normal) in mesh.vertices.iter().zip(&mesh.normals) {
+            if *normal != glm::vec3(0.0, 0.0, 0.0) {
+                assert_eq!(*normal, glm::vec3(0.0, 1.0, 0.0), "at {:?}", vertex);
+            }
+        }
+    }
+
+    #[test]
+    fn reversed_ring_faces_up() {
+        let mesh = build(&[&reversed(square(0, 0, 10, 10))], false);
+        assert_close(roof_area(&mesh), 1.0);
+    }
+
+    #[test]
+    fn holes_are_cut_out() {
+        let mesh = build(
+            &[&square(0, 0, 10, 10), &reversed(square(3, 3, 7, 7))],
+            false,
+        );
+        assert_close(roof_area(&mesh), 1.0 - 0.16);
+    }
+
+    #[test]
+    fn exterior_rings_start_new_polygons() {
+        let mesh = build(
+            &[
+                &square(0, 0, 4, 4),
+                &reversed(square(1, 1, 2, 2)),
+                &square(6, 6, 10, 10),
+            ],
+            false,
+        );
+        assert_close(roof_area(&mesh), 0.16 - 0.01 + 0.16);
+    }
+
+    #[test]
+    fn walls_face_outwards() {
+        let mesh = build(
+            &[&square(0, 0, 10, 10), &reversed(square(3, 3, 7, 7))],
+            true,
+        );
+        let walls: Vec<_> = triangles(&mesh)
+            .into_iter()
+            .filter(|t| t.iter().any(|v| v.y == 0.0))
+            .collect();
+        // Two triangles for each of the four sides of the outside and of the courtyard.
+        assert_eq!(walls.len(), 16);
+        for wall in walls {
+            let center = (wall[0] + wall[1] + wall[2]) / 3.0;
+            let normal = face_normal(&wall);
+            assert_eq!(normal.y, 0.0);
+            // Outer walls face away from the middle of the building, courtyard walls towards it.
+            let from_middle = glm::vec2(center.x - 0.5, center.z - 0.5);
+            let outside = from_middle.x.abs().max(from_middle.y.abs()) > 0.3;
+            let facing_middle = glm::dot(&glm::vec2(normal.x, normal.z), &from_middle) < 0.0;
+            assert_eq!(facing_middle, !outside, "wall {:?}", wall);
+        }
+    }
+
+    #[test]
+    fn polygons_are_clipped_to_the_tile() {
+        let mut builder = PolygonBuilder::new(PolygonOptions {
+            max_height: HEIGHT,
+            min_height: 0.0,
+            build_walls: true,
+            clip: Some(ClipBox::tile(0.0)),
+        });
+        // Half of the square, and half of the hole in it, lie in the tile to the right.
+        let geometry = encode_rings(&[&square(5, 0, 15, 10), &reversed(square(8, 2, 12, 8))]);
+        for polygon in decode_polygons(&geometry, RingClassification::Winding).unwrap() {
+            builder.add_polygon(&polygon.map(|point| normalize_point(*point, 10)));
+        }
+        let mesh = builder.output_mesh;
+        assert_close(roof_area(&mesh), 0.5 - 0.12);
+        assert!(mesh.vertices.iter().all(|v| v.x >= 0.5 && v.x <= 1.0));
+
+        let mut outside = PolygonBuilder::new(PolygonOptions {
+            max_height: HEIGHT,
+            min_height: 0.0,
+            build_walls: true,
+            clip: Some(ClipBox::tile(0.0)),
+        });
+        let geometry = encode_rings(&[&square(12, 0, 20, 10)]);
+        for polygon in decode_polygons(&geometry, RingClassification::Winding).unwrap() {
+            outside.add_polygon(&polygon.map(|point| normalize_point(*point, 10)));
+        }
+        assert!(outside.output_mesh.vertices.is_empty());
+    }
+
+    fn decode(rings: &[&[TilePoint]]) -> Vec<Polygon<glm::Vec2>> {
+        decode_polygons(&encode_rings(rings), RingClassification::Winding)
+            .unwrap()
+            .iter()
+            .map(|polygon| polygon.map(|point| normalize_point(*point, 10)))
+            .collect()
+    }
+
+    // Builds the rings as one shape, with `beyond` the rest of it in the neighbouring tiles.
+    fn build_shape(
+        rings: &[&[TilePoint]],
+        beyond: &[&[TilePoint]],
+        clip: Option<ClipBox>,
+    ) -> PolygonBuilder {
+        let mut builder = PolygonBuilder::new(PolygonOptions {
+            max_height: HEIGHT,
+            min_height: 0.0,
+            build_walls: true,
+            clip,
+        });
+        builder.add_polygons(&decode(rings), &decode(beyond));
+        builder
+    }
+
+    fn wall_count(rings: &[&[TilePoint]], clip: Option<ClipBox>) -> usize {
+        walls_of(&build_shape(rings, &[], clip))
+    }
+
+    fn walls_of(builder: &PolygonBuilder) -> usize {
+        // Two triangles for every wall.
+        triangles(&builder.output_mesh)
+            .iter()
+            .filter(|t| t.iter().any(|v| v.y == 0.0))
+            .count()
+            / 2
+    }
+
+    #[test]
+    fn no_walls_where_polygons_meet() {
+        assert_eq!(
+            wall_count(&[&square(0, 0, 5, 10), &square(5, 0, 10, 10)], None),
+            6
+        );
+        // A building filling the courtyard of another one.
+        assert_eq!(
+            wall_count(
+                &[
+                    &square(0, 0, 10, 10),
+                    &reversed(square(3, 3, 7, 7)),
+                    &square(3, 3, 7, 7)
+                ],
+                None
+            ),
+            4
+        );
+        assert_eq!(
+            wall_count(&[&square(0, 0, 4, 10), &square(6, 0, 10, 10)], None),
+            8
+        );
+    }
+
+    #[test]
+    fn no_walls_where_polygons_were_cut() {
+        let clip = Some(ClipBox::tile(0.0));
+        // The part in the tile to the right builds the rest of the building.
+        assert_eq!(wall_count(&[&square(5, 0, 15, 10)], clip), 3);
+        // A building ending right at the edge of the tile keeps its wall there.
+        assert_eq!(wall_count(&[&square(5, 0, 10, 10)], clip), 4);
+        // Only the part of the edge the building reaches past is a cut, the wall below it is real.
+        let reaching_past = [(5, 0), (15, 0), (15, 4), (10, 4), (10, 10), (5, 10)];
+        assert_eq!(wall_count(&[&reaching_past], clip), 4);
+    }
+
+    #[test]
+    fn walls_between_tiles_depend_on_the_neighbouring_tile() {
+        let clip = Some(ClipBox::tile(0.0));
+        let building = square(5, 2, 10, 8);
+
+        // Without the tile to the right the wall along the edge stays, but is reported as one that may be a cut.
+        let alone = build_shape(&[&building], &[], clip);
+        assert_eq!(walls_of(&alone), 4);
+        assert_eq!(alone.edge_walls, [false, true, false, false]);
+
+        // The rest of the building in the tile to the right.
+        let continued = build_shape(&[&building], &[&square(10, 2, 14, 8)], clip);
+        assert_eq!(walls_of(&continued), 3);
+        assert_eq!(continued.edge_walls, [false; 4]);
+
+        // A building in the next tile that starts a little further on leaves the wall in place.
+        let apart = build_shape(&[&building], &[&square(11, 2, 14, 8)], clip);
+        assert_eq!(walls_of(&apart), 4);
+    }
+
+    #[test]
+    fn empty_geometry_builds_nothing() {
+        let mesh = build(&[], true);
+        assert!(mesh.vertices.is_empty());
+        assert!(mesh.indices.is_empty());
+    }
+
+    #[test]
+    fn degenerate_rings_build_no_roof() {
+        let collinear = build(&[&[(0, 0), (5, 0), (10, 0)]], false);
+        assert!(collinear.indices.is_empty());
+
+        let repeated = build(&[&[(2, 2), (2, 2), (2, 2), (2, 2)]], true);
+        assert!(triangles(&repeated)
+            .iter()
+            .all(|t| face_normal(t) == glm::vec3(0.0, 0.0, 0.0)));
+        assert!(repeated.normals.iter().all(|n| !n.x.is_nan()));
+    }
+}
@@ -0,0 +1,329 @@
+use super::commands::TilePoint;
+use std::collections::HashMap;
+
+// The area from the surveyor's formula in tile coordinates, where Y points down. Exterior rings are positive and
+// holes negative.
+pub fn signed_area(ring: &[TilePoint]) -> f64 {
+    let mut area = 0.0;
+    let mut prev = match ring.last() {
+        Some(prev) => *prev,
+        None => return 0.0,
+    };
+    for curr in ring {
+        area += (prev.0 as f64) * (curr.1 as f64) - (curr.0 as f64) * (prev.1 as f64);
+        prev = *curr;
+    }
+    0.5 * area
+}
+
+// The ring turned so its area is positive for an exterior ring and negative for a hole.
+pub fn oriented(mut ring: Vec<TilePoint>, exterior: bool) -> Vec<TilePoint> {
+    if (signed_area(&ring) > 0.0) != exterior {
+        ring.reverse();
+    }
+    ring
+}
+
+// Whether `point` lies inside `ring`, or None when it lies on its boundary.
+pub fn point_in_ring(point: TilePoint, ring: &[TilePoint]) -> Option<bool> {
+    let mut inside = false;
+    for (i, &a) in ring.iter().enumerate() {
+        let b = ring[(i + 1) % ring.len()];
+        let turn = orientation(a, b, point);
+        if turn == 0 && on_segment(a, b, point) {
+            return None;
+        }
+        // Casts a ray from the point towards +X and counts the edges it crosses.
+        if (a.1 > point.1) != (b.1 > point.1) && (turn > 0) == (b.1 > a.1) {
+            inside = !inside;
+        }
+    }
+    Some(inside)
+}
+
+// Whether `inner` lies inside `outer`, decided by the first point of `inner` that is not on the boundary of
+// `outer`. Rings that only touch are not inside each other.
+pub fn contains(outer: &[TilePoint], inner: &[TilePoint]) -> bool {
+    inner
+        .iter()
+        .filter_map(|point| point_in_ring(*point, outer))
+        .next()
+        .unwrap_or(false)
+}
+
+// Makes a ring something earcut can triangulate: repeated points and points that do not change the direction of
+// the ring are dropped, and a ring that crosses or touches itself is split into simple rings at those points. A ring
+// without any area is dropped altogether, so the result may be empty.
+pub fn repair_ring(ring: &[TilePoint]) -> Vec<Vec<TilePoint>> {
+    let mut rings = vec![];
+    // Rings still to be repaired, taken from the back so the parts come out in the order they appear in the ring.
+    let mut work = vec![ring.to_vec()];
+    while let Some(ring) = work.pop() {
+        let ring = remove_degenerate_points(&ring);
+        if ring.len() < 3 {
+            continue;
+        }
+        let mut loops = split_touches(ring);
+        if loops.len() > 1 {
+            work.extend(loops.into_iter().rev());
+            continue;
+        }
+        let ring = loops.pop().unwrap();
+        match split_ring(&ring) {
+            Some((a, b)) => {
+                work.push(b);
+                work.push(a);
+            }
+            None if signed_area(&ring) == 0.0 => {}
+            None => rings.push(ring),
+        }
+    }
+    rings
+}
+
+fn remove_degenerate_points(ring: &[TilePoint]) -> Vec<TilePoint> {
+    let mut points: Vec<TilePoint> = vec![];
+    for point in ring {
+        push_point(&mut points, *point);
+    }
+    // The ring is closed, so its last points have to be checked against its first ones as well.
+    while points.len() >= 3 {
+        let n = points.len();
+        if points[n - 1] == points[0] || orientation(points[n - 2], points[n - 1], points[0]) == 0 {
+            points.pop();
+        } else if orientation(points[n - 1], points[0], points[1]) == 0 {
+            points.remove(0);
+        } else {
+            break;
+        }
+    }
+    points
+}
+
+// Appends `point`, first removing the points it makes redundant. A point going straight on or straight back, as
+// with a spike, adds no area.
+fn push_point(points: &mut Vec<TilePoint>, point: TilePoint) {
+    loop {
+        let n = points.len();
+        if n >= 1 && points[n - 1] == point {
+            return;
+        }
+        if n >= 2 && orientation(points[n - 2], points[n - 1], point) == 0 {
+            points.pop();
+        } else {
+            break;
+        }
+    }
+    points.push(point);
+}
+
+// Splits the ring into loops at every point it visits twice, in a single walk: when the walk comes back to a point,
+// the loop since its last visit is cut off and the walk goes on from that point. The loops come out in the order
+// they close, followed by what is left of the ring.
+fn split_touches(ring: Vec<TilePoint>) -> Vec<Vec<TilePoint>> {
+    let mut loops = vec![];
+    let mut path: Vec<TilePoint> = Vec::with_capacity(ring.len());
+    let mut visited: HashMap<TilePoint, usize> = HashMap::new();
+    for point in ring {
+        if let Some(&k) = visited.get(&point) {
+            let closed: Vec<TilePoint> = path.drain(k..).collect();
+            for point in &closed {
+                visited.remove(point);
+            }
+            loops.push(closed);
+        }
+        visited.insert(point, path.len());
+        path.push(point);
+    }
+    loops.push(path);
+    loops
+}
+
+// Splits the ring in two where two of its edges cross. Both parts have fewer points than the ring, so repeated
+// splitting ends.
+fn split_ring(ring: &[TilePoint]) -> Option<(Vec<TilePoint>, Vec<TilePoint>)> {
+    let n = ring.len();
+    for i in 0..n {
+        // Neighbouring edges share a point, they cannot cross.
+        for j in (i + 2)..n {
+            if i == 0 && j == n - 1 {
+                continue;
+            }
+            let crossing = match crossing(ring[i], ring[i + 1], ring[j], ring[(j + 1) % n]) {
+                Some(crossing) => crossing,
+                None => continue,
+            };
+            let inner = std::iter::once(crossing)
+                .chain(ring[(i + 1)..=j].iter().copied())
+                .collect();
+            let outer = ring[..=i]
+                .iter()
+                .copied()
+                .chain(std::iter::once(crossing))
+                .chain(ring[(j + 1)..].iter().copied())
+                .collect();
+            return Some((inner, outer));
+        }
+    }
+    None
+}
+
+// Where the segments a-b and c-d meet, rounded to tile coordinates. Segments lying on the same line are not
+// considered to meet, the points removed by `remove_degenerate_points` cover the common cases of those.
+fn crossing(a: TilePoint, b: TilePoint, c: TilePoint, d: TilePoint) -> Option<TilePoint> {
+    let (o1, o2) = (orientation(a, b, c), orientation(a, b, d));
+    let (o3, o4) = (orientation(c, d, a), orientation(c, d, b));
+    if o1 == 0 && o2 == 0 {
+        return None;
+    }
+    if o1 * o2 < 0 && o3 * o4 < 0 {
+        let t = cross(sub(c, a), sub(d, c)) as f64 / cross(sub(b, a), sub(d, c)) as f64;
+        return Some((
+            (a.0 as f64 + t * (b.0 - a.0) as f64).round() as i64,
+            (a.1 as f64 + t * (b.1 - a.1) as f64).round() as i64,
+        ));
+    }
+    // One segment ends on the other one.
+    [(o1, c, a, b), (o2, d, a, b), (o3, a, c, d), (o4, b, c, d)]
+        .iter()
+        .find(|(turn, point, start, end)| *turn == 0 && on_segment(*start, *end, *point))
+        .map(|(_, point, _, _)| *point)
+}
+
+fn sub(a: TilePoint, b: TilePoint) -> (i128, i128) {
+    ((a.0 - b.0) as i128, (a.1 - b.1) as i128)
+}
+
+fn cross(a: (i128, i128), b: (i128, i128)) -> i128 {
+    a.0 * b.1 - a.1 * b.0
+}
+
+// Which way the path a-b-c turns: positive, negative, or 0 when the points are on a line.
+fn orientation(a: TilePoint, b: TilePoint, c: TilePoint) -> i128 {
+    cross(sub(b, a), sub(c, b)).signum()
+}
+
+// Whether `point`, which lies on the line through a and b, lies between them.
+fn on_segment(a: TilePoint, b: TilePoint, point: TilePoint) -> bool {
+    a.0.min(b.0) <= point.0
+        && point.0 <= a.0.max(b.0)
+        && a.1.min(b.1) <= point.1
+        && point.1 <= a.1.max(b.1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::test_util::square;
+    use super::*;
+
+    #[test]
+    fn simple_rings_are_kept() {
+        let ring = square(0, 0, 10, 10);
+        assert_eq!(repair_ring(&ring), vec![ring]);
+    }
+
+    #[test]
+    fn repeated_and_straight_points_are_dropped() {
+        let ring = vec![(0, 0), (0, 0), (5, 0), (10, 0), (10, 10), (0, 10), (0, 0)];
+        assert_eq!(repair_ring(&ring), vec![square(0, 0, 10, 10)]);
+    }
+
+    #[test]
+    fn spikes_are_dropped() {
+        let ring = vec![
+            (0, 0),
+            (10, 0),
+            (10, 5),
+            (20, 5),
+            (10, 5),
+            (10, 10),
+            (0, 10),
+        ];
+        assert_eq!(repair_ring(&ring), vec![square(0, 0, 10, 10)]);
+    }
+
+    #[test]
+    fn rings_without_area_are_dropped() {
+        assert!(repair_ring(&[(0, 0), (5, 0), (10, 0)]).is_empty());
+        assert!(repair_ring(&[(2, 2), (2, 2), (2, 2)]).is_empty());
+        assert!(repair_ring(&[(0, 0), (10, 0), (0, 0)]).is_empty());
+        assert!(repair_ring(&[]).is_empty());
+    }
+
+    #[test]
+    fn crossing_rings_are_split() {
+        // A bow tie crossing itself at (5, 5).
+        let ring = vec![(0, 0), (10, 10), (10, 0), (0, 10)];
+        let rings = repair_ring(&ring);
+        assert_eq!(rings.len(), 2);
+        for ring in &rings {
+            assert_eq!(ring.len(), 3);
+            assert!(ring.contains(&(5, 5)));
+            assert_eq!(signed_area(ring).abs(), 25.0);
+        }
+    }
+
+    #[test]
+    fn pinched_rings_are_split() {
+        // Two squares meeting in the corner (10, 10), drawn as one ring.
+        let ring = vec![
+            (0, 0),
+            (10, 0),
+            (10, 10),
+            (20, 10),
+            (20, 20),
+            (10, 20),
+            (10, 10),
+            (0, 10),
+        ];
+        let mut areas: Vec<f64> = repair_ring(&ring).iter().map(|r| signed_area(r)).collect();
+        areas.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        assert_eq!(areas, vec![100.0, 100.0]);
+    }
+
+    #[test]
+    fn rings_touching_themselves_many_times_are_split() {
+        // A diagonal staircase of squares, each meeting the next in a corner, drawn as one ring that goes up along
+        // their right side and comes back down along their left side, so it visits every inner corner twice.
+        let n = 5000;
+        let mut ring = vec![];
+        for i in 0..n {
+            ring.push((10 * i, 10 * i));
+            ring.push((10 * i + 10, 10 * i));
+        }
+        for i in (1..=n).rev() {
+            ring.push((10 * i, 10 * i));
+            ring.push((10 * i - 10, 10 * i));
+        }
+        let rings = repair_ring(&ring);
+        assert_eq!(rings.len(), n as usize);
+        assert!(rings
+            .iter()
+            .all(|ring| ring.len() == 4 && signed_area(ring) == 100.0));
+    }
+
+    #[test]
+    fn points_on_the_boundary_are_neither_inside_nor_outside() {
+        let ring = square(0, 0, 10, 10);
+        assert_eq!(point_in_ring((5, 5), &ring), Some(true));
+        assert_eq!(point_in_ring((15, 5), &ring), Some(false));
+        assert_eq!(point_in_ring((10, 5), &ring), None);
+        assert_eq!(point_in_ring((0, 0), &ring), None);
+    }
+
+    #[test]
+    fn touching_rings_are_not_contained() {
+        let outer = square(0, 0, 10, 10);
+        assert!(contains(&outer, &square(2, 2, 4, 4)));
+        assert!(contains(&outer, &square(0, 0, 4, 4)));
+        assert!(!contains(&outer, &square(10, 0, 20, 10)));
+        assert!(!contains(&square(2, 2, 4, 4), &outer));
+    }
+
+    #[test]
+    fn oriented_turns_rings() {
+        let ring = square(0, 0, 10, 10);
+        assert_eq!(oriented(ring.clone(), true), ring);
+        assert!(signed_area(&oriented(ring, false)) < 0.0);
+    }
+}
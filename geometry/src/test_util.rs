@@ -0,0 +1,40 @@
+// Helpers shared by the unit tests, for writing geometry the way tiles encode it.
+use super::commands::TilePoint;
+
+pub fn command(id: u32, count: usize) -> u32 {
+    (id & 0x7) | ((count as u32) << 3)
+}
+
+pub fn zigzag(n: i64) -> u32 {
+    ((n << 1) ^ (n >> 63)) as u32
+}
+
+// Encodes every ring as a MoveTo, a LineTo and a ClosePath command, like a tile would.
+pub fn encode_rings<R: AsRef<[TilePoint]>>(rings: &[R]) -> Vec<u32> {
+    let mut geometry = vec![];
+    let mut cursor = (0, 0);
+    for ring in rings {
+        let ring = ring.as_ref();
+        for (i, point) in ring.iter().enumerate() {
+            if i == 0 {
+                geometry.push(command(1, 1));
+            } else if i == 1 {
+                geometry.push(command(2, ring.len() - 1));
+            }
+            geometry.push(zigzag(point.0 - cursor.0));
+            geometry.push(zigzag(point.1 - cursor.1));
+            cursor = *point;
+        }
+        geometry.push(command(7, 1));
+    }
+    geometry
+}
+
+// Clockwise with Y pointing down, which is a positive area and so an exterior ring.
+pub fn square<T: Copy>(x0: T, y0: T, x1: T, y1: T) -> Vec<(T, T)> {
+    vec![(x0, y0), (x1, y0), (x1, y1), (x0, y1)]
+}
+
+pub fn reversed<T>(ring: Vec<T>) -> Vec<T> {
+    ring.into_iter().rev().collect()
+}
@@ -0,0 +1,453 @@
+use std::f64::consts::PI;
+use std::fmt;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct TileAddress {
+    pub x: u32,
+    pub y: u32,
+    pub z: u32,
+}
+
+impl TileAddress {
+    pub fn new(x: u32, y: u32, z: u32) -> Self {
+        TileAddress { x, y, z }
+    }
+
+    // The tile at column `x` and row `y`. Columns wrap around the antimeridian, rows beyond the poles have no tile.
+    pub fn wrapped(x: i64, y: i64, z: u32) -> Option<TileAddress> {
+        let size = 1i64 << z;
+        if y < 0 || y >= size {
+            return None;
+        }
+        Some(TileAddress::new(x.rem_euclid(size) as u32, y as u32, z))
+    }
+
+    // The tiles within `radius` of this one, wrapping around the antimeridian and stopping at the poles. Every
+    // tile is returned once, even when the radius spans the whole world.
+    pub fn get_tiles(&self, radius: u32) -> Vec<TileAddress> {
+        let size = 1i64 << self.z;
+        let radius = radius as i64;
+        let min_x = self.x as i64 - radius;
+        let max_x = min_x + (2 * radius + 1).min(size) - 1;
+        let min_y = self.y as i64 - radius;
+        let max_y = self.y as i64 + radius;
+
+        let mut tiles = vec![];
+        for x in min_x..max_x + 1 {
+            for y in min_y..max_y + 1 {
+                if let Some(tile) = TileAddress::wrapped(x, y, self.z) {
+                    tiles.push(tile);
+                }
+            }
+        }
+
+        tiles
+    }
+
+    pub fn parent(&self) -> Option<TileAddress> {
+        if self.z == 0 {
+            return None;
+        }
+        Some(TileAddress::new(self.x / 2, self.y / 2, self.z - 1))
+    }
+
+    pub fn children(&self) -> [TileAddress; 4] {
+        let (x, y, z) = (self.x * 2, self.y * 2, self.z + 1);
+        [
+            TileAddress::new(x, y, z),
+            TileAddress::new(x + 1, y, z),
+            TileAddress::new(x, y + 1, z),
+            TileAddress::new(x + 1, y + 1, z),
+        ]
+    }
+
+    // The tile at `zoom` containing this one, which must be at `zoom` or deeper.
+    pub fn ancestor(&self, zoom: u32) -> TileAddress {
+        assert!(
+            zoom <= self.z,
+            "tile {} has no ancestor at zoom {}",
+            self,
+            zoom
+        );
+        let shift = self.z - zoom;
+        TileAddress::new(self.x >> shift, self.y >> shift, zoom)
+    }
+
+    // The up to eight tiles around this one. Columns wrap around the antimeridian, rows stop at the poles.
+    pub fn neighbours(&self) -> Vec<TileAddress> {
+        let mut neighbours = vec![];
+        for dy in -1..=1 {
+            for dx in -1..=1 {
+                let neighbour =
+                    TileAddress::wrapped(self.x as i64 + dx, self.y as i64 + dy, self.z);
+                if let Some(neighbour) = neighbour {
+                    if neighbour != *self && !neighbours.contains(&neighbour) {
+                        neighbours.push(neighbour);
+                    }
+                }
+            }
+        }
+        neighbours
+    }
+
+    pub fn bounds(&self) -> TileBounds {
+        let (north, west) = tile_to_latlon(self.x as f64, self.y as f64, self.z);
+        let (south, east) = tile_to_latlon(self.x as f64 + 1.0, self.y as f64 + 1.0, self.z);
+        TileBounds {
+            west,
+            south,
+            east,
+            north,
+        }
+    }
+
+    // The latitude and longitude of the centre of the tile in web mercator, which is not the middle of its
+    // latitude bounds.
+    pub fn center(&self) -> (f64, f64) {
+        tile_to_latlon(self.x as f64 + 0.5, self.y as f64 + 0.5, self.z)
+    }
+
+    // The Bing maps quadkey, one digit per zoom level.
+    pub fn quadkey(&self) -> String {
+        (1..=self.z)
+            .rev()
+            .map(|level| {
+                let mask = 1 << (level - 1);
+                let mut digit = 0;
+                if self.x & mask != 0 {
+                    digit += 1;
+                }
+                if self.y & mask != 0 {
+                    digit += 2;
+                }
+                std::char::from_digit(digit, 4).unwrap()
+            })
+            .collect()
+    }
+
+    pub fn from_quadkey(quadkey: &str) -> Result<TileAddress, QuadkeyError> {
+        if quadkey.len() > 31 {
+            return Err(QuadkeyError::TooLong {
+                length: quadkey.len(),
+            });
+        }
+        let mut address = TileAddress::new(0, 0, 0);
+        for (position, c) in quadkey.chars().enumerate() {
+            let digit = match c.to_digit(4) {
+                Some(digit) => digit,
+                None => return Err(QuadkeyError::InvalidDigit { position, digit: c }),
+            };
+            address = address.children()[digit as usize];
+        }
+        Ok(address)
+    }
+
+    // The width of the tile in metres at its centre. Web mercator stretches distances by 1 / cos(latitude), so
+    // tiles of one zoom cover less ground the further they are from the equator.
+    pub fn ground_width(&self) -> f64 {
+        let (latitude, _) = self.center();
+        meters_per_tile(self.z) * latitude.to_radians().cos()
+    }
+
+    // Converts between the XYZ row numbering, counted from the north, and TMS, counted from the south. Flipping
+    // twice gives back the original address.
+    pub fn flip_y(&self) -> TileAddress {
+        TileAddress::new(self.x, (1 << self.z) - 1 - self.y, self.z)
+    }
+}
+
+// Written as `z/x/y`, the way tile URLs and directories are laid out.
+impl fmt::Display for TileAddress {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}/{}/{}", self.z, self.x, self.y)
+    }
+}
+
+// Why a string is not a quadkey. Tile coordinates are 32 bit, so a quadkey has at most 31 digits.
+#[derive(Clone, Debug, PartialEq)]
+pub enum QuadkeyError {
+    TooLong { length: usize },
+    InvalidDigit { position: usize, digit: char },
+}
+
+impl fmt::Display for QuadkeyError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            QuadkeyError::TooLong { length } => {
+                write!(f, "quadkey has {} digits, at most 31 are supported", length)
+            }
+            QuadkeyError::InvalidDigit { position, digit } => {
+                write!(f, "invalid quadkey digit '{}' at {}", digit, position)
+            }
+        }
+    }
+}
+
+impl std::error::Error for QuadkeyError {}
+
+// Latitudes and longitudes in degrees.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct TileBounds {
+    pub west: f64,
+    pub south: f64,
+    pub east: f64,
+    pub north: f64,
+}
+
+pub const EARTH_RADIUS_METER: f64 = 6378137.0;
+pub const EARTH_CIRCUMFERENCE_METERS: f64 = EARTH_RADIUS_METER * std::f64::consts::PI * 2.0;
+pub const EARTH_HALF_CIRCUMFERENCE_METERS: f64 = EARTH_RADIUS_METER * std::f64::consts::PI;
+
+// The width of a tile at the equator.
+pub fn meters_per_tile(zoom: u32) -> f64 {
+    EARTH_CIRCUMFERENCE_METERS / (1u64 << zoom) as f64
+}
+
+// The ground resolution of a `tile_size` pixel tile at `latitude`.
+pub fn meters_per_pixel(latitude: f64, zoom: u32, tile_size: u32) -> f64 {
+    meters_per_tile(zoom) * latitude.to_radians().cos() / tile_size as f64
+}
+
+// The fractional tile coordinates of a point, e.g. `(10.5, 3.25)` is in the middle of column 10, a quarter of
+// the way down row 3.
+pub fn latlon_to_tile_position(latitude: f64, longitude: f64, zoom: u32) -> (f64, f64) {
+    let meters_per_tile = meters_per_tile(zoom);
+
+    let x = longitude * EARTH_HALF_CIRCUMFERENCE_METERS / 180.0;
+    let y = (0.25 * PI + latitude * PI / 360.0).tan().ln() * EARTH_RADIUS_METER;
+
+    (
+        (x + EARTH_HALF_CIRCUMFERENCE_METERS) / meters_per_tile,
+        (EARTH_HALF_CIRCUMFERENCE_METERS - y) / meters_per_tile,
+    )
+}
+
+pub fn latlon_to_tile_address(latitude: f64, longitude: f64, zoom: u32) -> TileAddress {
+    let (tile_x, tile_y) = latlon_to_tile_position(latitude, longitude, zoom);
+
+    // Longitudes past the antimeridian wrap, latitudes past the edge of the map clamp to the first or last row.
+    let tiles = 1i64 << zoom;
+    let tile_x = tile_x.floor() as i64;
+    let tile_y = tile_y.floor() as i64;
+    TileAddress::new(
+        tile_x.rem_euclid(tiles) as u32,
+        tile_y.max(0).min(tiles - 1) as u32,
+        zoom,
+    )
+}
+
+// The latitude and longitude of a point given in fractional tile coordinates, e.g. `(x + 1, y + 1)` for the
+// south east corner of tile `(x, y)`.
+pub fn tile_to_latlon(x: f64, y: f64, zoom: u32) -> (f64, f64) {
+    let tiles = (1u64 << zoom) as f64;
+    let longitude = x / tiles * 360.0 - 180.0;
+    let latitude = (PI * (1.0 - 2.0 * y / tiles)).sinh().atan().to_degrees();
+    (latitude, longitude)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MAX_LATITUDE: f64 = 85.0511287798066;
+
+    fn assert_close(a: f64, b: f64) {
+        assert!((a - b).abs() < 1e-9, "{} != {}", a, b);
+    }
+
+    #[test]
+    fn world_tile_bounds() {
+        let bounds = TileAddress::new(0, 0, 0).bounds();
+        assert_close(bounds.west, -180.0);
+        assert_close(bounds.east, 180.0);
+        assert_close(bounds.north, MAX_LATITUDE);
+        assert_close(bounds.south, -MAX_LATITUDE);
+    }
+
+    #[test]
+    fn quadrant_bounds_and_center() {
+        let bounds = TileAddress::new(1, 0, 1).bounds();
+        assert_close(bounds.west, 0.0);
+        assert_close(bounds.east, 180.0);
+        assert_close(bounds.north, MAX_LATITUDE);
+        assert_close(bounds.south, 0.0);
+
+        let (latitude, longitude) = TileAddress::new(1, 0, 1).center();
+        assert_close(latitude, 66.51326044311186);
+        assert_close(longitude, 90.0);
+    }
+
+    #[test]
+    fn latlon_round_trip() {
+        // London.
+        let address = latlon_to_tile_address(51.5074, -0.1278, 15);
+        assert_eq!(address, TileAddress::new(16372, 10896, 15));
+
+        let bounds = address.bounds();
+        assert!(bounds.west <= -0.1278 && -0.1278 < bounds.east);
+        assert!(bounds.south < 51.5074 && 51.5074 <= bounds.north);
+    }
+
+    #[test]
+    fn latlon_at_the_edges() {
+        assert_eq!(
+            latlon_to_tile_address(0.0, 180.0, 2),
+            TileAddress::new(0, 2, 2)
+        );
+        assert_eq!(
+            latlon_to_tile_address(0.0, -180.0, 2),
+            TileAddress::new(0, 2, 2)
+        );
+        assert_eq!(
+            latlon_to_tile_address(89.9, 0.0, 2),
+            TileAddress::new(2, 0, 2)
+        );
+        assert_eq!(
+            latlon_to_tile_address(-89.9, 179.9, 2),
+            TileAddress::new(3, 3, 2)
+        );
+    }
+
+    #[test]
+    fn parent_and_children() {
+        let address = TileAddress::new(5, 6, 4);
+        assert_eq!(address.parent(), Some(TileAddress::new(2, 3, 3)));
+        assert_eq!(TileAddress::new(0, 0, 0).parent(), None);
+        for child in address.children().iter() {
+            assert_eq!(child.parent(), Some(address));
+        }
+        assert_eq!(address.children()[3], TileAddress::new(11, 13, 5));
+        assert_eq!(address.ancestor(2), TileAddress::new(1, 1, 2));
+        assert_eq!(address.ancestor(4), address);
+        assert_eq!(address.ancestor(0), TileAddress::new(0, 0, 0));
+    }
+
+    #[test]
+    #[should_panic(expected = "tile 4/5/6 has no ancestor at zoom 5")]
+    fn ancestor_below_the_tile_panics() {
+        TileAddress::new(5, 6, 4).ancestor(5);
+    }
+
+    #[test]
+    fn neighbours() {
+        assert_eq!(TileAddress::new(5, 6, 4).neighbours().len(), 8);
+        assert!(TileAddress::new(0, 0, 0).neighbours().is_empty());
+
+        // Wraps around the antimeridian but not the poles.
+        let neighbours = TileAddress::new(0, 0, 2).neighbours();
+        assert_eq!(neighbours.len(), 5);
+        assert!(neighbours.contains(&TileAddress::new(3, 0, 2)));
+        assert!(neighbours.contains(&TileAddress::new(3, 1, 2)));
+        assert!(neighbours.iter().all(|neighbour| neighbour.y <= 1));
+    }
+
+    #[test]
+    fn get_tiles_in_the_middle() {
+        let tiles = TileAddress::new(5, 6, 4).get_tiles(1);
+        assert_eq!(tiles.len(), 9);
+        assert!(tiles.contains(&TileAddress::new(4, 5, 4)));
+        assert!(tiles.contains(&TileAddress::new(6, 7, 4)));
+    }
+
+    #[test]
+    fn get_tiles_wraps_at_the_antimeridian() {
+        let tiles = TileAddress::new(0, 5, 4).get_tiles(2);
+        assert_eq!(tiles.len(), 25);
+        assert!(tiles.contains(&TileAddress::new(14, 3, 4)));
+        assert!(tiles.contains(&TileAddress::new(15, 7, 4)));
+        assert!(tiles.contains(&TileAddress::new(2, 5, 4)));
+
+        let tiles = TileAddress::new(15, 5, 4).get_tiles(1);
+        assert!(tiles.contains(&TileAddress::new(0, 5, 4)));
+    }
+
+    #[test]
+    fn get_tiles_stops_at_the_poles() {
+        let tiles = TileAddress::new(5, 0, 4).get_tiles(2);
+        assert_eq!(tiles.len(), 15);
+        assert!(tiles.iter().all(|tile| tile.y <= 2));
+
+        let tiles = TileAddress::new(5, 15, 4).get_tiles(1);
+        assert_eq!(tiles.len(), 6);
+        assert!(tiles.iter().all(|tile| tile.y >= 14));
+    }
+
+    #[test]
+    fn get_tiles_covers_the_world_once() {
+        let tiles = TileAddress::new(1, 1, 2).get_tiles(5);
+        assert_eq!(tiles.len(), 16);
+        for tile in &tiles {
+            assert_eq!(tiles.iter().filter(|other| *other == tile).count(), 1);
+        }
+        assert_eq!(
+            TileAddress::new(0, 0, 0).get_tiles(1),
+            vec![TileAddress::new(0, 0, 0)]
+        );
+    }
+
+    #[test]
+    fn quadkey() {
+        // The example from the Bing maps tile system documentation.
+        let address = TileAddress::new(3, 5, 3);
+        assert_eq!(address.quadkey(), "213");
+        assert_eq!(TileAddress::from_quadkey("213"), Ok(address));
+
+        assert_eq!(TileAddress::new(0, 0, 0).quadkey(), "");
+        assert_eq!(TileAddress::from_quadkey(""), Ok(TileAddress::new(0, 0, 0)));
+        assert_eq!(
+            TileAddress::from_quadkey("214"),
+            Err(QuadkeyError::InvalidDigit {
+                position: 2,
+                digit: '4'
+            })
+        );
+        assert_eq!(
+            TileAddress::from_quadkey(&"0".repeat(32)),
+            Err(QuadkeyError::TooLong { length: 32 })
+        );
+        assert_eq!(
+            TileAddress::from_quadkey("21x").unwrap_err().to_string(),
+            "invalid quadkey digit 'x' at 2"
+        );
+
+        let address = TileAddress::new(16372, 10896, 15);
+        assert_eq!(TileAddress::from_quadkey(&address.quadkey()), Ok(address));
+    }
+
+    #[test]
+    fn tms_flip() {
+        assert_eq!(
+            TileAddress::new(1, 0, 2).flip_y(),
+            TileAddress::new(1, 3, 2)
+        );
+        assert_eq!(
+            TileAddress::new(0, 0, 0).flip_y(),
+            TileAddress::new(0, 0, 0)
+        );
+        let address = TileAddress::new(16372, 10896, 15);
+        assert_eq!(address.flip_y().flip_y(), address);
+    }
+
+    #[test]
+    fn ground_width() {
+        // Tiles either side of the equator are centred at the same latitude.
+        let address = TileAddress::new(0, 1, 1);
+        let (latitude, _) = address.center();
+        assert_close(latitude, -66.51326044311186);
+        assert_close(
+            address.ground_width(),
+            EARTH_HALF_CIRCUMFERENCE_METERS * latitude.to_radians().cos(),
+        );
+
+        let london = TileAddress::new(16372, 10896, 15);
+        let width = london.ground_width();
+        assert!((width - 761.24).abs() < 0.01, "{}", width);
+    }
+
+    #[test]
+    fn ground_resolution() {
+        assert_close(meters_per_pixel(0.0, 0, 256), 156543.03392804097);
+        assert_close(meters_per_pixel(60.0, 10, 256), 76.43702828517627);
+        assert_close(meters_per_tile(1), EARTH_HALF_CIRCUMFERENCE_METERS);
+    }
+}
@@ -2,10 +2,11 @@
 
 out vec4 FragColor;
 in vec4 gl_FragCoord;
+in vec4 v_color;
 uniform float u_time;
 uniform vec2 u_resolution;
 uniform vec4 u_color;
 void main()
 {   
-    FragColor = u_color;
+    FragColor = u_color * v_color;
 }
@@ -3,6 +3,7 @@
 out vec4 FragColor;
 
 in vec4 gl_FragCoord;
+in vec4 v_color;
 in vec2 v_uv;
 in vec3 v_normal;
 in vec3 v_position;
@@ -16,7 +17,7 @@ void main()
 {   
     float specularStrength = 0.5;
     float ambientStrength = 0.1;
-    vec3 color = texture(texture1, v_uv).rgb;
+    vec3 color = texture(texture1, v_uv).rgb * v_color.rgb;
     vec3 lightColor = vec3(1.0, 1.0, 1.0);
 
     vec3 ambient = ambientStrength * lightColor;
@@ -0,0 +1,223 @@
+use super::properties::FeatureProperties;
+use super::zoom::ZoomValue;
+use std::collections::HashMap;
+
+// How the colour of a feature is chosen. The result is baked into the vertex colours of the feature's mesh, so
+// features with different colours can still share one material and draw call.
+#[derive(Clone, Debug)]
+pub enum ColorRule {
+    Constant(glm::Vec4),
+    Zoom(ZoomValue<glm::Vec4>),
+    // Looks the property value up by its string form, e.g. `"church"`, `"3"` or `"true"`.
+    Categorical {
+        property: String,
+        categories: HashMap<String, glm::Vec4>,
+        default: glm::Vec4,
+    },
+    // Interpolates linearly between stops sorted by their property value, clamping outside the first and last.
+    Ramp {
+        property: String,
+        stops: Vec<(f64, glm::Vec4)>,
+        default: glm::Vec4,
+    },
+}
+
+impl ColorRule {
+    pub fn evaluate(&self, properties: &FeatureProperties, zoom: f32) -> glm::Vec4 {
+        match self {
+            ColorRule::Constant(color) => *color,
+            ColorRule::Zoom(color) => color.at(zoom),
+            ColorRule::Categorical {
+                property,
+                categories,
+                default,
+            } => properties
+                .get(property)
+                .and_then(|value| categories.get(&value.to_string()))
+                .copied()
+                .unwrap_or(*default),
+            ColorRule::Ramp {
+                property,
+                stops,
+                default,
+            } => match properties.get_f64(property) {
+                Some(value) => interpolate_stops(stops, value).unwrap_or(*default),
+                None => *default,
+            },
+        }
+    }
+}
+
+pub fn interpolate_stops(stops: &[(f64, glm::Vec4)], value: f64) -> Option<glm::Vec4> {
+    let (first, last) = (stops.first()?, stops.last()?);
+    if value <= first.0 {
+        return Some(first.1);
+    }
+    if value >= last.0 {
+        return Some(last.1);
+    }
+
+    for pair in stops.windows(2) {
+        let ((v0, c0), (v1, c1)) = (pair[0], pair[1]);
+        if value <= v1 {
+            let t = if v1 > v0 {
+                (value - v0) / (v1 - v0)
+            } else {
+                1.0
+            };
+            return Some(glm::lerp(&c0, &c1, t as f32));
+        }
+    }
+    Some(last.1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::protos::vector_tile;
+    use super::*;
+
+    const RED: [f32; 4] = [1.0, 0.0, 0.0, 1.0];
+    const BLUE: [f32; 4] = [0.0, 0.0, 1.0, 1.0];
+    const GREY: [f32; 4] = [0.5, 0.5, 0.5, 1.0];
+
+    fn color(c: [f32; 4]) -> glm::Vec4 {
+        glm::vec4(c[0], c[1], c[2], c[3])
+    }
+
+    // A feature with a `kind` string, a `levels` integer and a `oneway` boolean, or with no properties at all.
+    fn layer_and_feature(tagged: bool) -> (vector_tile::Tile_Layer, vector_tile::Tile_Feature) {
+        let mut layer = vector_tile::Tile_Layer::new();
+        let mut feature = vector_tile::Tile_Feature::new();
+        let mut kind = vector_tile::Tile_Value::new();
+        kind.set_string_value(String::from("church"));
+        let mut levels = vector_tile::Tile_Value::new();
+        levels.set_int_value(3);
+        let mut oneway = vector_tile::Tile_Value::new();
+        oneway.set_bool_value(true);
+        for (i, (key, value)) in vec![("kind", kind), ("levels", levels), ("oneway", oneway)]
+            .into_iter()
+            .enumerate()
+        {
+            layer.mut_keys().push(String::from(key));
+            layer.mut_values().push(value);
+            if tagged {
+                feature.mut_tags().push(i as u32);
+                feature.mut_tags().push(i as u32);
+            }
+        }
+        (layer, feature)
+    }
+
+    fn categorical(property: &str, categories: &[(&str, [f32; 4])]) -> ColorRule {
+        ColorRule::Categorical {
+            property: String::from(property),
+            categories: categories
+                .iter()
+                .map(|(category, c)| (String::from(*category), color(*c)))
+                .collect(),
+            default: color(GREY),
+        }
+    }
+
+    fn ramp(stops: &[(f64, [f32; 4])]) -> ColorRule {
+        ColorRule::Ramp {
+            property: String::from("levels"),
+            stops: stops.iter().map(|(value, c)| (*value, color(*c))).collect(),
+            default: color(GREY),
+        }
+    }
+
+    #[test]
+    fn categories_match_the_property_as_a_string() {
+        let (layer, feature) = layer_and_feature(true);
+        let properties = FeatureProperties::new(&layer, &feature);
+        let cases = [
+            (
+                categorical("kind", &[("church", RED), ("school", BLUE)]),
+                RED,
+            ),
+            (categorical("kind", &[("school", BLUE)]), GREY),
+            (categorical("levels", &[("3", BLUE)]), BLUE),
+            (categorical("oneway", &[("true", RED)]), RED),
+            (categorical("missing", &[("church", RED)]), GREY),
+        ];
+        for (rule, expected) in cases.iter() {
+            assert_eq!(
+                rule.evaluate(&properties, 14.0),
+                color(*expected),
+                "{:?}",
+                rule
+            );
+        }
+
+        let (layer, feature) = layer_and_feature(false);
+        let properties = FeatureProperties::new(&layer, &feature);
+        assert_eq!(
+            categorical("kind", &[("church", RED)]).evaluate(&properties, 14.0),
+            color(GREY)
+        );
+    }
+
+    #[test]
+    fn ramps_interpolate_the_property_and_clamp_outside_the_stops() {
+        let (layer, feature) = layer_and_feature(true);
+        let properties = FeatureProperties::new(&layer, &feature);
+        let cases = [
+            (ramp(&[(1.0, RED), (5.0, BLUE)]), [0.5, 0.0, 0.5, 1.0]),
+            (ramp(&[(5.0, RED), (10.0, BLUE)]), RED),
+            (ramp(&[(0.0, RED), (2.0, BLUE)]), BLUE),
+            (ramp(&[(3.0, RED)]), RED),
+            (ramp(&[]), GREY),
+        ];
+        for (rule, expected) in cases.iter() {
+            assert_eq!(
+                rule.evaluate(&properties, 14.0),
+                color(*expected),
+                "{:?}",
+                rule
+            );
+        }
+
+        // Features without the property, or with a non-numeric one, use the default.
+        let rule = ColorRule::Ramp {
+            property: String::from("kind"),
+            stops: vec![(0.0, color(RED))],
+            default: color(GREY),
+        };
+        assert_eq!(rule.evaluate(&properties, 14.0), color(GREY));
+        let (layer, feature) = layer_and_feature(false);
+        let properties = FeatureProperties::new(&layer, &feature);
+        assert_eq!(
+            ramp(&[(1.0, RED), (5.0, BLUE)]).evaluate(&properties, 14.0),
+            color(GREY)
+        );
+    }
+
+    #[test]
+    fn stops_interpolate_linearly() {
+        let stops: Vec<(f64, glm::Vec4)> = vec![
+            (0.0, color(RED)),
+            (10.0, color(BLUE)),
+            (10.0, color(GREY)),
+            (20.0, color([0.0, 0.0, 0.0, 0.0])),
+        ];
+        let cases = [
+            (-5.0, RED),
+            (0.0, RED),
+            (2.5, [0.75, 0.0, 0.25, 1.0]),
+            (10.0, BLUE),
+            (15.0, [0.25, 0.25, 0.25, 0.5]),
+            (20.0, [0.0, 0.0, 0.0, 0.0]),
+            (100.0, [0.0, 0.0, 0.0, 0.0]),
+        ];
+        for (value, expected) in cases.iter() {
+            assert_eq!(
+                interpolate_stops(&stops, *value),
+                Some(color(*expected)),
+                "at {}",
+                value
+            );
+        }
+        assert_eq!(interpolate_stops(&[], 1.0), None);
+    }
+}
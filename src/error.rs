@@ -0,0 +1,78 @@
+use super::tile_address::TileAddress;
+use std::fmt;
+
+// Everything that can go wrong while starting up and loading tiles, with the argument, file, tile or shader it
+// happened to, so the message alone says what to fix.
+#[derive(Debug)]
+pub enum Error {
+    // A command line option that does not parse as the expected type.
+    Argument {
+        name: String,
+        value: String,
+    },
+    Style {
+        path: String,
+        message: String,
+    },
+    Source {
+        name: String,
+        message: String,
+    },
+    // The tile could not be read from its source, e.g. it is missing or the download failed, or loading it panicked.
+    Fetch {
+        address: TileAddress,
+        message: String,
+    },
+    Decompress {
+        address: TileAddress,
+        message: String,
+    },
+    Parse {
+        address: TileAddress,
+        message: String,
+    },
+    Cache {
+        path: String,
+        message: String,
+    },
+    Texture {
+        path: String,
+        message: String,
+    },
+    // `log` is the compiler or linker output of the driver.
+    Shader {
+        path: String,
+        log: String,
+    },
+    // Creating a GL context or drawing to it failed.
+    Render(String),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Argument { name, value } => {
+                write!(f, "invalid value '{}' for --{}", value, name)
+            }
+            Error::Style { path, message } => write!(f, "invalid style {}: {}", path, message),
+            Error::Source { name, message } => {
+                write!(f, "unable to open tile source {}: {}", name, message)
+            }
+            Error::Fetch { address, message } => write!(f, "tile {}: {}", address, message),
+            Error::Decompress { address, message } => {
+                write!(f, "tile {}: unable to decompress: {}", address, message)
+            }
+            Error::Parse { address, message } => {
+                write!(f, "tile {}: invalid vector tile: {}", address, message)
+            }
+            Error::Cache { path, message } => write!(f, "tile cache {}: {}", path, message),
+            Error::Texture { path, message } => {
+                write!(f, "unable to load texture {}: {}", path, message)
+            }
+            Error::Shader { path, log } => write!(f, "unable to build shader {}:\n{}", path, log),
+            Error::Render(message) => write!(f, "{}", message),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
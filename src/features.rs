@@ -0,0 +1,123 @@
+use super::colors::ColorRule;
+use super::filter::Filter;
+use super::material;
+use super::protos::vector_tile;
+use super::zoom::ZoomValue;
+use vector_tile_geometry::clip::ClipBox;
+use vector_tile_geometry::line_strings::LineOptions;
+use vector_tile_geometry::points::PointOptions;
+use vector_tile_geometry::polygons::PolygonOptions;
+
+pub struct FeatureOption {
+    pub layer: String,
+    pub material: material::Material,
+    pub color: ColorRule,
+    pub filter: Option<Filter>,
+    pub geo_type: vector_tile::Tile_GeomType,
+    pub geometry: GeometryOptions,
+    pub texture: Option<String>,
+    pub min_zoom: Option<f32>,
+    pub max_zoom: Option<f32>,
+}
+
+// Geometry settings that may change with zoom. They are resolved into `PolygonOptions`, `LineOptions` or
+// `PointOptions` for the zoom of each tile being built.
+pub enum GeometryOptions {
+    Fill {
+        height: ZoomValue<f32>,
+        min_height: ZoomValue<f32>,
+        height_property: Option<String>,
+        min_height_property: Option<String>,
+        walls: bool,
+    },
+    Line {
+        width: ZoomValue<f32>,
+        height: f32,
+        buffer: f32,
+    },
+    Point {
+        radius: ZoomValue<f32>,
+        top_radius: ZoomValue<f32>,
+        height: ZoomValue<f32>,
+        sides: u32,
+    },
+}
+
+impl FeatureOption {
+    // Like Mapbox GL layers, an option is drawn from `min_zoom` up to but not including `max_zoom`.
+    pub fn visible_at(&self, zoom: f32) -> bool {
+        self.min_zoom.is_none_or(|min_zoom| zoom >= min_zoom)
+            && self.max_zoom.is_none_or(|max_zoom| zoom < max_zoom)
+    }
+
+    pub fn polygon_options(&self, zoom: f32) -> Option<PolygonOptions> {
+        match &self.geometry {
+            GeometryOptions::Fill {
+                height,
+                min_height,
+                walls,
+                ..
+            } => Some(PolygonOptions {
+                max_height: height.at(zoom),
+                min_height: min_height.at(zoom),
+                build_walls: *walls,
+                // Polygons from the buffer around the tile are drawn by the tile they belong to.
+                clip: Some(ClipBox::tile(0.0)),
+            }),
+            _ => None,
+        }
+    }
+
+    // The properties a polygon feature's own height and minimum height are read from.
+    pub fn height_properties(&self) -> (Option<&str>, Option<&str>) {
+        match &self.geometry {
+            GeometryOptions::Fill {
+                height_property,
+                min_height_property,
+                ..
+            } => (height_property.as_deref(), min_height_property.as_deref()),
+            _ => (None, None),
+        }
+    }
+
+    pub fn line_options(&self, zoom: f32) -> Option<LineOptions> {
+        match &self.geometry {
+            GeometryOptions::Line {
+                width,
+                height,
+                buffer,
+            } => Some(LineOptions::new(*height, width.at(zoom), *buffer)),
+            _ => None,
+        }
+    }
+
+    pub fn point_options(&self, zoom: f32) -> Option<PointOptions> {
+        match &self.geometry {
+            GeometryOptions::Point {
+                radius,
+                top_radius,
+                height,
+                sides,
+            } => Some(PointOptions::new(
+                radius.at(zoom),
+                top_radius.at(zoom),
+                height.at(zoom),
+                *sides,
+            )),
+            _ => None,
+        }
+    }
+}
+
+pub fn get_filtered_features<'a>(
+    layer: &'a vector_tile::Tile_Layer,
+    filter: &'a Option<Filter>,
+) -> impl Iterator<Item = &'a vector_tile::Tile_Feature> {
+    layer
+        .get_features()
+        .iter()
+        .filter(move |feature| match filter {
+            Some(filter) => filter.evaluate(layer, feature),
+            None => true,
+        })
+}
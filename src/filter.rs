@@ -0,0 +1,402 @@
+use super::properties::{FeatureProperties, PropertyValue};
+use super::protos::vector_tile;
+use serde::de::{Deserialize, Deserializer, Error};
+use serde_json::Value;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Literal {
+    String(String),
+    Number(f64),
+    Bool(bool),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Comparison {
+    Less,
+    LessOrEqual,
+    Greater,
+    GreaterOrEqual,
+}
+
+// A feature filter expression. In a style file filters are written as JSON arrays, e.g.
+// `["all", ["==", "class", "street"], [">", "height", 50]]`, using the same syntax as Mapbox GL filters.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Filter {
+    All(Vec<Filter>),
+    Any(Vec<Filter>),
+    Not(Box<Filter>),
+    Has(String),
+    Equals(String, Literal),
+    In(String, Vec<Literal>),
+    Compare(String, Comparison, f64),
+    GeometryType(vector_tile::Tile_GeomType),
+}
+
+impl Filter {
+    pub fn parse(value: &Value) -> Result<Filter, String> {
+        let expression = match value {
+            Value::Array(expression) => expression,
+            Value::Bool(true) => return Ok(Filter::All(vec![])),
+            Value::Bool(false) => return Ok(Filter::Any(vec![])),
+            _ => return Err(format!("expected a filter array, found {}", value)),
+        };
+        let operator = match expression.first() {
+            Some(Value::String(operator)) => operator.as_str(),
+            _ => return Err(format!("filter {} does not start with an operator", value)),
+        };
+        let arguments = &expression[1..];
+
+        match operator {
+            "all" | "any" | "none" => {
+                let mut filters = vec![];
+                for (i, argument) in arguments.iter().enumerate() {
+                    let filter = Filter::parse(argument)
+                        .map_err(|e| format!("\"{}\" argument {}: {}", operator, i + 1, e))?;
+                    filters.push(filter);
+                }
+                Ok(match operator {
+                    "all" => Filter::All(filters),
+                    "any" => Filter::Any(filters),
+                    _ => Filter::Not(Box::new(Filter::Any(filters))),
+                })
+            }
+            "!" => {
+                expect_arguments(operator, arguments, 1)?;
+                Ok(Filter::Not(Box::new(Filter::parse(&arguments[0])?)))
+            }
+            "has" | "!has" => {
+                expect_arguments(operator, arguments, 1)?;
+                let filter = Filter::Has(parse_key(&arguments[0])?);
+                Ok(negate_if(operator == "!has", filter))
+            }
+            "==" | "!=" => {
+                expect_arguments(operator, arguments, 2)?;
+                let key = parse_key(&arguments[0])?;
+                let filter = if key == "$type" {
+                    Filter::GeometryType(parse_geometry_type(&arguments[1])?)
+                } else {
+                    Filter::Equals(key, parse_literal(&arguments[1])?)
+                };
+                Ok(negate_if(operator == "!=", filter))
+            }
+            "in" | "!in" => {
+                if arguments.len() < 2 {
+                    return Err(format!(
+                        "\"{}\" expects a key and at least one value",
+                        operator
+                    ));
+                }
+                let key = parse_key(&arguments[0])?;
+                let filter = if key == "$type" {
+                    let mut types = vec![];
+                    for argument in &arguments[1..] {
+                        types.push(Filter::GeometryType(parse_geometry_type(argument)?));
+                    }
+                    Filter::Any(types)
+                } else {
+                    let mut values = vec![];
+                    for argument in &arguments[1..] {
+                        values.push(parse_literal(argument)?);
+                    }
+                    Filter::In(key, values)
+                };
+                Ok(negate_if(operator == "!in", filter))
+            }
+            "<" | "<=" | ">" | ">=" => {
+                expect_arguments(operator, arguments, 2)?;
+                let key = parse_key(&arguments[0])?;
+                let number = match &arguments[1] {
+                    Value::Number(n) => n.as_f64().unwrap_or(0.0),
+                    other => {
+                        return Err(format!(
+                            "\"{}\" expects a number, found {}",
+                            operator, other
+                        ))
+                    }
+                };
+                let comparison = match operator {
+                    "<" => Comparison::Less,
+                    "<=" => Comparison::LessOrEqual,
+                    ">" => Comparison::Greater,
+                    _ => Comparison::GreaterOrEqual,
+                };
+                Ok(Filter::Compare(key, comparison, number))
+            }
+            _ => Err(format!("unknown filter operator \"{}\"", operator)),
+        }
+    }
+
+    pub fn evaluate(
+        &self,
+        layer: &vector_tile::Tile_Layer,
+        feature: &vector_tile::Tile_Feature,
+    ) -> bool {
+        self.matches(
+            &FeatureProperties::new(layer, feature),
+            feature.get_field_type(),
+        )
+    }
+
+    pub fn matches(
+        &self,
+        properties: &FeatureProperties,
+        geo_type: vector_tile::Tile_GeomType,
+    ) -> bool {
+        match self {
+            Filter::All(filters) => filters.iter().all(|f| f.matches(properties, geo_type)),
+            Filter::Any(filters) => filters.iter().any(|f| f.matches(properties, geo_type)),
+            Filter::Not(filter) => !filter.matches(properties, geo_type),
+            Filter::Has(key) => properties.get(key).is_some(),
+            Filter::Equals(key, literal) => match properties.get(key) {
+                Some(value) => literal.equals(&value),
+                None => false,
+            },
+            Filter::In(key, literals) => match properties.get(key) {
+                Some(value) => literals.iter().any(|literal| literal.equals(&value)),
+                None => false,
+            },
+            Filter::Compare(key, comparison, number) => match properties.get_f64(key) {
+                Some(value) => match comparison {
+                    Comparison::Less => value < *number,
+                    Comparison::LessOrEqual => value <= *number,
+                    Comparison::Greater => value > *number,
+                    Comparison::GreaterOrEqual => value >= *number,
+                },
+                None => false,
+            },
+            Filter::GeometryType(filter_type) => geo_type == *filter_type,
+        }
+    }
+}
+
+impl Literal {
+    pub fn equals(&self, value: &PropertyValue) -> bool {
+        match self {
+            Literal::String(s) => value.as_str() == Some(s.as_str()),
+            Literal::Number(n) => value.as_f64() == Some(*n),
+            Literal::Bool(b) => value.as_bool() == Some(*b),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for Filter {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let value = Value::deserialize(deserializer)?;
+        Filter::parse(&value).map_err(D::Error::custom)
+    }
+}
+
+fn negate_if(negate: bool, filter: Filter) -> Filter {
+    if negate {
+        Filter::Not(Box::new(filter))
+    } else {
+        filter
+    }
+}
+
+fn expect_arguments(operator: &str, arguments: &[Value], count: usize) -> Result<(), String> {
+    if arguments.len() != count {
+        return Err(format!(
+            "\"{}\" expects {} arguments, found {}",
+            operator,
+            count,
+            arguments.len()
+        ));
+    }
+    Ok(())
+}
+
+// Keys are plain strings, or `["get", key]` and `["geometry-type"]` expressions.
+fn parse_key(value: &Value) -> Result<String, String> {
+    match value {
+        Value::String(key) => Ok(key.clone()),
+        Value::Array(expression) => match (expression.first(), expression.get(1)) {
+            (Some(Value::String(get)), Some(Value::String(key)))
+                if get == "get" && expression.len() == 2 =>
+            {
+                Ok(key.clone())
+            }
+            (Some(Value::String(geometry_type)), None) if geometry_type == "geometry-type" => {
+                Ok(String::from("$type"))
+            }
+            _ => Err(format!("unsupported key expression {}", value)),
+        },
+        _ => Err(format!("expected a property key, found {}", value)),
+    }
+}
+
+fn parse_literal(value: &Value) -> Result<Literal, String> {
+    match value {
+        Value::String(s) => Ok(Literal::String(s.clone())),
+        Value::Number(n) => Ok(Literal::Number(n.as_f64().unwrap_or(0.0))),
+        Value::Bool(b) => Ok(Literal::Bool(*b)),
+        _ => Err(format!(
+            "expected a string, number or boolean, found {}",
+            value
+        )),
+    }
+}
+
+fn parse_geometry_type(value: &Value) -> Result<vector_tile::Tile_GeomType, String> {
+    match value.as_str() {
+        Some("Point") => Ok(vector_tile::Tile_GeomType::POINT),
+        Some("LineString") => Ok(vector_tile::Tile_GeomType::LINESTRING),
+        Some("Polygon") => Ok(vector_tile::Tile_GeomType::POLYGON),
+        _ => Err(format!(
+            "expected \"Point\", \"LineString\" or \"Polygon\", found {}",
+            value
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A polygon with a string, an integer, a double and a boolean property.
+    fn layer_and_feature() -> (vector_tile::Tile_Layer, vector_tile::Tile_Feature) {
+        let mut layer = vector_tile::Tile_Layer::new();
+        let mut feature = vector_tile::Tile_Feature::new();
+        let mut values = vec![];
+
+        let mut value = vector_tile::Tile_Value::new();
+        value.set_string_value(String::from("street"));
+        values.push(("class", value));
+        let mut value = vector_tile::Tile_Value::new();
+        value.set_int_value(30);
+        values.push(("height", value));
+        let mut value = vector_tile::Tile_Value::new();
+        value.set_double_value(2.5);
+        values.push(("levels", value));
+        let mut value = vector_tile::Tile_Value::new();
+        value.set_bool_value(true);
+        values.push(("oneway", value));
+
+        for (i, (key, value)) in values.into_iter().enumerate() {
+            layer.mut_keys().push(String::from(key));
+            layer.mut_values().push(value);
+            feature.mut_tags().push(i as u32);
+            feature.mut_tags().push(i as u32);
+        }
+        feature.set_field_type(vector_tile::Tile_GeomType::POLYGON);
+        (layer, feature)
+    }
+
+    fn check(cases: &[(&str, bool)]) {
+        let (layer, feature) = layer_and_feature();
+        for (filter, expected) in cases {
+            let parsed = Filter::parse(&serde_json::from_str(filter).unwrap())
+                .unwrap_or_else(|e| panic!("{}: {}", filter, e));
+            assert_eq!(parsed.evaluate(&layer, &feature), *expected, "{}", filter);
+        }
+    }
+
+    #[test]
+    fn comparisons() {
+        check(&[
+            (r#"["==", "class", "street"]"#, true),
+            (r#"["==", "class", "path"]"#, false),
+            (r#"["!=", "class", "path"]"#, true),
+            (r#"["==", "height", 30]"#, true),
+            (r#"["==", "height", "30"]"#, false),
+            (r#"["==", "oneway", true]"#, true),
+            (r#"["==", ["get", "levels"], 2.5]"#, true),
+            (r#"["==", "missing", "street"]"#, false),
+            (r#"["!=", "missing", "street"]"#, true),
+            (r#"["<", "height", 30]"#, false),
+            (r#"["<=", "height", 30]"#, true),
+            (r#"[">", "levels", 2]"#, true),
+            (r#"[">=", "levels", 3]"#, false),
+            (r#"[">", "class", 0]"#, false),
+            (r#"["==", "$type", "Polygon"]"#, true),
+            (r#"["==", ["geometry-type"], "LineString"]"#, false),
+        ]);
+    }
+
+    #[test]
+    fn membership() {
+        check(&[
+            (r#"["in", "class", "path", "street"]"#, true),
+            (r#"["in", "class", "path", "track"]"#, false),
+            (r#"["in", "height", 10, 30]"#, true),
+            (r#"["in", "missing", "street"]"#, false),
+            (r#"["!in", "class", "path", "track"]"#, true),
+            (r#"["!in", "class", "street"]"#, false),
+            (r#"["!in", "missing", "street"]"#, true),
+            (r#"["in", "$type", "Point", "Polygon"]"#, true),
+            (r#"["!in", "$type", "Point", "LineString"]"#, true),
+        ]);
+    }
+
+    #[test]
+    fn has() {
+        check(&[
+            (r#"["has", "class"]"#, true),
+            (r#"["has", "missing"]"#, false),
+            (r#"["!has", "class"]"#, false),
+            (r#"["!has", "missing"]"#, true),
+        ]);
+    }
+
+    #[test]
+    fn combinations() {
+        check(&[
+            (r#"["all"]"#, true),
+            (r#"["any"]"#, false),
+            (r#"["none"]"#, true),
+            (r#"["all", ["has", "class"], [">", "height", 10]]"#, true),
+            (r#"["all", ["has", "class"], [">", "height", 50]]"#, false),
+            (r#"["any", ["has", "missing"], [">", "height", 10]]"#, true),
+            (r#"["any", ["has", "missing"], [">", "height", 50]]"#, false),
+            (r#"["none", ["has", "missing"], [">", "height", 50]]"#, true),
+            (
+                r#"["none", ["has", "missing"], [">", "height", 10]]"#,
+                false,
+            ),
+            (r#"["!", ["==", "class", "street"]]"#, false),
+            (
+                r#"["all", ["any", ["==", "class", "path"], ["has", "oneway"]], ["!has", "missing"]]"#,
+                true,
+            ),
+            ("true", true),
+            ("false", false),
+        ]);
+    }
+
+    #[test]
+    fn malformed_filters_are_rejected() {
+        let cases = [
+            (r#""class""#, "expected a filter array"),
+            ("[]", "does not start with an operator"),
+            ("[1, 2]", "does not start with an operator"),
+            (r#"["~=", "class", "street"]"#, "unknown filter operator"),
+            (r#"["==", "class"]"#, "expects 2 arguments, found 1"),
+            (r#"["has"]"#, "expects 1 arguments, found 0"),
+            (r#"["in", "class"]"#, "expects a key and at least one value"),
+            (r#"["<", "height", "30"]"#, "expects a number"),
+            (
+                r#"["==", "class", ["street"]]"#,
+                "expected a string, number or boolean",
+            ),
+            (
+                r#"["==", "$type", "Circle"]"#,
+                "expected \"Point\", \"LineString\" or \"Polygon\"",
+            ),
+            (
+                r#"["==", ["length", "name"], 3]"#,
+                "unsupported key expression",
+            ),
+            (r#"["==", 3, 3]"#, "expected a property key"),
+            (
+                r#"["all", ["has", "class"], ["==", "class"]]"#,
+                "\"all\" argument 2",
+            ),
+        ];
+        for (filter, message) in cases.iter() {
+            match Filter::parse(&serde_json::from_str(filter).unwrap()) {
+                Ok(parsed) => panic!("{} parsed as {:?}", filter, parsed),
+                Err(e) => assert!(e.contains(message), "{}: {}", filter, e),
+            }
+        }
+    }
+}
@@ -0,0 +1,132 @@
+use super::error::Error;
+use super::scene::{self, Scene, SceneOptions};
+use super::style::Style;
+use super::tile_source::TileSource;
+use glutin::dpi::PhysicalSize;
+use glutin::platform::unix::HeadlessContextExt;
+use glutin::{Api, GlProfile, GlRequest};
+use std::os::raw::c_void;
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+// How long to wait for the tiles of the frame before giving up, e.g. on a source that never answers.
+const LOAD_TIMEOUT: Duration = Duration::from_secs(300);
+
+// Renders a single frame without a window and writes it to `output` as a PNG. The context comes from OSMesa, so
+// neither a display nor a GPU is needed; Mesa's llvmpipe renders in software.
+pub fn render_to_png(
+    style: &Style,
+    tile_source: Arc<dyn TileSource>,
+    options: &SceneOptions,
+    width: u32,
+    height: u32,
+    output: &str,
+) -> Result<(), Error> {
+    let context = glutin::ContextBuilder::new()
+        .with_gl(GlRequest::Specific(Api::OpenGl, (4, 5)))
+        .with_gl_profile(GlProfile::Core)
+        .build_osmesa(PhysicalSize::new(width, height))
+        .map_err(|e| Error::Render(format!("unable to create an OSMesa context: {}", e)))?;
+    let context = unsafe { context.make_current() }.map_err(|(_, e)| {
+        Error::Render(format!("unable to make the OSMesa context current: {}", e))
+    })?;
+    gl::load_with(|symbol| context.get_proc_address(symbol) as *const _);
+
+    let pixels = unsafe {
+        scene::init_gl();
+        let framebuffer = create_framebuffer(width, height)?;
+
+        let mut scene = Scene::new(style, tile_source, options)?;
+        let mut state = scene.new_state();
+        // Tiles arrive over several updates, the only frame is drawn once all of them are in.
+        let started = Instant::now();
+        loop {
+            scene.update(&mut state);
+            if !scene.is_loading() {
+                break;
+            }
+            if started.elapsed() > LOAD_TIMEOUT {
+                return Err(Error::Render(format!(
+                    "tiles were still loading after {}s",
+                    LOAD_TIMEOUT.as_secs()
+                )));
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+
+        gl::BindFramebuffer(gl::FRAMEBUFFER, framebuffer);
+        gl::Viewport(0, 0, width as i32, height as i32);
+        let projection_transform = scene.projection(width, height);
+        // Transforms returned by `before_render` callbacks, like the one keeping the ground plane below the
+        // camera, only take effect on the frame after, so one frame is drawn and thrown away first.
+        scene.draw(&state, &projection_transform);
+        scene.draw(&state, &projection_transform);
+
+        let mut pixels = vec![0u8; (width * height * 3) as usize];
+        gl::PixelStorei(gl::PACK_ALIGNMENT, 1);
+        gl::ReadPixels(
+            0,
+            0,
+            width as i32,
+            height as i32,
+            gl::RGB,
+            gl::UNSIGNED_BYTE,
+            pixels.as_mut_ptr() as *mut c_void,
+        );
+        pixels
+    };
+
+    // GL rows start at the bottom of the image.
+    let image = match image::RgbImage::from_raw(width, height, pixels) {
+        Some(image) => image,
+        None => {
+            return Err(Error::Render(String::from(
+                "unable to read back the rendered frame",
+            )))
+        }
+    };
+    image::imageops::flip_vertical(&image)
+        .save(output)
+        .map_err(|e| Error::Render(format!("{}: {}", output, e)))
+}
+
+// A framebuffer with a colour and depth attachment of the given size, so rendering does not depend on the
+// default framebuffer of the context.
+unsafe fn create_framebuffer(width: u32, height: u32) -> Result<u32, Error> {
+    let (mut framebuffer, mut color, mut depth) = (0, 0, 0);
+    gl::GenFramebuffers(1, &mut framebuffer);
+    gl::BindFramebuffer(gl::FRAMEBUFFER, framebuffer);
+
+    gl::GenRenderbuffers(1, &mut color);
+    gl::BindRenderbuffer(gl::RENDERBUFFER, color);
+    gl::RenderbufferStorage(gl::RENDERBUFFER, gl::RGBA8, width as i32, height as i32);
+    gl::FramebufferRenderbuffer(
+        gl::FRAMEBUFFER,
+        gl::COLOR_ATTACHMENT0,
+        gl::RENDERBUFFER,
+        color,
+    );
+
+    gl::GenRenderbuffers(1, &mut depth);
+    gl::BindRenderbuffer(gl::RENDERBUFFER, depth);
+    gl::RenderbufferStorage(
+        gl::RENDERBUFFER,
+        gl::DEPTH_COMPONENT24,
+        width as i32,
+        height as i32,
+    );
+    gl::FramebufferRenderbuffer(
+        gl::FRAMEBUFFER,
+        gl::DEPTH_ATTACHMENT,
+        gl::RENDERBUFFER,
+        depth,
+    );
+
+    if gl::CheckFramebufferStatus(gl::FRAMEBUFFER) != gl::FRAMEBUFFER_COMPLETE {
+        return Err(Error::Render(String::from(
+            "the offscreen framebuffer is incomplete",
+        )));
+    }
+    Ok(framebuffer)
+}
@@ -2,19 +2,33 @@ extern crate nalgebra_glm as glm;
 use std::sync::{Arc, Mutex, RwLock};
 use std::thread;
 mod camera;
-mod line_strings;
+mod colors;
+mod error;
+mod features;
+mod filter;
+#[cfg(target_os = "linux")]
+mod headless;
+mod mapbox_style;
 mod material;
 mod mesh;
 mod model;
 mod player;
 mod primitives;
+mod properties;
 mod renderable;
+mod scene;
 mod scene_graph;
 mod shader;
 mod state;
 mod skybox;
-mod tile_address;
+mod style;
+mod tile_builder;
+mod tile_cache;
+mod tile_manager;
+mod tile_source;
+mod tile_worker;
 mod util;
+mod zoom;
 use glutin::event::{
     DeviceEvent,
     ElementState::{Pressed, Released},
@@ -22,88 +36,243 @@ use glutin::event::{
     VirtualKeyCode::{self, *},
     WindowEvent,
 };
-mod polygons;
-use line_strings::{LineOptions, LineStringDecoder};
-use polygons::{PolygonDecoder, PolygonOptions};
-
 mod protos;
-use protos::vector_tile;
 
-use clap::{App, Arg};
-use flate2::read::GzDecoder;
+use clap::{App, Arg, ArgMatches, SubCommand};
+use error::Error;
 use glutin::event_loop::ControlFlow;
-use std::io::Read;
-use std::ptr;
+use std::str::FromStr;
+use vector_tile_geometry::tile_address;
 
 const SCREEN_W: u32 = 1500;
 const SCREEN_H: u32 = 1300;
-extern crate earcutr;
 
 fn main() {
+    if let Err(e) = run() {
+        eprintln!("{}", e);
+        std::process::exit(1);
+    }
+}
+
+// The value of the option `name`, or `default` when it is not given.
+fn parse_arg<T: FromStr>(args: &ArgMatches, name: &str, default: &str) -> Result<T, Error> {
+    let value = args.value_of(name).unwrap_or(default);
+    value.parse::<T>().map_err(|_| Error::Argument {
+        name: String::from(name),
+        value: String::from(value),
+    })
+}
+
+fn run() -> Result<(), Error> {
     let matches = App::new("Vector Tile Renderer")
         .arg(
             Arg::with_name("longitude")
                 .short("b")
                 .long("longitude")
                 .takes_value(true)
-                .help("center longitude"),
+                .help("center longitude")
+                .global(true),
         )
         .arg(
             Arg::with_name("latitude")
                 .short("c")
                 .long("latitude")
                 .takes_value(true)
-                .help("center latitude"),
+                .help("center latitude")
+                .global(true),
         )
         .arg(
             Arg::with_name("zoom")
                 .short("z")
                 .long("zoom")
                 .takes_value(true)
-                .help("zoom"),
+                .help("zoom")
+                .global(true),
         )
         .arg(
             Arg::with_name("tile_radius")
                 .short("t")
                 .long("tile_radius")
                 .takes_value(true)
-                .help("tile_radius"),
+                .help("tile_radius")
+                .global(true),
+        )
+        .arg(
+            Arg::with_name("lod_levels")
+                .short("l")
+                .long("lod_levels")
+                .takes_value(true)
+                .help("number of zoom levels the tile quadtree spans below --zoom")
+                .global(true),
         )
         .arg(
             Arg::with_name("api_key")
                 .short("a")
                 .long("api_key")
                 .takes_value(true)
-                .help("api_key"),
+                .help("api_key")
+                .global(true),
+        )
+        .arg(
+            Arg::with_name("source")
+                .short("s")
+                .long("source")
+                .takes_value(true)
+                .help("tile source: URL template with {z}/{x}/{y}, a {z}/{x}/{y}.pbf directory or an .mbtiles file")
+                .global(true),
+        )
+        .arg(
+            Arg::with_name("cache_dir")
+                .long("cache_dir")
+                .takes_value(true)
+                .help("directory used to cache downloaded tiles")
+                .global(true),
+        )
+        .arg(
+            Arg::with_name("cache_max_age")
+                .long("cache_max_age")
+                .takes_value(true)
+                .help("hours before a cached tile is revalidated")
+                .global(true),
+        )
+        .arg(
+            Arg::with_name("offline")
+                .long("offline")
+                .help("only load tiles from the cache")
+                .global(true),
+        )
+        .arg(
+            Arg::with_name("workers")
+                .short("w")
+                .long("workers")
+                .takes_value(true)
+                .help("number of threads used to fetch and build tiles")
+                .global(true),
+        )
+        .arg(
+            Arg::with_name("style")
+                .long("style")
+                .takes_value(true)
+                .help("path to a JSON style file")
+                .global(true),
+        )
+        .arg(
+            Arg::with_name("yaw")
+                .long("yaw")
+                .takes_value(true)
+                .allow_hyphen_values(true)
+                .help("camera yaw in degrees")
+                .global(true),
+        )
+        .arg(
+            Arg::with_name("pitch")
+                .long("pitch")
+                .takes_value(true)
+                .allow_hyphen_values(true)
+                .help("camera pitch in degrees")
+                .global(true),
+        )
+        .arg(
+            Arg::with_name("distance")
+                .long("distance")
+                .takes_value(true)
+                .help("camera distance from the player in metres")
+                .global(true),
+        )
+        .subcommand(
+            SubCommand::with_name("render")
+                .about("renders one frame without a window and writes it to a PNG")
+                .arg(
+                    Arg::with_name("output")
+                        .short("o")
+                        .long("output")
+                        .takes_value(true)
+                        .required(true)
+                        .help("path of the PNG to write"),
+                )
+                .arg(
+                    Arg::with_name("width")
+                        .long("width")
+                        .takes_value(true)
+                        .help("image width in pixels"),
+                )
+                .arg(
+                    Arg::with_name("height")
+                        .long("height")
+                        .takes_value(true)
+                        .help("image height in pixels"),
+                ),
         )
         .get_matches();
 
-    let center_lat = matches.value_of("latitude").unwrap_or("40.706042");
-    let center_lat = match center_lat.parse::<f64>() {
-        Ok(n) => n,
-        Err(_) => panic!("unable to parse center_lat"),
-    };
-
-    let center_lon = matches.value_of("longitude").unwrap_or("-74.011991");
-    let center_lon = match center_lon.parse::<f64>() {
-        Ok(n) => n,
-        Err(_) => panic!("unable to parse center_lon"),
-    };
-
-    let zoom = matches.value_of("zoom").unwrap_or("17");
-    let zoom = match zoom.parse::<u32>() {
-        Ok(n) => n,
-        Err(_) => panic!("unable to parse zoom"),
-    };
-
-    let tile_radius = matches.value_of("tile_radius").unwrap_or("2");
-    let tile_radius = match tile_radius.parse::<u32>() {
-        Ok(n) => n,
-        Err(_) => panic!("unable to parse tile_radius"),
+    // Options can be given before or after the subcommand.
+    let render_args = matches.subcommand_matches("render");
+    let args: &ArgMatches = render_args.unwrap_or(&matches);
+
+    let center_lat: f64 = parse_arg(args, "latitude", "40.706042")?;
+    let center_lon: f64 = parse_arg(args, "longitude", "-74.011991")?;
+    let zoom: u32 = parse_arg(args, "zoom", "17")?;
+    let tile_radius: u32 = parse_arg(args, "tile_radius", "2")?;
+    let lod_levels: u32 = parse_arg(args, "lod_levels", "2")?;
+
+    let api_key = args.value_of("api_key");
+    let source = args
+        .value_of("source")
+        .unwrap_or(tile_source::MAPBOX_STREETS_URL);
+    let workers: usize = parse_arg(args, "workers", "4")?;
+
+    let style_path = args.value_of("style").unwrap_or("./styles/default.json");
+    let style = style::load_style(style_path)?;
+
+    let offline = args.is_present("offline");
+    let cache_dir = args.value_of("cache_dir").unwrap_or("cache");
+    let cache_max_age: u64 = parse_arg(args, "cache_max_age", "168")?;
+    let cache_max_age = std::time::Duration::from_secs(cache_max_age * 60 * 60);
+
+    let mut tile_source = tile_source::open_tile_source(source, api_key, offline)?;
+    if tile_source::is_remote(source) {
+        tile_source = Box::new(tile_cache::CachedSource::new(
+            tile_source,
+            tile_cache::TileCache::new(cache_dir),
+            cache_max_age,
+            offline,
+        ));
+    }
+    println!("Tile source\t: {}", tile_source.name());
+    let tile_source: Arc<dyn tile_source::TileSource> = Arc::from(tile_source);
+
+    let camera_yaw: f32 = parse_arg(args, "yaw", "-90")?;
+    let camera_pitch: f32 = parse_arg(args, "pitch", "-30")?;
+    let camera_distance: f32 = parse_arg(args, "distance", "70")?;
+
+    let scene_options = scene::SceneOptions {
+        latitude: center_lat,
+        longitude: center_lon,
+        zoom,
+        tile_radius,
+        lod_levels,
+        workers,
+        camera_yaw,
+        camera_pitch,
+        camera_distance,
     };
 
-    let api_key = matches.value_of("api_key").expect("--api_key is required");
-    let api_key = String::from(api_key);
+    if let Some(render_args) = render_args {
+        let width: u32 = parse_arg(render_args, "width", "800")?;
+        let height: u32 = parse_arg(render_args, "height", "600")?;
+
+        let output = render_args.value_of("output").unwrap();
+        #[cfg(target_os = "linux")]
+        let result =
+            headless::render_to_png(&style, tile_source, &scene_options, width, height, output);
+        #[cfg(not(target_os = "linux"))]
+        let result = Err(Error::Render(String::from(
+            "rendering without a window needs Linux",
+        )));
+        result?;
+        println!("Wrote {}", output);
+        return Ok(());
+    }
 
     // Set up the necessary objects to deal with windows and event handling
     let el = glutin::event_loop::EventLoop::new();
@@ -112,7 +281,9 @@ fn main() {
         .with_resizable(false)
         .with_inner_size(glutin::dpi::LogicalSize::new(SCREEN_W, SCREEN_H));
     let cb = glutin::ContextBuilder::new().with_vsync(true);
-    let windowed_context = cb.build_windowed(wb, &el).unwrap();
+    let windowed_context = cb
+        .build_windowed(wb, &el)
+        .map_err(|e| Error::Render(format!("unable to open a window: {}", e)))?;
     /*   windowed_context
         .window()
         .set_cursor_grab(true)
@@ -129,421 +300,28 @@ fn main() {
     let mouse_delta = Arc::clone(&arc_mouse_delta);
 
     // Spawn a separate thread for rendering, so event handling doesn't block rendering
-    let render_thread = thread::spawn(move || {
+    let render_thread = thread::spawn(move || -> Result<(), Error> {
         // Acquire the OpenGL Context and load the function pointers. This has to be done inside of the rendering thread, because
         // an active OpenGL context cannot safely traverse a thread boundary
         let context = unsafe {
-            let c = windowed_context.make_current().unwrap();
+            let c = windowed_context.make_current().map_err(|(_, e)| {
+                Error::Render(format!("unable to make the window context current: {}", e))
+            })?;
             gl::load_with(|symbol| c.get_proc_address(symbol) as *const _);
             c
         };
 
-        // build shaders
-        let skybox_shader = shader::Shader::new("shaders/skybox.vert", "shaders/skybox.frag");
-
-        let shader_color = shader::Shader::new("./shaders/color.vert", "./shaders/color.frag");
-
-        let mut green_mat = material::Material::new(shader_color);
-        green_mat.set_vec4("u_color", 0.1, 0.56, 0.121, 1.0);
-        let mut dark_green_mat = material::Material::new(shader_color);
-        dark_green_mat.set_vec4("u_color", 0.0, 0.078, 0.05, 1.0);
-        let mut yellow_mat = material::Material::new(shader_color);
-        yellow_mat.set_vec4("u_color", 1.0, 0.68, 0.0001, 1.0);
-        let mut red_mat = material::Material::new(shader_color);
-        red_mat.set_vec4("u_color", 1.0, 0.1, 0.1, 1.0);
-        let mut blue_mat = material::Material::new(shader_color);
-        blue_mat.set_vec4("u_color", 0.1, 0.1, 1.0, 1.0);
-        let mut gray_mat = material::Material::new(shader_color);
-        gray_mat.set_vec4("u_color", 0.5, 0.5, 0.5, 1.0);
-        let mut dark_gray_mat = material::Material::new(shader_color);
-        dark_gray_mat.set_vec4("u_color", 0.1, 0.1, 0.1, 1.0);
-
-        let shader_color_lit =
-            shader::Shader::new("./shaders/color.vert", "./shaders/color_lit.frag");
-        let mut blue_lit_mat = material::Material::new(shader_color_lit);
-        blue_lit_mat.set_vec4("u_color", 0.1, 0.1, 1.0, 1.0);
-        let mut gray_lit_mat = material::Material::new(shader_color_lit);
-        gray_lit_mat.set_vec4("u_color", 0.5, 0.5, 0.5, 1.0);
-        let mut light_gray_lit_mat = material::Material::new(shader_color_lit);
-        light_gray_lit_mat.set_vec4("u_color", 0.9, 0.9, 0.9, 1.0);
-
-        let texture_shader =
-            shader::Shader::new("./shaders/texture.vert", "./shaders/texture_phong.frag");
-        let texture_mat = material::Material::new(texture_shader);
-
-        // create models
-        let root = scene_graph::SceneNode::new(scene_graph::NodeType::None);
-        let mut graph = scene_graph::SceneGraph::new(root);
-
-        let center_tile = tile_address::latlon_to_tile_address(center_lat, center_lon, zoom);
-        let tiles = center_tile.get_tiles(tile_radius);
-
-        let options = vec![
-            FeatureOption {
-                layer: String::from("road"),
-                material: dark_gray_mat.clone(),
-                filter: has_key_any_value(
-                    String::from("class"),
-                    vec![
-                        String::from("street"),
-                        String::from("primary"),
-                        String::from("secondary"),
-                        String::from("motorway_link"),
-                        String::from("motorway"),
-                        String::from("path"),
-                        String::from("trunk"),
-                    ],
-                ),
-                geo_type: vector_tile::Tile_GeomType::LINESTRING,
-                polygon_options: None,
-                line_string_options: Some(LineOptions::new(0.11, 5.0)),
-                texture: None,
-            },
-            FeatureOption {
-                layer: String::from("road"),
-                material: gray_mat.clone(),
-                filter: has_key_value(String::from("class"), String::from("path")),
-                geo_type: vector_tile::Tile_GeomType::LINESTRING,
-                polygon_options: None,
-                line_string_options: Some(LineOptions::new(0.12, 3.0)),
-                texture: None,
-            },
-            FeatureOption {
-                layer: String::from("road"),
-                material: yellow_mat.clone(),
-                filter: has_key_any_value(
-                    String::from("class"),
-                    vec![String::from("major_rail"), String::from("service_rail")],
-                ),
-                geo_type: vector_tile::Tile_GeomType::LINESTRING,
-                polygon_options: None,
-                line_string_options: Some(LineOptions::new(0.15, 1.0)),
-                texture: None,
-            },
-            FeatureOption {
-                layer: String::from("motorway_junction"),
-                material: yellow_mat.clone(),
-                filter: none_filter(),
-                geo_type: vector_tile::Tile_GeomType::LINESTRING,
-                polygon_options: None,
-                line_string_options: Some(LineOptions::new(0.15, 1.0)),
-                texture: None,
-            },
-            FeatureOption {
-                layer: String::from("building"),
-                material: gray_lit_mat.clone(),
-                filter: none_filter(),
-                geo_type: vector_tile::Tile_GeomType::POLYGON,
-                polygon_options: Some(PolygonOptions {
-                    max_height: 1.0,
-                    min_height: 0.0,
-                    build_walls: true,
-                }),
-                line_string_options: None,
-                texture: None,
-            },
-            FeatureOption {
-                layer: String::from("structure"),
-                material: gray_lit_mat.clone(),
-                filter: none_filter(),
-                geo_type: vector_tile::Tile_GeomType::POLYGON,
-                polygon_options: Some(PolygonOptions {
-                    max_height: 1.0,
-                    min_height: 0.0,
-                    build_walls: true,
-                }),
-                line_string_options: None,
-                texture: None,
-            },
-            FeatureOption {
-                layer: String::from("water"),
-                material: blue_lit_mat.clone(),
-                filter: none_filter(),
-                geo_type: vector_tile::Tile_GeomType::POLYGON,
-                polygon_options: Some(PolygonOptions {
-                    max_height: 0.0,
-                    min_height: 0.0,
-                    build_walls: false,
-                }),
-                line_string_options: None,
-                texture: None,
-            },
-            FeatureOption {
-                layer: String::from("landuse"),
-                material: texture_mat.clone(),
-                filter: has_not_key_value(String::from("type"), String::from("rock")),
-                geo_type: vector_tile::Tile_GeomType::POLYGON,
-                polygon_options: Some(PolygonOptions {
-                    max_height: -0.1,
-                    min_height: 0.0,
-                    build_walls: false,
-                }),
-                line_string_options: None,
-                texture: Some(String::from("./assets/textures/grass.jpg")),
-            },
-           FeatureOption {
-                layer: String::from("landuse"),
-                material: gray_mat.clone(),
-                filter: has_key_value(String::from("type"), String::from("rock")),
-                geo_type: vector_tile::Tile_GeomType::POLYGON,
-                polygon_options: Some(PolygonOptions {
-                    max_height: -0.05,
-                    min_height: 0.0,
-                    build_walls: false,
-                }),
-                line_string_options: None,
-                texture: None,
-            },
-        ];
-
-        let scale = 100.0;
-        let inverse_tile_scale =
-            1.0 / (tile_address::EARTH_CIRCUMFERENCE_METERS as f64 / (1 << zoom) as f64);
-        for tile_address in tiles {
-            let url = format!(
-                "https://api.mapbox.com/v4/mapbox.mapbox-streets-v8/{}/{}/{}.vector.pbf?access_token={}",
-                tile_address.z, tile_address.x, tile_address.y, api_key
-            );
-
-            let mut reader = ureq::get(&url[..]).call().into_reader();
-            let mut bytes: Vec<u8> = Vec::new();
-            reader.read_to_end(&mut bytes).unwrap();
-            let mut decompressor = GzDecoder::new(&bytes[..]);
-            let mut bytes: Vec<u8> = Vec::new();
-            decompressor.read_to_end(&mut bytes).expect("Invalid token");
-            let tile: vector_tile::Tile = protobuf::parse_from_bytes(&bytes).unwrap();
-
-            let offset_x: i32 = tile_address.x as i32 - center_tile.x as i32;
-            let offset_y: i32 = tile_address.y as i32 - center_tile.y as i32;
-
-            let translation = glm::translate(
-                &glm::identity(),
-                &glm::vec3(offset_x as f32 * scale, 0.0, offset_y as f32 * scale),
-            );
-            let tile_transform =
-                translation * glm::scale(&glm::identity(), &glm::vec3(scale, 5.0, scale));
-
-            for layer in tile.get_layers() {
-                let extent = layer.get_extent();
-                for option in &options {
-                    if option.layer.as_str() != layer.get_name() {
-                        continue;
-                    }
-
-                    let features = get_filtered_features(layer, &option.filter);
-
-                    if option.geo_type == vector_tile::Tile_GeomType::POLYGON {
-                        let polygon_options = option.polygon_options.as_ref().unwrap();
-                        for feature in features {
-                            let geo_type = feature.get_field_type();
-
-                            if geo_type != option.geo_type {
-                                continue;
-                            }
-
-                            let geometry = feature.get_geometry();
-                            let mut options = polygon_options.clone();
-                            let height = get_float(String::from("height"), &layer, &feature);
-                            let min_height =
-                                get_float(String::from("min_height"), &layer, &feature);
-                            if let Some(min_height) = min_height {
-                                options.min_height =
-                                    ((min_height as f64) * inverse_tile_scale) as f32 * 7.0;
-                            }
-
-                            if let Some(height) = height {
-                                options.max_height = options.min_height
-                                    + ((height as f64) * inverse_tile_scale) as f32 * 7.0;
-                            }
-
-                            let mut polygon_builder = polygons::PolygonBuilder::new(options);
-                            let mut decoder =
-                                PolygonDecoder::new(extent, geometry, &mut polygon_builder);
-                            decoder.decode();
-
-                            let m = polygon_builder.output_mesh;
-                            let indices = m.indices;
-                            let vertices = m.vertices;
-                            let uvs = m.uvs;
-                            let normals = m.normals;
-                            let mut mesh = mesh::Mesh::new_full(vertices, indices, normals, uvs);
-
-                            if let Some(texture) = &option.texture {
-                                mesh.set_texture(texture.as_str());
-                            }
-
-                            let poly_model = model::Model::new(
-                                String::from("wdwdwd"),
-                                vec![mesh],
-                                option.material.clone(),
-                                tile_transform,
-                                Some(Box::new(|transform, mat, s| {
-                                    mat.set_vec3(
-                                        "u_viewPos",
-                                        s.camera_position.x,
-                                        s.camera_position.y,
-                                        s.camera_position.z,
-                                    );
-                                    *transform
-                                })),
-                            );
-                            let poly_node = scene_graph::SceneNode::new_model(poly_model);
-                            graph.root.add_child(&poly_node);
-                        }
-                    } else if option.geo_type == vector_tile::Tile_GeomType::LINESTRING {
-                        let line_string_options = option.line_string_options.as_ref().unwrap();
-                        for feature in features {
-                            let geo_type = feature.get_field_type();
-
-                            if geo_type != option.geo_type {
-                                continue;
-                            }
-
-                            let geometry = feature.get_geometry();
-                            let thickness = (line_string_options.width as f64 * inverse_tile_scale)
-                                as f32
-                                * 1.0;
-                            let height = line_string_options.height;
-
-                            let mut decoder =
-                                LineStringDecoder::new(extent, geometry, thickness, height);
-                            decoder.decode();
-                            let m = decoder.output_mesh;
-                            let indices = m.indices;
-                            let vertices = m.vertices;
-                            let uvs = m.uvs;
-                            let normals = m.normals;
-                            if vertices.len() < 3 {
-                                continue;
-                            }
-                            let mesh = mesh::Mesh::new_full(vertices, indices, normals, uvs);
-
-                            let poly_model = model::Model::new(
-                                String::from("line"),
-                                vec![mesh],
-                                option.material.clone(),
-                                tile_transform,
-                                Some(Box::new(|transform, mat, s| {
-                                    mat.set_vec3(
-                                        "u_viewPos",
-                                        s.camera_position.x,
-                                        s.camera_position.y,
-                                        s.camera_position.z,
-                                    );
-                                    *transform
-                                })),
-                            );
-
-                            let poly_node = scene_graph::SceneNode::new_model(poly_model);
-                            graph.root.add_child(&poly_node);
-                        }
-                    }
-                }
-            }
-        }
-
-        let mut map_plane_transform: glm::Mat4 = glm::translate(
-            &glm::identity(),
-            &glm::vec3(
-                -scale * (tile_radius as f32 + 1.0) * 1.5,
-                -0.9,
-                1.5 * scale * (tile_radius as f32 + 1.0),
-            ),
-        );
-        map_plane_transform = glm::rotate_x(&map_plane_transform, -90_f32.to_radians());
-        map_plane_transform = glm::scale(
-            &map_plane_transform,
-            &glm::vec3(
-                3.0 * scale * (tile_radius as f32 + 1.0),
-                3.0 * scale * (tile_radius as f32 + 1.0),
-                3.0 * scale * (tile_radius as f32 + 1.0),
-            ),
-        );
-
-        let mut plane_mesh = primitives::generate_quad();
-        let normals = util::calculate_normals(&plane_mesh.vertices, &plane_mesh.indices);
-        plane_mesh.set_normals(normals);
-
-        let map_plane_model = model::Model::new(
-            String::from("plane_mesh"),
-            vec![plane_mesh],
-            dark_green_mat.clone(),
-            map_plane_transform,
-            None,
-        );
-
-        let plane_node = scene_graph::SceneNode::new_model(map_plane_model);
-        graph.root.add_child(&plane_node);
-
-        let player = player::Player::new();
-        let mut player_node = scene_graph::SceneNode::new_player(player);
-        graph.root.add_child(&player_node);
-
-        let yaw: f32 = -90.0;
-        let pitch: f32 = -30.0;
-        let radius = 30.0;
-        let camera = camera::Camera::new(yaw, pitch, radius);
-        let mut camera_node = scene_graph::SceneNode::new_camera(camera);
-        player_node.add_child(&camera_node);
-
-        let camera = &mut camera_node.value;
-        let camera = match camera {
-            scene_graph::NodeType::Camera(camera) => camera,
-            _ => panic!("not camera"),
-        };
-
-        let player = &mut player_node.value;
-        let player = match player {
-            scene_graph::NodeType::Player(player) => player,
-            _ => panic!("not player"),
+        let mut scene = unsafe {
+            scene::init_gl();
+            scene::Scene::new(&style, tile_source, &scene_options)?
         };
+        let projection_transform = scene.projection(SCREEN_W, SCREEN_H);
 
-        let (skybox_vao, skybox_texture) = unsafe { skybox::create_skybox() };
-
-        // Set up openGLprojection
-        unsafe {
-            gl::Enable(gl::CULL_FACE);
-            gl::Enable(gl::DEPTH_TEST);
-            gl::Disable(gl::MULTISAMPLE);
-            gl::Enable(gl::BLEND);
-            gl::BlendFunc(gl::SRC_ALPHA, gl::ONE_MINUS_SRC_ALPHA);
-            gl::Enable(gl::DEBUG_OUTPUT_SYNCHRONOUS);
-            gl::DebugMessageCallback(Some(util::debug_callback), ptr::null());
-            // gl::PolygonMode(gl::FRONT_AND_BACK, gl::LINE);
-            // Print some diagnostics
-            println!(
-                "{}: {}",
-                util::get_gl_string(gl::VENDOR),
-                util::get_gl_string(gl::RENDERER)
-            );
-            println!("OpenGL\t: {}", util::get_gl_string(gl::VERSION));
-            println!(
-                "GLSL\t: {}",
-                util::get_gl_string(gl::SHADING_LANGUAGE_VERSION)
-            );
-        }
+        let mut state = scene.new_state();
 
         let first_frame_time = std::time::Instant::now();
         let mut last_frame_time = first_frame_time;
         // The main rendering loop
-
-        let projection_transform: glm::Mat4 = glm::perspective(
-            SCREEN_W as f32 / SCREEN_H as f32,
-            45_f32.to_radians(),
-            1.0,
-            1000.0,
-        );
-
-        let mut state = state::State {
-            camera_position: camera.position,
-            elapsed: 0.0,
-            delta_time: 0.0,
-            frame_num: 0,
-            pressed_keys: vec![],
-            delta_x: 0.0,
-            delta_y: 0.0,
-        };
-
         let mut frame_num = 0;
         loop {
             let now = std::time::Instant::now();
@@ -556,7 +334,7 @@ fn main() {
             state.frame_num = frame_num;
 
             if let Ok(mut delta) = mouse_delta.lock() {
-                player.process_mouse(delta.0, delta.1);
+                scene.player().process_mouse(delta.0, delta.1);
                 state.delta_x = delta.0;
                 state.delta_y = delta.1;
                 *delta = (0.0, 0.0);
@@ -564,41 +342,15 @@ fn main() {
 
             if let Ok(keys) = pressed_keys.lock() {
                 state.pressed_keys = keys.clone();
-                player.process_keyboard(keys, delta_time);
+                scene.player().process_keyboard(keys, delta_time);
             }
 
-            unsafe {
-                graph.update_transforms(&state);
-                state.camera_position = (camera.parent_transform
-                    * glm::vec4(camera.position.x, camera.position.y, camera.position.z, 1.0))
-                .xyz();
-
-                gl::ClearColor(0.1, 0.1, 0.1, 1.0);
-                gl::Clear(gl::COLOR_BUFFER_BIT | gl::DEPTH_BUFFER_BIT);
-
-                // using change of coordinates:
-                let view_transform = camera.get_view_transform();
-                graph.draw_scene(&state, &view_transform, &projection_transform);
-
-                gl::DepthFunc(gl::LEQUAL);
-                skybox_shader.activate();
-                skybox_shader.set_int("skybox", 0);
-
-                // remove translation from the view matrix
-                let mut view = view_transform;
-                view = glm::set_column(&view, 3, &glm::vec4(0.0, 0.0, 0.0, 1.0));
-                skybox_shader.set_mat4("view_transform", &view);
-                skybox_shader.set_mat4("projection", &projection_transform);
+            scene.update(&mut state);
+            unsafe { scene.draw(&state, &projection_transform) };
 
-                // skybox cube
-                gl::BindVertexArray(skybox_vao);
-                gl::ActiveTexture(gl::TEXTURE0);
-                gl::BindTexture(gl::TEXTURE_CUBE_MAP, skybox_texture);
-                gl::DrawArrays(gl::TRIANGLES, 0, 36);
-                gl::DepthFunc(gl::LESS);
-            }
-
-            context.swap_buffers().unwrap();
+            context
+                .swap_buffers()
+                .map_err(|e| Error::Render(e.to_string()))?;
         }
     });
 
@@ -606,11 +358,14 @@ fn main() {
     let render_thread_healthy = Arc::new(RwLock::new(true));
     let render_thread_watchdog = Arc::clone(&render_thread_healthy);
     thread::spawn(move || {
-        if !render_thread.join().is_ok() {
-            if let Ok(mut health) = render_thread_watchdog.write() {
-                println!("Render thread panicked!");
-                *health = false;
-            }
+        // The render thread only ever stops when something went wrong.
+        match render_thread.join() {
+            Ok(Err(e)) => eprintln!("{}", e),
+            Err(_) => println!("Render thread panicked!"),
+            Ok(Ok(())) => {}
+        }
+        if let Ok(mut health) = render_thread_watchdog.write() {
+            *health = false;
         }
     });
 
@@ -683,129 +438,3 @@ fn main() {
         }
     });
 }
-
-struct FeatureOption {
-    pub layer: String,
-    pub material: material::Material,
-    pub filter: FilterFunc,
-    pub geo_type: vector_tile::Tile_GeomType,
-    pub polygon_options: Option<PolygonOptions>,
-    pub texture: Option<String>,
-    pub line_string_options: Option<LineOptions>,
-}
-
-type FilterFunc = Box<dyn Fn(&vector_tile::Tile_Layer, &vector_tile::Tile_Feature) -> bool>;
-
-pub fn get_filtered_features<'a>(
-    layer: &'a vector_tile::Tile_Layer,
-    filter: &'a FilterFunc,
-) -> impl Iterator<Item = &'a vector_tile::Tile_Feature> {
-    layer
-        .get_features()
-        .iter()
-        .filter(move |feature| filter(layer, feature))
-}
-
-pub fn none_filter() -> FilterFunc {
-    return Box::new(
-        |layer: &vector_tile::Tile_Layer, feature: &vector_tile::Tile_Feature| {
-            return true;
-        },
-    );
-}
-
-pub fn has_key_value(key: String, value: String) -> FilterFunc {
-    return Box::new(
-        move |layer: &vector_tile::Tile_Layer, feature: &vector_tile::Tile_Feature| {
-            let tags = feature.get_tags();
-            let mut i: usize = 0;
-            while i < tags.len() {
-                let prop_index = tags[i];
-                let curr_key = &layer.keys[prop_index as usize];
-                if curr_key == key.as_str() {
-                    let values = layer.get_values();
-                    let value_index = tags[i + 1];
-                    let curr_value = values[value_index as usize].get_string_value();
-                    if curr_value == value {
-                        return true;
-                    }
-                }
-                i += 2;
-            }
-
-            return false;
-        },
-    );
-}
-
-pub fn has_not_key_value(key: String, value: String) -> FilterFunc {
-    return Box::new(
-        move |layer: &vector_tile::Tile_Layer, feature: &vector_tile::Tile_Feature| {
-            let tags = feature.get_tags();
-            let mut i: usize = 0;
-            while i < tags.len() {
-                let prop_index = tags[i];
-                let curr_key = &layer.keys[prop_index as usize];
-                if curr_key == key.as_str() {
-                    let values = layer.get_values();
-                    let value_index = tags[i + 1];
-                    let curr_value = values[value_index as usize].get_string_value();
-                    if curr_value == value {
-                        return false;
-                    }
-                }
-                i += 2;
-            }
-
-            return true;
-        },
-    );
-}
-
-pub fn has_key_any_value(key: String, values: Vec<String>) -> FilterFunc {
-    return Box::new(
-        move |layer: &vector_tile::Tile_Layer, feature: &vector_tile::Tile_Feature| {
-            let tags = feature.get_tags();
-            let mut i: usize = 0;
-            while i < tags.len() {
-                let prop_index = tags[i];
-                let curr_key = &layer.keys[prop_index as usize];
-                if curr_key == key.as_str() {
-                    let layer_values = layer.get_values();
-                    let value_index = tags[i + 1];
-                    let curr_value = layer_values[value_index as usize].get_string_value();
-                    for value in &values {
-                        if curr_value == value {
-                            return true;
-                        }
-                    }
-                }
-                i += 2;
-            }
-
-            return false;
-        },
-    );
-}
-
-pub fn get_float(
-    key: String,
-    layer: &vector_tile::Tile_Layer,
-    feature: &vector_tile::Tile_Feature,
-) -> Option<f64> {
-    let tags = feature.get_tags();
-    let mut i: usize = 0;
-    while i < tags.len() {
-        let prop_index = tags[i];
-        let curr_key = &layer.keys[prop_index as usize];
-        if curr_key == key.as_str() {
-            let layer_values = layer.get_values();
-            let value_index = tags[i + 1];
-            let curr_value = layer_values[value_index as usize].get_double_value();
-            return Some(curr_value);
-        }
-        i += 2;
-    }
-
-    return None;
-}
@@ -0,0 +1,870 @@
+use super::filter::Filter;
+use super::style::{
+    ColorStyle, FillStyle, LayerStyle, LayerType, LineStyle, PointStyle, PropertyColorStyle, Style,
+};
+use super::zoom::ZoomValue;
+use serde_json::Value;
+use std::collections::HashMap;
+
+// Imports the subset of the Mapbox GL style specification that maps onto the renderer: `fill`,
+// `fill-extrusion`, `line` and `circle` layers. Anything else is skipped and reported in the returned warnings.
+pub fn import_style(document: &Value) -> Result<(Style, Vec<String>), String> {
+    let layers = document
+        .get("layers")
+        .and_then(|layers| layers.as_array())
+        .ok_or_else(|| String::from("Mapbox GL style has no \"layers\" array"))?;
+
+    let mut warnings = vec![];
+    let mut style_layers = vec![];
+    let mut line_count = 0;
+    for (i, layer) in layers.iter().enumerate() {
+        let id = layer
+            .get("id")
+            .and_then(|id| id.as_str())
+            .map(String::from)
+            .unwrap_or_else(|| format!("layers[{}]", i));
+
+        let mut layer_warnings = vec![];
+        if let Some(style_layer) = import_layer(layer, line_count, &mut layer_warnings) {
+            if style_layer.layer_type == LayerType::Line {
+                line_count += 1;
+            }
+            style_layers.push(style_layer);
+        }
+        for warning in layer_warnings {
+            warnings.push(format!("{}: {}", id, warning));
+        }
+    }
+
+    let style = Style {
+        layers: style_layers,
+    };
+    style.validate()?;
+    Ok((style, warnings))
+}
+
+pub fn is_mapbox_style(document: &Value) -> bool {
+    document.get("version").is_some() && document.get("sources").is_some()
+}
+
+fn import_layer(
+    layer: &Value,
+    line_count: usize,
+    warnings: &mut Vec<String>,
+) -> Option<LayerStyle> {
+    let layer_type = layer.get("type").and_then(|t| t.as_str()).unwrap_or("");
+    if !["fill", "fill-extrusion", "line", "circle"].contains(&layer_type) {
+        warnings.push(format!("layer type \"{}\" is not supported", layer_type));
+        return None;
+    }
+
+    let visibility = layer
+        .get("layout")
+        .and_then(|layout| layout.get("visibility"))
+        .and_then(|visibility| visibility.as_str());
+    if visibility == Some("none") {
+        return None;
+    }
+
+    let source_layer = match layer.get("source-layer").and_then(|s| s.as_str()) {
+        Some(source_layer) => String::from(source_layer),
+        None => {
+            warnings.push(String::from("layer has no \"source-layer\""));
+            return None;
+        }
+    };
+
+    let min_zoom = layer
+        .get("minzoom")
+        .and_then(|z| z.as_f64())
+        .map(|z| z as f32);
+    let max_zoom = layer
+        .get("maxzoom")
+        .and_then(|z| z.as_f64())
+        .map(|z| z as f32);
+
+    let filter = match layer.get("filter") {
+        Some(filter) => match Filter::parse(filter) {
+            Ok(filter) => Some(filter),
+            Err(e) => {
+                warnings.push(format!("skipped, {}", e));
+                return None;
+            }
+        },
+        None => None,
+    };
+
+    let empty = serde_json::Map::new();
+    let paint = layer
+        .get("paint")
+        .and_then(|paint| paint.as_object())
+        .unwrap_or(&empty);
+
+    let mut style_layer = LayerStyle {
+        source_layer,
+        layer_type: LayerType::Fill,
+        filter,
+        shader: String::from("color"),
+        color: None,
+        texture: None,
+        fill: None,
+        line: None,
+        point: None,
+        min_zoom,
+        max_zoom,
+    };
+
+    let supported: Vec<&str> = match layer_type {
+        "fill" => {
+            let supported = vec!["fill-color", "fill-opacity"];
+            style_layer.color = Some(import_color(paint, "fill-color", "fill-opacity", warnings));
+            style_layer.fill = Some(FillStyle {
+                height: ZoomValue::Constant(0.0),
+                min_height: ZoomValue::Constant(0.0),
+                height_property: None,
+                min_height_property: None,
+                walls: false,
+            });
+            supported
+        }
+        "fill-extrusion" => {
+            let supported = vec![
+                "fill-extrusion-color",
+                "fill-extrusion-opacity",
+                "fill-extrusion-height",
+                "fill-extrusion-base",
+            ];
+            style_layer.shader = String::from("color_lit");
+            style_layer.color = Some(import_color(
+                paint,
+                "fill-extrusion-color",
+                "fill-extrusion-opacity",
+                warnings,
+            ));
+            let (height, height_property) = import_height(paint, "fill-extrusion-height", warnings);
+            let (min_height, min_height_property) =
+                import_height(paint, "fill-extrusion-base", warnings);
+            style_layer.fill = Some(FillStyle {
+                height,
+                min_height,
+                height_property,
+                min_height_property,
+                walls: true,
+            });
+            supported
+        }
+        "line" => {
+            let supported = vec!["line-color", "line-opacity", "line-width"];
+            style_layer.layer_type = LayerType::Line;
+            style_layer.color = Some(import_color(paint, "line-color", "line-opacity", warnings));
+            let width = import_number(paint, "line-width", 1.0, warnings);
+            // Stack line layers in style order so later layers are drawn on top.
+            style_layer.line = Some(LineStyle {
+                width,
+                height: 0.5 + 0.05 * line_count as f32,
+                buffer: 0.0,
+            });
+            supported
+        }
+        "circle" => {
+            let supported = vec!["circle-color", "circle-opacity", "circle-radius"];
+            style_layer.layer_type = LayerType::Point;
+            style_layer.color = Some(import_color(
+                paint,
+                "circle-color",
+                "circle-opacity",
+                warnings,
+            ));
+            let radius = import_number(paint, "circle-radius", 5.0, warnings);
+            style_layer.point = Some(PointStyle {
+                height: radius.map(|radius| radius * 2.0),
+                radius,
+                top_radius: None,
+                sides: 8,
+            });
+            supported
+        }
+        _ => vec![],
+    };
+
+    for key in paint.keys() {
+        if !supported.contains(&key.as_str()) {
+            warnings.push(format!("paint property \"{}\" is ignored", key));
+        }
+    }
+
+    Some(style_layer)
+}
+
+// The feature property read by `["get", key]` or a legacy `{"type": "identity", "property": key}` function.
+fn property_key(value: &Value) -> Option<String> {
+    match value {
+        Value::Object(function)
+            if function.get("type").and_then(|t| t.as_str()) == Some("identity") =>
+        {
+            function.get("property")?.as_str().map(String::from)
+        }
+        value => get_expression_key(value),
+    }
+}
+
+// Converts `fill-extrusion-height` or `fill-extrusion-base`, which default to 0. Numbers and zoom functions become
+// the style value, while `["get", key]` names the feature property that replaces it for features that have one.
+// Zoom functions that reach the property, like `["interpolate", ["linear"], ["zoom"], 15, 0, 15.05, ["get",
+// "height"]]`, read it at every zoom and keep their numeric stops for features without it.
+fn import_height(
+    paint: &serde_json::Map<String, Value>,
+    key: &str,
+    warnings: &mut Vec<String>,
+) -> (ZoomValue<f32>, Option<String>) {
+    let value = match paint.get(key) {
+        Some(value) => value,
+        None => return (ZoomValue::Constant(0.0), None),
+    };
+    if let Some(property) = property_key(value) {
+        return (ZoomValue::Constant(0.0), Some(property));
+    }
+    if let Some(stops) = zoom_stops(value) {
+        if let Some(property) = stops.iter().find_map(|(_, output)| property_key(output)) {
+            let stops: Vec<(f32, f32)> = stops
+                .iter()
+                .filter_map(|(zoom, output)| output.as_f64().map(|n| (*zoom, n as f32)))
+                .collect();
+            let height = if stops.is_empty() {
+                ZoomValue::Constant(0.0)
+            } else {
+                ZoomValue::Stops {
+                    stops,
+                    base: zoom_base(value),
+                }
+            };
+            return (height, Some(property));
+        }
+    }
+    (import_number(paint, key, 0.0, warnings), None)
+}
+
+fn import_number(
+    paint: &serde_json::Map<String, Value>,
+    key: &str,
+    default: f32,
+    warnings: &mut Vec<String>,
+) -> ZoomValue<f32> {
+    let value = match paint.get(key) {
+        None => return ZoomValue::Constant(default),
+        Some(Value::Number(n)) => {
+            return ZoomValue::Constant(n.as_f64().unwrap_or(default as f64) as f32)
+        }
+        Some(value) => value,
+    };
+
+    let stops: Option<Vec<(f32, f32)>> = zoom_stops(value).and_then(|stops| {
+        stops
+            .into_iter()
+            .map(|(zoom, value)| value.as_f64().map(|value| (zoom, value as f32)))
+            .collect()
+    });
+    if let Some(stops) = stops {
+        return ZoomValue::Stops {
+            stops,
+            base: zoom_base(value),
+        };
+    }
+
+    match last_stop(value).and_then(|v| v.as_f64()) {
+        Some(n) => {
+            warnings.push(format!(
+                "\"{}\" is not supported, using the last stop {}",
+                key, n
+            ));
+            ZoomValue::Constant(n as f32)
+        }
+        None => {
+            warnings.push(format!("\"{}\" is not a number, using {}", key, default));
+            ZoomValue::Constant(default)
+        }
+    }
+}
+
+fn import_color(
+    paint: &serde_json::Map<String, Value>,
+    key: &str,
+    opacity_key: &str,
+    warnings: &mut Vec<String>,
+) -> ColorStyle {
+    let default = [0.0, 0.0, 0.0, 1.0];
+    let mut color = match paint.get(key) {
+        None => ColorStyle::Constant(default),
+        Some(Value::String(color)) => match parse_color(color) {
+            Some(color) => ColorStyle::Constant(color),
+            None => {
+                warnings.push(format!(
+                    "\"{}\" has an unsupported color \"{}\"",
+                    key, color
+                ));
+                ColorStyle::Constant(default)
+            }
+        },
+        Some(value) => match import_property_color(value) {
+            Some(color) => ColorStyle::Property(color),
+            None => match import_zoom_color(value) {
+                Some(stops) => ColorStyle::Zoom(ZoomValue::Stops {
+                    stops,
+                    base: zoom_base(value),
+                }),
+                None => match last_stop(value)
+                    .and_then(|v| v.as_str())
+                    .and_then(parse_color)
+                {
+                    Some(color) => {
+                        warnings.push(format!("\"{}\" is not supported, using the last stop", key));
+                        ColorStyle::Constant(color)
+                    }
+                    None => {
+                        warnings.push(format!("\"{}\" is not a color, using black", key));
+                        ColorStyle::Constant(default)
+                    }
+                },
+            },
+        },
+    };
+
+    match paint.get(opacity_key) {
+        None => {}
+        Some(Value::Number(n)) => {
+            let opacity = n.as_f64().unwrap_or(1.0) as f32;
+            match &mut color {
+                ColorStyle::Constant(color) => color[3] *= opacity,
+                ColorStyle::Zoom(color) => {
+                    *color = color.map(|mut c| {
+                        c[3] *= opacity;
+                        c
+                    })
+                }
+                ColorStyle::Property(style) => {
+                    style.default[3] *= opacity;
+                    for color in style.categories.iter_mut().flat_map(|c| c.values_mut()) {
+                        color[3] *= opacity;
+                    }
+                    for (_, color) in style.stops.iter_mut().flatten() {
+                        color[3] *= opacity;
+                    }
+                }
+            }
+        }
+        Some(_) => warnings.push(format!("non-constant \"{}\" is ignored", opacity_key)),
+    }
+    color
+}
+
+// Converts colours driven by a feature property: `["match", ["get", key], ...]`, linear
+// `["interpolate", ["linear"], ["get", key], ...]` and legacy `{"property": key, "stops": [...]}` functions.
+fn import_property_color(value: &Value) -> Option<PropertyColorStyle> {
+    match value {
+        Value::Array(expression) => {
+            let operator = expression.first()?.as_str()?;
+            match operator {
+                "match" if expression.len() >= 5 && expression.len() % 2 == 1 => {
+                    let property = get_expression_key(&expression[1])?;
+                    let default = parse_color(expression.last()?.as_str()?)?;
+                    let mut categories = HashMap::new();
+                    for pair in expression[2..expression.len() - 1].chunks(2) {
+                        let color = parse_color(pair[1].as_str()?)?;
+                        let labels = match &pair[0] {
+                            Value::Array(labels) => labels.iter().collect(),
+                            label => vec![label],
+                        };
+                        for label in labels {
+                            categories.insert(category_key(label)?, color);
+                        }
+                    }
+                    Some(PropertyColorStyle {
+                        property,
+                        categories: Some(categories),
+                        stops: None,
+                        default,
+                    })
+                }
+                "interpolate" if expression.len() >= 5 && expression.len() % 2 == 1 => {
+                    if expression[1].get(0)?.as_str()? != "linear" {
+                        return None;
+                    }
+                    let property = get_expression_key(&expression[2])?;
+                    let mut stops = vec![];
+                    for pair in expression[3..].chunks(2) {
+                        stops.push((pair[0].as_f64()?, parse_color(pair[1].as_str()?)?));
+                    }
+                    let default = stops[0].1;
+                    Some(PropertyColorStyle {
+                        property,
+                        categories: None,
+                        stops: Some(stops),
+                        default,
+                    })
+                }
+                _ => None,
+            }
+        }
+        Value::Object(function) => {
+            let property = function.get("property")?.as_str()?;
+            let function_type = function.get("type").and_then(|t| t.as_str());
+            let default = match function.get("default") {
+                Some(default) => parse_color(default.as_str()?)?,
+                None => [0.0, 0.0, 0.0, 1.0],
+            };
+            let stops = function.get("stops")?.as_array()?;
+            let mut categories = HashMap::new();
+            let mut numeric_stops = vec![];
+            for stop in stops {
+                let stop = stop.as_array()?;
+                let color = parse_color(stop.get(1)?.as_str()?)?;
+                if function_type == Some("categorical") {
+                    categories.insert(category_key(stop.first()?)?, color);
+                } else {
+                    numeric_stops.push((stop.first()?.as_f64()?, color));
+                }
+            }
+            let (categories, stops) = if function_type == Some("categorical") {
+                (Some(categories), None)
+            } else {
+                (None, Some(numeric_stops))
+            };
+            Some(PropertyColorStyle {
+                property: String::from(property),
+                categories,
+                stops,
+                default,
+            })
+        }
+        _ => None,
+    }
+}
+
+fn get_expression_key(value: &Value) -> Option<String> {
+    match value {
+        Value::Array(expression)
+            if expression.len() == 2 && expression[0].as_str() == Some("get") =>
+        {
+            expression[1].as_str().map(String::from)
+        }
+        _ => None,
+    }
+}
+
+// Categories are keyed by the string form of the property value, so `3` and `3.0` both become `"3"`.
+fn category_key(label: &Value) -> Option<String> {
+    match label {
+        Value::String(s) => Some(s.clone()),
+        Value::Number(n) => n.as_f64().map(|n| n.to_string()),
+        Value::Bool(b) => Some(b.to_string()),
+        _ => None,
+    }
+}
+
+fn import_zoom_color(value: &Value) -> Option<Vec<(f32, [f32; 4])>> {
+    zoom_stops(value)?
+        .into_iter()
+        .map(|(zoom, color)| Some((zoom, parse_color(color.as_str()?)?)))
+        .collect()
+}
+
+// The `[zoom, output]` stops of a legacy zoom function `{"stops": [...]}` or an
+// `["interpolate", ..., ["zoom"], ...]` expression. See `zoom_base` for how they are interpolated.
+fn zoom_stops(value: &Value) -> Option<Vec<(f32, &Value)>> {
+    match value {
+        Value::Object(function) if function.get("property").is_none() => function
+            .get("stops")?
+            .as_array()?
+            .iter()
+            .map(|stop| Some((stop.get(0)?.as_f64()? as f32, stop.get(1)?)))
+            .collect(),
+        Value::Array(expression)
+            if expression.len() >= 5
+                && expression.len() % 2 == 1
+                && expression[0].as_str() == Some("interpolate")
+                && expression[2].get(0).and_then(|z| z.as_str()) == Some("zoom") =>
+        {
+            expression[3..]
+                .chunks(2)
+                .map(|stop| Some((stop[0].as_f64()? as f32, &stop[1])))
+                .collect()
+        }
+        _ => None,
+    }
+}
+
+// The exponential base of a zoom function or `["interpolate", ["exponential", base], ["zoom"], ...]` expression.
+// Linear and cubic bezier interpolation both come out as 1, the latter approximated linearly.
+fn zoom_base(value: &Value) -> f32 {
+    let base = match value {
+        Value::Object(function) => function.get("base").and_then(|base| base.as_f64()),
+        Value::Array(expression) => expression
+            .get(1)
+            .filter(|interpolation| {
+                interpolation.get(0).and_then(|i| i.as_str()) == Some("exponential")
+            })
+            .and_then(|interpolation| interpolation.get(1))
+            .and_then(|base| base.as_f64()),
+        _ => None,
+    };
+    base.unwrap_or(1.0) as f32
+}
+
+// The output of the last stop of a legacy `{"stops": [...]}` function or an `["interpolate", ...]`/`["step", ...]`
+// expression.
+fn last_stop(value: &Value) -> Option<&Value> {
+    match value {
+        Value::Object(function) => function
+            .get("stops")
+            .and_then(|stops| stops.as_array())
+            .and_then(|stops| stops.last())
+            .and_then(|stop| stop.as_array())
+            .and_then(|stop| stop.get(1)),
+        Value::Array(expression) => {
+            let operator = expression.first().and_then(|o| o.as_str());
+            if operator == Some("interpolate") || operator == Some("step") {
+                expression.last()
+            } else {
+                None
+            }
+        }
+        _ => None,
+    }
+}
+
+// Parses the CSS color formats used by Mapbox GL styles: `#rgb`, `#rrggbb`, `rgb()`, `rgba()`, `hsl()`, `hsla()`
+// and a few named colors.
+pub fn parse_color(color: &str) -> Option<[f32; 4]> {
+    let color = color.trim().to_lowercase();
+
+    if let Some(hex) = color.strip_prefix('#') {
+        let digits: Vec<u32> = hex.chars().map(|c| c.to_digit(16)).collect::<Option<_>>()?;
+        return match digits.len() {
+            3 => Some([
+                (digits[0] * 17) as f32 / 255.0,
+                (digits[1] * 17) as f32 / 255.0,
+                (digits[2] * 17) as f32 / 255.0,
+                1.0,
+            ]),
+            6 => Some([
+                (digits[0] * 16 + digits[1]) as f32 / 255.0,
+                (digits[2] * 16 + digits[3]) as f32 / 255.0,
+                (digits[4] * 16 + digits[5]) as f32 / 255.0,
+                1.0,
+            ]),
+            _ => None,
+        };
+    }
+
+    if let Some(open) = color.find('(') {
+        let function = &color[..open];
+        let arguments = color[open + 1..].strip_suffix(')')?;
+        let arguments: Vec<&str> = arguments.split(',').map(|a| a.trim()).collect();
+        let alpha = match arguments.get(3) {
+            Some(alpha) => alpha.parse::<f32>().ok()?,
+            None => 1.0,
+        };
+
+        return match (function, arguments.len()) {
+            ("rgb", 3) | ("rgba", 4) => {
+                let r = arguments[0].parse::<f32>().ok()?;
+                let g = arguments[1].parse::<f32>().ok()?;
+                let b = arguments[2].parse::<f32>().ok()?;
+                Some([r / 255.0, g / 255.0, b / 255.0, alpha])
+            }
+            ("hsl", 3) | ("hsla", 4) => {
+                let h = arguments[0].parse::<f32>().ok()?;
+                let s = arguments[1].strip_suffix('%')?.parse::<f32>().ok()? / 100.0;
+                let l = arguments[2].strip_suffix('%')?.parse::<f32>().ok()? / 100.0;
+                let (r, g, b) = hsl_to_rgb(h, s, l);
+                Some([r, g, b, alpha])
+            }
+            _ => None,
+        };
+    }
+
+    match color.as_str() {
+        "black" => Some([0.0, 0.0, 0.0, 1.0]),
+        "white" => Some([1.0, 1.0, 1.0, 1.0]),
+        "gray" | "grey" => Some([0.5, 0.5, 0.5, 1.0]),
+        "red" => Some([1.0, 0.0, 0.0, 1.0]),
+        "green" => Some([0.0, 0.5, 0.0, 1.0]),
+        "blue" => Some([0.0, 0.0, 1.0, 1.0]),
+        "yellow" => Some([1.0, 1.0, 0.0, 1.0]),
+        "transparent" => Some([0.0, 0.0, 0.0, 0.0]),
+        _ => None,
+    }
+}
+
+fn hsl_to_rgb(h: f32, s: f32, l: f32) -> (f32, f32, f32) {
+    let c = (1.0 - (2.0 * l - 1.0).abs()) * s;
+    let h = ((h % 360.0) + 360.0) % 360.0 / 60.0;
+    let x = c * (1.0 - (h % 2.0 - 1.0).abs());
+    let (r, g, b) = match h as u32 {
+        0 => (c, x, 0.0),
+        1 => (x, c, 0.0),
+        2 => (0.0, c, x),
+        3 => (0.0, x, c),
+        4 => (x, 0.0, c),
+        _ => (c, 0.0, x),
+    };
+    let m = l - c / 2.0;
+    (r + m, g + m, b + m)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_color(actual: Option<[f32; 4]>, expected: [f32; 4], input: &str) {
+        let actual = actual.unwrap_or_else(|| panic!("{} did not parse", input));
+        for i in 0..4 {
+            assert!(
+                (actual[i] - expected[i]).abs() < 1e-3,
+                "{}: {:?} != {:?}",
+                input,
+                actual,
+                expected
+            );
+        }
+    }
+
+    #[test]
+    fn colors_parse() {
+        let cases = [
+            ("#f00", [1.0, 0.0, 0.0, 1.0]),
+            ("#FFCC00", [1.0, 0.8, 0.0, 1.0]),
+            ("#336699", [0.2, 0.4, 0.6, 1.0]),
+            ("rgb(255, 128, 0)", [1.0, 0.502, 0.0, 1.0]),
+            ("rgba(0,0,255,0.5)", [0.0, 0.0, 1.0, 0.5]),
+            ("hsl(120, 100%, 50%)", [0.0, 1.0, 0.0, 1.0]),
+            ("hsla(0, 0%, 50%, 0.25)", [0.5, 0.5, 0.5, 0.25]),
+            ("  White ", [1.0, 1.0, 1.0, 1.0]),
+            ("transparent", [0.0, 0.0, 0.0, 0.0]),
+        ];
+        for (input, expected) in cases.iter() {
+            assert_color(parse_color(input), *expected, input);
+        }
+    }
+
+    #[test]
+    fn invalid_colors_are_rejected() {
+        for input in [
+            "",
+            "#",
+            "#12",
+            "#1234",
+            "#ggg",
+            "rgb(1, 2)",
+            "rgba(1, 2, 3)",
+            "rgb(1, 2, x)",
+            "rgb(1, 2, 3",
+            "hsl(0, 50, 50%)",
+            "cmyk(0, 0, 0, 0)",
+            "chartreuse",
+        ]
+        .iter()
+        {
+            assert_eq!(parse_color(input), None, "{}", input);
+        }
+    }
+
+    #[test]
+    fn hsl_converts_to_rgb() {
+        let cases = [
+            ((0.0, 1.0, 0.5), (1.0, 0.0, 0.0)),
+            ((60.0, 1.0, 0.25), (0.5, 0.5, 0.0)),
+            ((240.0, 1.0, 0.5), (0.0, 0.0, 1.0)),
+            ((300.0, 0.5, 0.5), (0.75, 0.25, 0.75)),
+            // Hues wrap around the colour wheel.
+            ((360.0, 1.0, 0.5), (1.0, 0.0, 0.0)),
+            ((-120.0, 1.0, 0.5), (0.0, 0.0, 1.0)),
+            ((200.0, 0.0, 0.3), (0.3, 0.3, 0.3)),
+            ((90.0, 1.0, 1.0), (1.0, 1.0, 1.0)),
+        ];
+        for ((h, s, l), (r, g, b)) in cases.iter() {
+            let rgb = hsl_to_rgb(*h, *s, *l);
+            assert!(
+                (rgb.0 - r).abs() < 1e-5 && (rgb.1 - g).abs() < 1e-5 && (rgb.2 - b).abs() < 1e-5,
+                "hsl({}, {}, {}) gave {:?}",
+                h,
+                s,
+                l,
+                rgb
+            );
+        }
+    }
+
+    #[test]
+    fn styles_import() {
+        let document: Value = serde_json::from_str(
+            r##"{
+                "version": 8,
+                "sources": {},
+                "layers": [
+                    { "id": "background", "type": "background", "paint": { "background-color": "#fff" } },
+                    {
+                        "id": "water",
+                        "type": "fill",
+                        "source-layer": "water",
+                        "paint": { "fill-color": "#0000ff", "fill-opacity": 0.5, "fill-antialias": false }
+                    },
+                    {
+                        "id": "buildings",
+                        "type": "fill-extrusion",
+                        "source-layer": "building",
+                        "filter": ["==", "extrude", "true"],
+                        "paint": {
+                            "fill-extrusion-color": ["match", ["get", "type"], "church", "#ff0000", "#888888"],
+                            "fill-extrusion-height": ["get", "height"],
+                            "fill-extrusion-base": 4
+                        }
+                    },
+                    {
+                        "id": "roads",
+                        "type": "line",
+                        "source-layer": "road",
+                        "minzoom": 12,
+                        "paint": { "line-color": "hsl(0, 0%, 20%)", "line-width": { "stops": [[12, 1], [18, 6]] } }
+                    },
+                    { "id": "hidden", "type": "line", "source-layer": "road", "layout": { "visibility": "none" } },
+                    {
+                        "id": "pois",
+                        "type": "circle",
+                        "source-layer": "poi_label",
+                        "paint": { "circle-radius": 3, "circle-color": "rgb(0, 128, 0)" }
+                    },
+                    { "id": "orphan", "type": "fill" }
+                ]
+            }"##,
+        )
+        .unwrap();
+        assert!(is_mapbox_style(&document));
+
+        let (style, warnings) = import_style(&document).unwrap();
+        let layers: Vec<(&str, LayerType)> = style
+            .layers
+            .iter()
+            .map(|layer| (layer.source_layer.as_str(), layer.layer_type))
+            .collect();
+        assert_eq!(
+            layers,
+            vec![
+                ("water", LayerType::Fill),
+                ("building", LayerType::Fill),
+                ("road", LayerType::Line),
+                ("poi_label", LayerType::Point),
+            ]
+        );
+
+        match &style.layers[0].color {
+            Some(ColorStyle::Constant(color)) => assert_eq!(*color, [0.0, 0.0, 1.0, 0.5]),
+            other => panic!("unexpected water color {:?}", other),
+        }
+        assert!(!style.layers[0].fill.as_ref().unwrap().walls);
+
+        let buildings = &style.layers[1];
+        assert_eq!(buildings.shader, "color_lit");
+        assert!(buildings.filter.is_some());
+        let fill = buildings.fill.as_ref().unwrap();
+        assert!(fill.walls);
+        assert_eq!(fill.height_property.as_deref(), Some("height"));
+        assert_eq!(fill.min_height_property, None);
+        assert_eq!(fill.min_height.at(16.0), 4.0);
+        match &buildings.color {
+            Some(ColorStyle::Property(color)) => {
+                assert_eq!(color.property, "type");
+                assert_eq!(
+                    color.categories.as_ref().unwrap()["church"],
+                    [1.0, 0.0, 0.0, 1.0]
+                );
+            }
+            other => panic!("unexpected building color {:?}", other),
+        }
+
+        let roads = &style.layers[2];
+        assert_eq!(roads.min_zoom, Some(12.0));
+        assert_eq!(roads.line.as_ref().unwrap().width.at(15.0), 3.5);
+
+        assert_eq!(style.layers[3].point.as_ref().unwrap().radius.at(0.0), 3.0);
+
+        assert_eq!(
+            warnings,
+            vec![
+                "background: layer type \"background\" is not supported",
+                "water: paint property \"fill-antialias\" is ignored",
+                "orphan: layer has no \"source-layer\"",
+            ]
+        );
+    }
+
+    #[test]
+    fn extrusion_heights_import() {
+        let paint = serde_json::json!({
+            "constant": 12,
+            "zoom": ["interpolate", ["linear"], ["zoom"], 14, 0, 16, 20],
+            "property": ["get", "render_height"],
+            "identity": { "type": "identity", "property": "render_min_height" },
+            "fade_in": ["interpolate", ["linear"], ["zoom"], 15, 0, 15.05, ["get", "height"]],
+            "invalid": "tall"
+        });
+        let paint = paint.as_object().unwrap();
+        let mut warnings = vec![];
+        let cases = [
+            ("constant", 12.0, None),
+            ("zoom", 10.0, None),
+            ("property", 0.0, Some("render_height")),
+            ("identity", 0.0, Some("render_min_height")),
+            ("fade_in", 0.0, Some("height")),
+            ("missing", 0.0, None),
+        ];
+        for (key, height, property) in cases.iter() {
+            let (value, value_property) = import_height(paint, key, &mut warnings);
+            assert_eq!(value.at(15.0), *height, "{}", key);
+            assert_eq!(value_property.as_deref(), *property, "{}", key);
+        }
+        assert!(warnings.is_empty(), "{:?}", warnings);
+
+        let (value, property) = import_height(paint, "invalid", &mut warnings);
+        assert_eq!((value.at(15.0), property), (0.0, None));
+        assert_eq!(warnings, vec!["\"invalid\" is not a number, using 0"]);
+    }
+
+    #[test]
+    fn zoom_functions_keep_their_base() {
+        let paint = serde_json::json!({
+            "linear": { "stops": [[10, 0], [12, 3]] },
+            "legacy": { "base": 2, "stops": [[10, 0], [12, 3]] },
+            "expression": ["interpolate", ["exponential", 2], ["zoom"], 10, 0, 12, 3],
+            "bezier": ["interpolate", ["cubic-bezier", 0, 0, 1, 1], ["zoom"], 10, 0, 12, 3]
+        });
+        let paint = paint.as_object().unwrap();
+        let mut warnings = vec![];
+        for (key, expected) in [
+            ("linear", 1.5),
+            ("legacy", 1.0),
+            ("expression", 1.0),
+            ("bezier", 1.5),
+        ]
+        .iter()
+        {
+            let value = import_number(paint, key, 0.0, &mut warnings);
+            assert!(
+                (value.at(11.0) - expected).abs() < 1e-5,
+                "{}: {:?}",
+                key,
+                value
+            );
+        }
+        assert!(warnings.is_empty(), "{:?}", warnings);
+    }
+
+    #[test]
+    fn styles_without_layers_are_rejected() {
+        let document: Value = serde_json::from_str(r#"{ "version": 8, "sources": {} }"#).unwrap();
+        assert!(import_style(&document).is_err());
+        assert!(!is_mapbox_style(
+            &serde_json::from_str(r#"{ "layers": [] }"#).unwrap()
+        ));
+    }
+}
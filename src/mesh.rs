@@ -1,3 +1,4 @@
+use super::error::Error;
 use super::model;
 use super::util;
 use gl::types::*;
@@ -17,6 +18,8 @@ pub struct Mesh {
     pub texture0: u32,
     pub normals_vbo: u32,
     pub normals: Vec<glm::Vec3>,
+    pub color_vbo: u32,
+    pub colors: Vec<glm::Vec4>,
     pub img: TextureType,
 }
 
@@ -45,6 +48,8 @@ impl Mesh {
             uv_vbo: 0,
             uvs: Vec::new(),
             texture0: 0,
+            color_vbo: 0,
+            colors: Vec::new(),
             img: TextureType::None,
         };
 
@@ -69,6 +74,8 @@ impl Mesh {
             uv_vbo: 0,
             uvs,
             texture0: 0,
+            color_vbo: 0,
+            colors: Vec::new(),
             img: TextureType::None,
         };
 
@@ -77,8 +84,13 @@ impl Mesh {
     }
 
 
-    pub fn set_flip_book_texture(&mut self, path: &str, columns: u32, rows: u32) {
-        let img = image::open(&Path::new(path)).expect("Failed to load texture");
+    pub fn set_flip_book_texture(
+        &mut self,
+        path: &str,
+        columns: u32,
+        rows: u32,
+    ) -> Result<(), Error> {
+        let img = load_image(path)?;
         let color = img.color();
         self.img = TextureType::FlipBook {
             columns,
@@ -88,12 +100,14 @@ impl Mesh {
         };
 
         self.apply_texture();
+        Ok(())
     }
 
-    pub fn set_texture(&mut self, path: &str) {
-        let img = image::open(&Path::new(path)).expect("Failed to load texture");
+    pub fn set_texture(&mut self, path: &str) -> Result<(), Error> {
+        let img = load_image(path)?;
         self.img = TextureType::Img(img);
         self.apply_texture();
+        Ok(())
     }
 
     pub fn apply_texture(&mut self) {
@@ -209,6 +223,38 @@ impl Mesh {
         }
     }
 
+    pub fn set_colors(&mut self, colors: Vec<glm::Vec4>) {
+        if colors.len() == 0 {
+            return;
+        }
+        self.colors = colors;
+
+        unsafe {
+            if self.color_vbo == 0 {
+                gl::GenBuffers(1, &mut self.color_vbo);
+            }
+            gl::BindVertexArray(self.vao);
+            gl::BindBuffer(gl::ARRAY_BUFFER, self.color_vbo);
+            gl::BufferData(
+                gl::ARRAY_BUFFER,
+                util::byte_size_of_array(&self.colors) as GLsizeiptr,
+                util::pointer_to_array(&self.colors),
+                gl::STATIC_DRAW,
+            );
+
+            gl::VertexAttribPointer(
+                1,
+                4,
+                gl::FLOAT,
+                gl::FALSE,
+                4 * util::size_of::<GLfloat>() as GLsizei,
+                ptr::null(),
+            );
+
+            gl::EnableVertexAttribArray(1);
+        }
+    }
+
     pub fn set_uvs_from_f32(&mut self, uvs: Vec<f32>) {
         if uvs.len() == 0 {
             return;
@@ -226,6 +272,29 @@ impl Mesh {
         self.set_uvs(uvs2);
     }
 
+    // Frees the GPU buffers and texture owned by this mesh. The mesh must not be drawn afterwards.
+    pub unsafe fn delete(&mut self) {
+        let buffers = [
+            self.vert_vbo,
+            self.uv_vbo,
+            self.normals_vbo,
+            self.color_vbo,
+            self.ebo,
+        ];
+        gl::DeleteBuffers(buffers.len() as GLsizei, buffers.as_ptr());
+        gl::DeleteVertexArrays(1, &self.vao);
+        if self.texture0 != 0 {
+            gl::DeleteTextures(1, &self.texture0);
+        }
+        self.vao = 0;
+        self.vert_vbo = 0;
+        self.uv_vbo = 0;
+        self.normals_vbo = 0;
+        self.color_vbo = 0;
+        self.ebo = 0;
+        self.texture0 = 0;
+    }
+
     pub fn update_vao(&mut self) {
         unsafe { self.setup_vao() }
     }
@@ -336,6 +405,14 @@ impl Mesh {
     }
 }
 
+// Reads an image for a texture, or a cube map face.
+pub fn load_image(path: &str) -> Result<image::DynamicImage, Error> {
+    image::open(&Path::new(path)).map_err(|e| Error::Texture {
+        path: String::from(path),
+        message: e.to_string(),
+    })
+}
+
 fn color_type(color: &ColorType) -> u32 {
     match color {
         ColorType::Rgb8 => gl::RGB,
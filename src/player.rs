@@ -8,7 +8,10 @@ pub struct Player {
     pub position: glm::Vec3,
     pub yaw: f32,
     pub pitch: f32,
+    // Metres per second.
     pub movement_speed: f32,
+    // Degrees per second.
+    pub turn_speed: f32,
     pub mouse_sensitivity: f32,
     pub movement_speed_multiplier: f32,
 }
@@ -34,7 +37,8 @@ impl Player {
             up: cam_up,
             yaw,
             pitch,
-            movement_speed: 10.0,
+            movement_speed: 25.0,
+            turn_speed: 10.0,
             mouse_sensitivity: 0.1,
             movement_speed_multiplier: 1.0,
         }
@@ -130,16 +134,16 @@ impl Player {
                     self.position -= self.movement_speed * self.up * delta_time * self.movement_speed_multiplier;
                 }
                 VirtualKeyCode::Left => {
-                    self.yaw -= self.movement_speed * delta_time;
+                    self.yaw -= self.turn_speed * delta_time;
                 }
                 VirtualKeyCode::Right => {
-                    self.yaw += self.movement_speed * delta_time;
+                    self.yaw += self.turn_speed * delta_time;
                 }
                 VirtualKeyCode::Up => {
-                    self.pitch += self.movement_speed * delta_time;
+                    self.pitch += self.turn_speed * delta_time;
                 }
                 VirtualKeyCode::Down => {
-                    self.pitch -= self.movement_speed * delta_time;
+                    self.pitch -= self.turn_speed * delta_time;
                 }
                 VirtualKeyCode::LShift => {
                     shift_pressed = true;
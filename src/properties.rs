@@ -0,0 +1,222 @@
+use super::protos::vector_tile;
+use std::fmt;
+
+// A feature property, borrowed from the layer's value table. One variant per `Tile_Value` field.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum PropertyValue<'a> {
+    String(&'a str),
+    Float(f32),
+    Double(f64),
+    Int(i64),
+    UInt(u64),
+    SInt(i64),
+    Bool(bool),
+}
+
+impl<'a> PropertyValue<'a> {
+    pub fn from_tile_value(value: &'a vector_tile::Tile_Value) -> Option<PropertyValue<'a>> {
+        if value.has_string_value() {
+            Some(PropertyValue::String(value.get_string_value()))
+        } else if value.has_float_value() {
+            Some(PropertyValue::Float(value.get_float_value()))
+        } else if value.has_double_value() {
+            Some(PropertyValue::Double(value.get_double_value()))
+        } else if value.has_int_value() {
+            Some(PropertyValue::Int(value.get_int_value()))
+        } else if value.has_uint_value() {
+            Some(PropertyValue::UInt(value.get_uint_value()))
+        } else if value.has_sint_value() {
+            Some(PropertyValue::SInt(value.get_sint_value()))
+        } else if value.has_bool_value() {
+            Some(PropertyValue::Bool(value.get_bool_value()))
+        } else {
+            None
+        }
+    }
+
+    // Any numeric variant as a double, so integer and floating point heights are read the same way.
+    pub fn as_f64(&self) -> Option<f64> {
+        match *self {
+            PropertyValue::Float(v) => Some(v as f64),
+            PropertyValue::Double(v) => Some(v),
+            PropertyValue::Int(v) => Some(v as f64),
+            PropertyValue::UInt(v) => Some(v as f64),
+            PropertyValue::SInt(v) => Some(v as f64),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&'a str> {
+        match *self {
+            PropertyValue::String(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    pub fn as_bool(&self) -> Option<bool> {
+        match *self {
+            PropertyValue::Bool(b) => Some(b),
+            _ => None,
+        }
+    }
+}
+
+impl<'a> fmt::Display for PropertyValue<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            PropertyValue::String(s) => write!(f, "{}", s),
+            PropertyValue::Float(v) => write!(f, "{}", v),
+            PropertyValue::Double(v) => write!(f, "{}", v),
+            PropertyValue::Int(v) => write!(f, "{}", v),
+            PropertyValue::UInt(v) => write!(f, "{}", v),
+            PropertyValue::SInt(v) => write!(f, "{}", v),
+            PropertyValue::Bool(b) => write!(f, "{}", b),
+        }
+    }
+}
+
+// The properties of one feature. Tags are pairs of indices into the layer's keys and values; pairs with an
+// index out of range are skipped.
+#[derive(Clone, Copy)]
+pub struct FeatureProperties<'a> {
+    layer: &'a vector_tile::Tile_Layer,
+    feature: &'a vector_tile::Tile_Feature,
+}
+
+impl<'a> FeatureProperties<'a> {
+    pub fn new(
+        layer: &'a vector_tile::Tile_Layer,
+        feature: &'a vector_tile::Tile_Feature,
+    ) -> FeatureProperties<'a> {
+        FeatureProperties { layer, feature }
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&'a str, PropertyValue<'a>)> + 'a {
+        let keys = self.layer.get_keys();
+        let values = self.layer.get_values();
+        self.feature
+            .get_tags()
+            .chunks_exact(2)
+            .filter_map(move |tag| {
+                let key = keys.get(tag[0] as usize)?;
+                let value = PropertyValue::from_tile_value(values.get(tag[1] as usize)?)?;
+                Some((key.as_str(), value))
+            })
+    }
+
+    pub fn get(&self, key: &str) -> Option<PropertyValue<'a>> {
+        self.iter().find(|(k, _)| *k == key).map(|(_, v)| v)
+    }
+
+    pub fn get_f64(&self, key: &str) -> Option<f64> {
+        self.get(key).and_then(|v| v.as_f64())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tile_value(set: fn(&mut vector_tile::Tile_Value)) -> vector_tile::Tile_Value {
+        let mut value = vector_tile::Tile_Value::new();
+        set(&mut value);
+        value
+    }
+
+    #[test]
+    fn every_value_type_converts() {
+        let cases: Vec<(vector_tile::Tile_Value, PropertyValue, Option<f64>)> = vec![
+            (
+                tile_value(|v| v.set_string_value(String::from("10"))),
+                PropertyValue::String("10"),
+                None,
+            ),
+            (
+                tile_value(|v| v.set_float_value(1.5)),
+                PropertyValue::Float(1.5),
+                Some(1.5),
+            ),
+            (
+                tile_value(|v| v.set_double_value(-2.25)),
+                PropertyValue::Double(-2.25),
+                Some(-2.25),
+            ),
+            (
+                tile_value(|v| v.set_int_value(-7)),
+                PropertyValue::Int(-7),
+                Some(-7.0),
+            ),
+            (
+                tile_value(|v| v.set_uint_value(u64::from(u32::MAX) + 1)),
+                PropertyValue::UInt(u64::from(u32::MAX) + 1),
+                Some(4294967296.0),
+            ),
+            (
+                tile_value(|v| v.set_sint_value(-3)),
+                PropertyValue::SInt(-3),
+                Some(-3.0),
+            ),
+            (
+                tile_value(|v| v.set_bool_value(true)),
+                PropertyValue::Bool(true),
+                None,
+            ),
+        ];
+        for (tile_value, expected, number) in cases.iter() {
+            let value = PropertyValue::from_tile_value(tile_value);
+            assert_eq!(value, Some(*expected));
+            assert_eq!(value.unwrap().as_f64(), *number, "{:?}", expected);
+        }
+
+        assert_eq!(
+            PropertyValue::from_tile_value(&vector_tile::Tile_Value::new()),
+            None
+        );
+    }
+
+    // A layer with one key and value per height type, plus a feature tagged with all of them and two tags whose
+    // key or value index is out of range.
+    fn layer_and_feature() -> (vector_tile::Tile_Layer, vector_tile::Tile_Feature) {
+        let mut layer = vector_tile::Tile_Layer::new();
+        let mut feature = vector_tile::Tile_Feature::new();
+        let values = vec![
+            ("uint", tile_value(|v| v.set_uint_value(12))),
+            ("sint", tile_value(|v| v.set_sint_value(-4))),
+            ("float", tile_value(|v| v.set_float_value(7.5))),
+            (
+                "name",
+                tile_value(|v| v.set_string_value(String::from("a"))),
+            ),
+        ];
+        for (i, (key, value)) in values.into_iter().enumerate() {
+            layer.mut_keys().push(String::from(key));
+            layer.mut_values().push(value);
+            feature.mut_tags().push(i as u32);
+            feature.mut_tags().push(i as u32);
+        }
+        layer.mut_keys().push(String::from("dangling"));
+        feature.mut_tags().extend_from_slice(&[4, 4, 9, 0]);
+        (layer, feature)
+    }
+
+    #[test]
+    fn heights_read_from_any_numeric_type() {
+        let (layer, feature) = layer_and_feature();
+        let properties = FeatureProperties::new(&layer, &feature);
+        assert_eq!(properties.get_f64("uint"), Some(12.0));
+        assert_eq!(properties.get_f64("sint"), Some(-4.0));
+        assert_eq!(properties.get_f64("float"), Some(7.5));
+        assert_eq!(properties.get_f64("name"), None);
+        assert_eq!(properties.get_f64("missing"), None);
+    }
+
+    #[test]
+    fn out_of_range_tags_are_skipped() {
+        let (layer, feature) = layer_and_feature();
+        let properties = FeatureProperties::new(&layer, &feature);
+        // "dangling" has a key but its value index 4 is past the 4 values; key index 9 does not exist at all.
+        assert_eq!(properties.get("dangling"), None);
+        assert_eq!(properties.iter().count(), 4);
+        assert_eq!(properties.get("name"), Some(PropertyValue::String("a")));
+    }
+}
@@ -0,0 +1,282 @@
+use super::camera::Camera;
+use super::error::Error;
+use super::material::Material;
+use super::model::Model;
+use super::player::Player;
+use super::primitives;
+use super::scene_graph::{Node, NodeType, SceneGraph, SceneNode};
+use super::shader::Shader;
+use super::skybox;
+use super::state::State;
+use super::style::{self, Style};
+use super::tile_address;
+use super::tile_manager::TileManager;
+use super::tile_source::TileSource;
+use super::tile_worker::TileWorkerPool;
+use super::util;
+use std::collections::HashMap;
+use std::ptr;
+use std::sync::Arc;
+use vector_tile_geometry::normals::calculate_normals;
+
+// Where the scene is centred, how much of the map around it is loaded and where the camera looks from.
+pub struct SceneOptions {
+    pub latitude: f64,
+    pub longitude: f64,
+    pub zoom: u32,
+    pub tile_radius: u32,
+    pub lod_levels: u32,
+    pub workers: usize,
+    // The camera orbits the player `camera_distance` metres away, `camera_yaw` and `camera_pitch` are in degrees.
+    pub camera_yaw: f32,
+    pub camera_pitch: f32,
+    pub camera_distance: f32,
+}
+
+// Everything that is drawn: the streamed tiles, the ground plane below them and the skybox, seen by a camera
+// orbiting the player. Creating, updating and drawing a scene needs a current GL context.
+pub struct Scene {
+    pub graph: SceneGraph,
+    pub tile_manager: TileManager,
+    player_node: Node,
+    camera_node: Node,
+    _plane_node: Node,
+    skybox_shader: Shader,
+    skybox_vao: u32,
+    skybox_texture: u32,
+    view_distance: f32,
+}
+
+// Sets the GL state the scene is drawn with and prints what it is drawn on.
+pub unsafe fn init_gl() {
+    gl::Enable(gl::CULL_FACE);
+    gl::Enable(gl::DEPTH_TEST);
+    gl::Disable(gl::MULTISAMPLE);
+    gl::Enable(gl::BLEND);
+    gl::BlendFunc(gl::SRC_ALPHA, gl::ONE_MINUS_SRC_ALPHA);
+    gl::Enable(gl::DEBUG_OUTPUT_SYNCHRONOUS);
+    gl::DebugMessageCallback(Some(util::debug_callback), ptr::null());
+    // gl::PolygonMode(gl::FRONT_AND_BACK, gl::LINE);
+    // Print some diagnostics
+    println!(
+        "{}: {}",
+        util::get_gl_string(gl::VENDOR),
+        util::get_gl_string(gl::RENDERER)
+    );
+    println!("OpenGL\t: {}", util::get_gl_string(gl::VERSION));
+    println!(
+        "GLSL\t: {}",
+        util::get_gl_string(gl::SHADING_LANGUAGE_VERSION)
+    );
+}
+
+impl Scene {
+    pub unsafe fn new(
+        style: &Style,
+        tile_source: Arc<dyn TileSource>,
+        options: &SceneOptions,
+    ) -> Result<Scene, Error> {
+        // build shaders
+        let skybox_shader = Shader::new("shaders/skybox.vert", "shaders/skybox.frag")?;
+
+        let mut shaders = HashMap::new();
+        for (name, vertex_shader, fragment_shader) in style::SHADERS {
+            shaders.insert(
+                String::from(*name),
+                Shader::new(vertex_shader, fragment_shader)?,
+            );
+        }
+
+        let mut dark_green_mat = Material::new(shaders["color"]);
+        dark_green_mat.set_vec4("u_color", 0.0, 0.078, 0.05, 1.0);
+
+        // create models
+        let root = SceneNode::new(NodeType::None);
+        let mut graph = SceneGraph::new(root);
+
+        let (tile_x, tile_y) = tile_address::latlon_to_tile_position(
+            options.latitude,
+            options.longitude,
+            options.zoom,
+        );
+        let center_tile =
+            tile_address::latlon_to_tile_address(options.latitude, options.longitude, options.zoom);
+
+        let feature_options = Arc::new(style.feature_options(&shaders));
+
+        // One unit is one metre around the centre tile. Every tile is built to that scale, so sizes in metres are
+        // the same on all of them while the map away from the centre keeps the stretch of the projection.
+        let scale = center_tile.ground_width();
+        let tile_workers = TileWorkerPool::new(
+            options.workers,
+            tile_source,
+            Arc::clone(&feature_options),
+            scale * (1u64 << center_tile.z) as f64,
+        );
+        let tile_manager = TileManager::new(
+            center_tile,
+            options.tile_radius,
+            options.lod_levels,
+            scale,
+            tile_workers,
+            feature_options,
+        );
+
+        let mut plane_mesh = primitives::generate_quad();
+        let normals = calculate_normals(&plane_mesh.vertices, &plane_mesh.indices);
+        plane_mesh.set_normals(normals);
+        let white = glm::vec4(1.0, 1.0, 1.0, 1.0);
+        plane_mesh.set_colors(vec![white; plane_mesh.vertices.len()]);
+
+        let view_distance = (scale * tile_manager.view_radius() as f64) as f32;
+        let plane_size = 3.0 * view_distance;
+        let map_plane_model = Model::new(
+            String::from("plane_mesh"),
+            vec![plane_mesh],
+            dark_green_mat.clone(),
+            glm::identity(),
+            Some(Box::new(move |_transform, _mat, s| {
+                // Keep the plane centered below the camera so it stays under the streamed tiles.
+                let mut transform: glm::Mat4 = glm::translate(
+                    &glm::identity(),
+                    &glm::vec3(
+                        s.camera_position.x - plane_size * 0.5,
+                        -0.9,
+                        s.camera_position.z + plane_size * 0.5,
+                    ),
+                );
+                transform = glm::rotate_x(&transform, -90_f32.to_radians());
+                glm::scale(&transform, &glm::vec3(plane_size, plane_size, plane_size))
+            })),
+        );
+
+        let plane_node = SceneNode::new_model(map_plane_model);
+        graph.root.add_child(&plane_node);
+
+        // The player starts at the requested coordinates inside the centre tile.
+        let mut player = Player::new();
+        let tiles = (1u64 << options.zoom) as f64;
+        player.position = glm::vec3(
+            ((tile_x.rem_euclid(tiles) - center_tile.x as f64) * scale) as f32,
+            0.0,
+            ((tile_y - center_tile.y as f64).clamp(0.0, 1.0) * scale) as f32,
+        );
+        let mut player_node = SceneNode::new_player(player);
+        graph.root.add_child(&player_node);
+
+        let camera = Camera::new(
+            options.camera_yaw,
+            options.camera_pitch,
+            options.camera_distance,
+        );
+        let camera_node = SceneNode::new_camera(camera);
+        player_node.add_child(&camera_node);
+
+        let (skybox_vao, skybox_texture) = skybox::create_skybox()?;
+
+        Ok(Scene {
+            graph,
+            tile_manager,
+            player_node,
+            camera_node,
+            _plane_node: plane_node,
+            skybox_shader,
+            skybox_vao,
+            skybox_texture,
+            view_distance,
+        })
+    }
+
+    pub fn player(&mut self) -> &mut Player {
+        match &mut self.player_node.value {
+            NodeType::Player(player) => player,
+            _ => panic!("not player"),
+        }
+    }
+
+    pub fn camera(&mut self) -> &mut Camera {
+        match &mut self.camera_node.value {
+            NodeType::Camera(camera) => camera,
+            _ => panic!("not camera"),
+        }
+    }
+
+    pub fn projection(&self, width: u32, height: u32) -> glm::Mat4 {
+        glm::perspective(
+            width as f32 / height as f32,
+            45_f32.to_radians(),
+            1.0,
+            (2.0 * self.view_distance).max(1000.0),
+        )
+    }
+
+    // The state for the first frame, with the camera already placed in the world.
+    pub fn new_state(&mut self) -> State {
+        let mut state = State {
+            camera_position: glm::vec3(0.0, 0.0, 0.0),
+            elapsed: 0.0,
+            delta_time: 0.0,
+            frame_num: 0,
+            pressed_keys: vec![],
+            delta_x: 0.0,
+            delta_y: 0.0,
+        };
+        self.graph.update_transforms(&state);
+        state.camera_position = self.camera_world_position();
+        state
+    }
+
+    // Streams tiles in and out around the camera and updates the transforms. Call once per frame, after the
+    // player has moved.
+    pub fn update(&mut self, state: &mut State) {
+        // Keep the world centred on the camera so positions stay precise wherever the player travels.
+        if let Some(shift) = self.tile_manager.rebase(&state.camera_position) {
+            self.player().position -= shift;
+            state.camera_position -= shift;
+        }
+        self.tile_manager
+            .update(&state.camera_position, &mut self.graph.root);
+
+        self.graph.update_transforms(state);
+        state.camera_position = self.camera_world_position();
+    }
+
+    pub fn is_loading(&self) -> bool {
+        self.tile_manager.is_loading()
+    }
+
+    pub unsafe fn draw(&mut self, state: &State, projection_transform: &glm::Mat4) {
+        gl::ClearColor(0.1, 0.1, 0.1, 1.0);
+        gl::Clear(gl::COLOR_BUFFER_BIT | gl::DEPTH_BUFFER_BIT);
+
+        // using change of coordinates:
+        let view_transform = self.camera().get_view_transform();
+        self.graph
+            .draw_scene(state, &view_transform, projection_transform);
+
+        gl::DepthFunc(gl::LEQUAL);
+        self.skybox_shader.activate();
+        self.skybox_shader.set_int("skybox", 0);
+
+        // remove translation from the view matrix
+        let mut view = view_transform;
+        view = glm::set_column(&view, 3, &glm::vec4(0.0, 0.0, 0.0, 1.0));
+        self.skybox_shader.set_mat4("view_transform", &view);
+        self.skybox_shader
+            .set_mat4("projection", projection_transform);
+
+        // skybox cube
+        gl::BindVertexArray(self.skybox_vao);
+        gl::ActiveTexture(gl::TEXTURE0);
+        gl::BindTexture(gl::TEXTURE_CUBE_MAP, self.skybox_texture);
+        gl::DrawArrays(gl::TRIANGLES, 0, 36);
+        gl::DepthFunc(gl::LESS);
+    }
+
+    fn camera_world_position(&mut self) -> glm::Vec3 {
+        let camera = self.camera();
+        (camera.parent_transform
+            * glm::vec4(camera.position.x, camera.position.y, camera.position.z, 1.0))
+        .xyz()
+    }
+}
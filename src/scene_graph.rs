@@ -184,4 +184,9 @@ impl SceneNode {
         self.children
             .push(child as *const SceneNode as *mut SceneNode)
     }
+
+    pub fn remove_child(&mut self, child: &SceneNode) {
+        let child = child as *const SceneNode as *mut SceneNode;
+        self.children.retain(|node| *node != child);
+    }
 }
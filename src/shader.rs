@@ -1,3 +1,4 @@
+use super::error::Error;
 use gl;
 use std::{ffi::CString, path::Path, ptr, str};
 
@@ -7,12 +8,15 @@ pub struct Shader {
 }
 
 impl Shader {
-    pub fn new(vertex_shader_path: &str, fragment_shader_path: &str) -> Self {
+    pub fn new(vertex_shader_path: &str, fragment_shader_path: &str) -> Result<Self, Error> {
         unsafe {
             let mut shader_builder = ShaderBuilder::new();
-            shader_builder = shader_builder.attach_file(vertex_shader_path);
-            shader_builder = shader_builder.attach_file(fragment_shader_path);
-            shader_builder.link()
+            shader_builder = shader_builder.attach_file(vertex_shader_path)?;
+            shader_builder = shader_builder.attach_file(fragment_shader_path)?;
+            shader_builder.link().map_err(|log| Error::Shader {
+                path: format!("{} and {}", vertex_shader_path, fragment_shader_path),
+                log,
+            })
         }
     }
 
@@ -81,7 +85,7 @@ impl Into<gl::types::GLenum> for ShaderType {
 
 impl ShaderType {
     fn from_ext(ext: &std::ffi::OsStr) -> Result<ShaderType, String> {
-        match ext.to_str().expect("Failed to read extension") {
+        match ext.to_str().unwrap_or("") {
             "vert" => Ok(ShaderType::Vertex),
             "frag" => Ok(ShaderType::Fragment),
             "tcs" => Ok(ShaderType::TessellationControl),
@@ -100,98 +104,105 @@ impl ShaderBuilder {
         }
     }
 
-    pub unsafe fn attach_file(self, shader_path: &str) -> ShaderBuilder {
+    pub unsafe fn attach_file(self, shader_path: &str) -> Result<ShaderBuilder, Error> {
+        let error = |log: String| Error::Shader {
+            path: String::from(shader_path),
+            log,
+        };
         let path = Path::new(shader_path);
-        if let Some(extension) = path.extension() {
-            let shader_type =
-                ShaderType::from_ext(extension).expect("Failed to parse file extension.");
-            let shader_src = std::fs::read_to_string(path)
-                .expect(&format!("Failed to read shader source. {}", shader_path));
-            self.compile_shader(&shader_src, shader_type)
-        } else {
-            panic!(
-                "Failed to read extension of file with path: {}",
-                shader_path
-            );
-        }
+        let extension = match path.extension() {
+            Some(extension) => extension,
+            None => return Err(error(String::from("the file has no extension"))),
+        };
+        let shader_type = ShaderType::from_ext(extension)
+            .map_err(|e| error(format!("unknown shader type .{}", e)))?;
+        let shader_src = std::fs::read_to_string(path).map_err(|e| error(e.to_string()))?;
+        self.compile_shader(&shader_src, shader_type).map_err(error)
     }
 
+    // Returns the compiler output when the source does not compile.
     pub unsafe fn compile_shader(
         mut self,
         shader_src: &str,
         shader_type: ShaderType,
-    ) -> ShaderBuilder {
+    ) -> Result<ShaderBuilder, String> {
         let shader = gl::CreateShader(shader_type.into());
-        let c_str_shader = CString::new(shader_src.as_bytes()).unwrap();
+        let c_str_shader = CString::new(shader_src.as_bytes()).map_err(|e| e.to_string())?;
         gl::ShaderSource(shader, 1, &c_str_shader.as_ptr(), ptr::null());
         gl::CompileShader(shader);
 
-        if !self.check_shader_errors(shader) {
-            panic!("Shader failed to compile.");
+        if let Err(log) = self.check_shader_errors(shader) {
+            gl::DeleteShader(shader);
+            return Err(log);
         }
 
         self.shaders.push(shader);
 
-        self
+        Ok(self)
     }
 
-    unsafe fn check_shader_errors(&self, shader_id: u32) -> bool {
+    unsafe fn check_shader_errors(&self, shader_id: u32) -> Result<(), String> {
         let mut success = i32::from(gl::FALSE);
-        let mut info_log = Vec::with_capacity(512);
-        info_log.set_len(512 - 1);
         gl::GetShaderiv(shader_id, gl::COMPILE_STATUS, &mut success);
         if success != i32::from(gl::TRUE) {
+            let mut info_log = vec![0u8; 512];
+            let mut length = 0;
             gl::GetShaderInfoLog(
                 shader_id,
-                512,
-                ptr::null_mut(),
+                info_log.len() as i32,
+                &mut length,
                 info_log.as_mut_ptr() as *mut gl::types::GLchar,
             );
-            println!(
-                "ERROR::Shader Compilation Failed!\n{}",
-                String::from_utf8_lossy(&info_log)
-            );
-            return false;
+            info_log.truncate(length.max(0) as usize);
+            return Err(info_log_string(&info_log));
         }
-        true
+        Ok(())
     }
 
-    unsafe fn check_linker_errors(&self) -> bool {
+    unsafe fn check_linker_errors(&self) -> Result<(), String> {
         let mut success = i32::from(gl::FALSE);
-        let mut info_log = Vec::with_capacity(512);
-        info_log.set_len(512 - 1);
         gl::GetProgramiv(self.program_id, gl::LINK_STATUS, &mut success);
         if success != i32::from(gl::TRUE) {
+            let mut info_log = vec![0u8; 512];
+            let mut length = 0;
             gl::GetProgramInfoLog(
                 self.program_id,
-                512,
-                ptr::null_mut(),
+                info_log.len() as i32,
+                &mut length,
                 info_log.as_mut_ptr() as *mut gl::types::GLchar,
             );
-            println!(
-                "ERROR::SHADER::PROGRAM::COMPILATION_FAILED\n{}",
-                String::from_utf8_lossy(&info_log)
-            );
-            return false;
+            info_log.truncate(length.max(0) as usize);
+            return Err(info_log_string(&info_log));
         }
-        true
+        Ok(())
     }
 
-    pub unsafe fn link(self) -> Shader {
+    // Returns the linker output when the shaders do not link into a program.
+    pub unsafe fn link(self) -> Result<Shader, String> {
         for &shader in &self.shaders {
             gl::AttachShader(self.program_id, shader);
         }
         gl::LinkProgram(self.program_id);
 
-        // todo:: use this to make safer abstraction
-        self.check_linker_errors();
+        let linked = self.check_linker_errors();
 
         for &shader in &self.shaders {
             gl::DeleteShader(shader);
         }
 
-        Shader {
+        linked.map(|_| Shader {
             program_id: self.program_id,
-        }
+        })
     }
 }
+
+// The info log is a C string in a fixed size buffer.
+fn info_log_string(info_log: &[u8]) -> String {
+    let end = info_log
+        .iter()
+        .position(|&c| c == 0)
+        .unwrap_or(info_log.len());
+    String::from_utf8_lossy(&info_log[..end])
+        .trim_end()
+        .to_string()
+}
@@ -1,12 +1,13 @@
+use super::error::Error;
+use super::mesh;
 use super::util;
 use gl;
 use gl::types::*;
 use image;
 use image::GenericImageView;
 use std::os::raw::c_void;
-use std::path::Path;
 use std::ptr;
-pub unsafe fn create_skybox() -> (u32, u32) {
+pub unsafe fn create_skybox() -> Result<(u32, u32), Error> {
     let skybox_vertices: [f32; 108] = [
         // positions
         -1.0, -1.0, -1.0, 1.0, -1.0, -1.0, -1.0, 1.0, -1.0, 1.0, 1.0, -1.0, -1.0, 1.0, -1.0, 1.0,
@@ -41,17 +42,17 @@ pub unsafe fn create_skybox() -> (u32, u32) {
         "assets/textures/skybox/back.png",
         "assets/textures/skybox/front.png",
     ];
-    let cubemap_texture = load_cubemap(&faces);
-    (skybox_vao, cubemap_texture)
+    let cubemap_texture = load_cubemap(&faces)?;
+    Ok((skybox_vao, cubemap_texture))
 }
 
-unsafe fn load_cubemap(faces: &[&str]) -> u32 {
+unsafe fn load_cubemap(faces: &[&str]) -> Result<u32, Error> {
     let mut texture_id = 0;
     gl::GenTextures(1, &mut texture_id);
     gl::BindTexture(gl::TEXTURE_CUBE_MAP, texture_id);
 
     for (i, face) in faces.iter().enumerate() {
-        let img = image::open(&Path::new(face)).expect("Cubemap texture failed to load");
+        let img = mesh::load_image(face)?;
         let data = img.to_bytes();
         gl::TexImage2D(
             gl::TEXTURE_CUBE_MAP_POSITIVE_X + i as u32,
@@ -92,5 +93,5 @@ unsafe fn load_cubemap(faces: &[&str]) -> u32 {
         gl::CLAMP_TO_EDGE as i32,
     );
 
-    texture_id
+    Ok(texture_id)
 }
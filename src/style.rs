@@ -0,0 +1,489 @@
+use super::colors::ColorRule;
+use super::error::Error;
+use super::features::{FeatureOption, GeometryOptions};
+use super::filter::Filter;
+use super::mapbox_style;
+use super::material::Material;
+use super::protos::vector_tile;
+use super::shader::Shader;
+use super::zoom::ZoomValue;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::path::Path;
+
+// Shader names usable in a style, with the vertex and fragment shader they are built from.
+pub const SHADERS: &[(&str, &str, &str)] = &[
+    ("color", "./shaders/color.vert", "./shaders/color.frag"),
+    (
+        "color_lit",
+        "./shaders/color.vert",
+        "./shaders/color_lit.frag",
+    ),
+    (
+        "texture",
+        "./shaders/texture.vert",
+        "./shaders/texture_phong.frag",
+    ),
+];
+
+#[derive(Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct Style {
+    pub layers: Vec<LayerStyle>,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct LayerStyle {
+    pub source_layer: String,
+    #[serde(rename = "type")]
+    pub layer_type: LayerType,
+    #[serde(default)]
+    pub filter: Option<Filter>,
+    #[serde(default = "default_shader")]
+    pub shader: String,
+    #[serde(default)]
+    pub color: Option<ColorStyle>,
+    #[serde(default)]
+    pub texture: Option<String>,
+    #[serde(default)]
+    pub fill: Option<FillStyle>,
+    #[serde(default)]
+    pub line: Option<LineStyle>,
+    #[serde(default)]
+    pub point: Option<PointStyle>,
+    #[serde(default)]
+    pub min_zoom: Option<f32>,
+    #[serde(default)]
+    pub max_zoom: Option<f32>,
+}
+
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum LayerType {
+    Fill,
+    Line,
+    Point,
+}
+
+// A constant `[r, g, b, a]` colour, one picked per feature from a property, or one interpolated by zoom.
+#[derive(Deserialize, Debug, Clone)]
+#[serde(untagged)]
+pub enum ColorStyle {
+    Constant([f32; 4]),
+    Property(PropertyColorStyle),
+    Zoom(ZoomValue<[f32; 4]>),
+}
+
+// Maps the values of `property` to colours, either through `categories` keyed by the value's string form or
+// by interpolating between numeric `stops`. Features without a matching value use `default`.
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct PropertyColorStyle {
+    pub property: String,
+    #[serde(default)]
+    pub categories: Option<HashMap<String, [f32; 4]>>,
+    #[serde(default)]
+    pub stops: Option<Vec<(f64, [f32; 4])>>,
+    pub default: [f32; 4],
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct FillStyle {
+    #[serde(default)]
+    pub height: ZoomValue<f32>,
+    #[serde(default)]
+    pub min_height: ZoomValue<f32>,
+    // The feature properties that replace `height` and `min_height` for features that have them, or null to always
+    // use the style values.
+    #[serde(default = "default_height_property")]
+    pub height_property: Option<String>,
+    #[serde(default = "default_min_height_property")]
+    pub min_height_property: Option<String>,
+    #[serde(default)]
+    pub walls: bool,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct LineStyle {
+    pub width: ZoomValue<f32>,
+    #[serde(default)]
+    pub height: f32,
+    // How many metres lines reach past the edge of their tile.
+    #[serde(default)]
+    pub buffer: f32,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct PointStyle {
+    pub radius: ZoomValue<f32>,
+    #[serde(default)]
+    pub top_radius: Option<ZoomValue<f32>>,
+    pub height: ZoomValue<f32>,
+    #[serde(default = "default_sides")]
+    pub sides: u32,
+}
+
+fn default_shader() -> String {
+    String::from("color")
+}
+
+fn default_sides() -> u32 {
+    8
+}
+
+fn default_height_property() -> Option<String> {
+    Some(String::from("height"))
+}
+
+fn default_min_height_property() -> Option<String> {
+    Some(String::from("min_height"))
+}
+
+pub fn load_style<P: AsRef<Path>>(path: P) -> Result<Style, Error> {
+    let path = path.as_ref();
+    let error = |message: String| Error::Style {
+        path: path.display().to_string(),
+        message,
+    };
+    let source = std::fs::read_to_string(path).map_err(|e| error(e.to_string()))?;
+
+    let document: serde_json::Value =
+        serde_json::from_str(&source).map_err(|e| error(e.to_string()))?;
+    if mapbox_style::is_mapbox_style(&document) {
+        let (style, warnings) = mapbox_style::import_style(&document).map_err(error)?;
+        for warning in warnings {
+            println!("Style warning: {}", warning);
+        }
+        return Ok(style);
+    }
+
+    // Parse the source again rather than the document so errors keep their line and column.
+    let style: Style = serde_json::from_str(&source).map_err(|e| error(e.to_string()))?;
+    style.validate().map_err(error)?;
+    Ok(style)
+}
+
+impl Style {
+    pub fn validate(&self) -> Result<(), String> {
+        for (i, layer) in self.layers.iter().enumerate() {
+            layer
+                .validate()
+                .map_err(|e| format!("layers[{}] ({}): {}", i, layer.source_layer, e))?;
+        }
+        Ok(())
+    }
+
+    // Builds the feature options for every layer. `shaders` must contain a shader for every name in `SHADERS`.
+    pub fn feature_options(&self, shaders: &HashMap<String, Shader>) -> Vec<FeatureOption> {
+        self.layers
+            .iter()
+            .map(|layer| layer.feature_option(shaders))
+            .collect()
+    }
+}
+
+impl LayerStyle {
+    fn validate(&self) -> Result<(), String> {
+        if !SHADERS.iter().any(|(name, _, _)| *name == self.shader) {
+            let names: Vec<&str> = SHADERS.iter().map(|(name, _, _)| *name).collect();
+            return Err(format!(
+                "unknown shader \"{}\", expected one of {}",
+                self.shader,
+                names.join(", ")
+            ));
+        }
+
+        match &self.color {
+            Some(color) => color.validate()?,
+            None => {
+                if self.texture.is_none() {
+                    return Err(String::from("either \"color\" or \"texture\" is required"));
+                }
+            }
+        }
+
+        if let Some(texture) = &self.texture {
+            if !Path::new(texture).is_file() {
+                return Err(format!("texture {} does not exist", texture));
+            }
+        }
+
+        if let (Some(min_zoom), Some(max_zoom)) = (self.min_zoom, self.max_zoom) {
+            if min_zoom >= max_zoom {
+                return Err(String::from("\"min_zoom\" must be less than \"max_zoom\""));
+            }
+        }
+
+        match self.layer_type {
+            LayerType::Fill => match &self.fill {
+                Some(fill) => {
+                    fill.height.validate()?;
+                    fill.min_height.validate()?;
+                }
+                None => return Err(String::from("type \"fill\" requires a \"fill\" section")),
+            },
+            LayerType::Line => match &self.line {
+                Some(line) => {
+                    line.width.validate()?;
+                    if line.width.values().iter().any(|width| *width <= 0.0) {
+                        return Err(String::from("line width must be positive"));
+                    }
+                }
+                None => return Err(String::from("type \"line\" requires a \"line\" section")),
+            },
+            LayerType::Point => match &self.point {
+                Some(point) => {
+                    point.radius.validate()?;
+                    point.height.validate()?;
+                    if let Some(top_radius) = &point.top_radius {
+                        top_radius.validate()?;
+                    }
+                    let mut sizes = point.radius.values();
+                    sizes.extend(point.height.values());
+                    if sizes.iter().any(|size| *size <= 0.0) {
+                        return Err(String::from("point radius and height must be positive"));
+                    }
+                }
+                None => return Err(String::from("type \"point\" requires a \"point\" section")),
+            },
+        }
+
+        Ok(())
+    }
+
+    fn feature_option(&self, shaders: &HashMap<String, Shader>) -> FeatureOption {
+        // Colours are baked into the vertices, the material only tints them.
+        let mut material = Material::new(shaders[&self.shader]);
+        material.set_vec4("u_color", 1.0, 1.0, 1.0, 1.0);
+        let color = match &self.color {
+            Some(color) => color.color_rule(),
+            None => ColorRule::Constant(glm::vec4(1.0, 1.0, 1.0, 1.0)),
+        };
+
+        let (geo_type, geometry) = match self.layer_type {
+            LayerType::Fill => {
+                let fill = self.fill.as_ref().unwrap();
+                let geometry = GeometryOptions::Fill {
+                    height: fill.height.clone(),
+                    min_height: fill.min_height.clone(),
+                    height_property: fill.height_property.clone(),
+                    min_height_property: fill.min_height_property.clone(),
+                    walls: fill.walls,
+                };
+                (vector_tile::Tile_GeomType::POLYGON, geometry)
+            }
+            LayerType::Line => {
+                let line = self.line.as_ref().unwrap();
+                let geometry = GeometryOptions::Line {
+                    width: line.width.clone(),
+                    height: line.height,
+                    buffer: line.buffer,
+                };
+                (vector_tile::Tile_GeomType::LINESTRING, geometry)
+            }
+            LayerType::Point => {
+                let point = self.point.as_ref().unwrap();
+                let geometry = GeometryOptions::Point {
+                    radius: point.radius.clone(),
+                    top_radius: point.top_radius.as_ref().unwrap_or(&point.radius).clone(),
+                    height: point.height.clone(),
+                    sides: point.sides,
+                };
+                (vector_tile::Tile_GeomType::POINT, geometry)
+            }
+        };
+
+        FeatureOption {
+            layer: self.source_layer.clone(),
+            material,
+            color,
+            filter: self.filter.clone(),
+            geo_type,
+            geometry,
+            texture: self.texture.clone(),
+            min_zoom: self.min_zoom,
+            max_zoom: self.max_zoom,
+        }
+    }
+}
+
+impl ColorStyle {
+    fn validate(&self) -> Result<(), String> {
+        match self {
+            ColorStyle::Constant(color) => validate_color(color),
+            ColorStyle::Zoom(color) => {
+                color.validate()?;
+                for color in color.values() {
+                    validate_color(&color)?;
+                }
+                Ok(())
+            }
+            ColorStyle::Property(style) => {
+                validate_color(&style.default)?;
+                match (&style.categories, &style.stops) {
+                    (Some(categories), None) => {
+                        for color in categories.values() {
+                            validate_color(color)?;
+                        }
+                    }
+                    (None, Some(stops)) => {
+                        if stops.is_empty() {
+                            return Err(String::from("color \"stops\" must not be empty"));
+                        }
+                        if stops.windows(2).any(|pair| pair[0].0 > pair[1].0) {
+                            return Err(String::from("color \"stops\" must be sorted by value"));
+                        }
+                        for (_, color) in stops {
+                            validate_color(color)?;
+                        }
+                    }
+                    _ => {
+                        return Err(String::from(
+                            "a property color needs either \"categories\" or \"stops\"",
+                        ))
+                    }
+                }
+                Ok(())
+            }
+        }
+    }
+
+    fn color_rule(&self) -> ColorRule {
+        match self {
+            ColorStyle::Constant(color) => ColorRule::Constant(to_vec4(color)),
+            ColorStyle::Zoom(color) => ColorRule::Zoom(color.map(|color| to_vec4(&color))),
+            ColorStyle::Property(style) => match (&style.categories, &style.stops) {
+                (Some(categories), _) => ColorRule::Categorical {
+                    property: style.property.clone(),
+                    categories: categories
+                        .iter()
+                        .map(|(value, color)| (value.clone(), to_vec4(color)))
+                        .collect(),
+                    default: to_vec4(&style.default),
+                },
+                (None, stops) => ColorRule::Ramp {
+                    property: style.property.clone(),
+                    stops: stops
+                        .iter()
+                        .flatten()
+                        .map(|(value, color)| (*value, to_vec4(color)))
+                        .collect(),
+                    default: to_vec4(&style.default),
+                },
+            },
+        }
+    }
+}
+
+fn validate_color(color: &[f32; 4]) -> Result<(), String> {
+    if color.iter().any(|c| *c < 0.0 || *c > 1.0) {
+        return Err(String::from("color components must be between 0 and 1"));
+    }
+    Ok(())
+}
+
+fn to_vec4(color: &[f32; 4]) -> glm::Vec4 {
+    glm::vec4(color[0], color[1], color[2], color[3])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A style with a valid water layer followed by `layer`.
+    fn parse(layer: &str) -> Result<Style, String> {
+        let source = format!(
+            r#"{{ "layers": [
+                {{ "source_layer": "water", "type": "fill", "color": [0, 0, 1, 1], "fill": {{}} }},
+                {}
+            ] }}"#,
+            layer
+        );
+        let style: Style = serde_json::from_str(&source).map_err(|e| e.to_string())?;
+        style.validate()?;
+        Ok(style)
+    }
+
+    #[test]
+    fn valid_layers_pass() {
+        for layer in [
+            r#"{ "source_layer": "road", "type": "line", "color": [0, 0, 0, 1], "line": { "width": 2 } }"#,
+            r#"{
+                "source_layer": "road",
+                "type": "line",
+                "color": { "stops": [[10, [0, 0, 0, 1]], [14, [1, 1, 1, 1]]] },
+                "line": { "width": { "stops": [[12, 1], [18, 6]] } },
+                "min_zoom": 12
+            }"#,
+            r#"{
+                "source_layer": "building",
+                "type": "fill",
+                "shader": "color_lit",
+                "color": { "property": "type", "categories": { "church": [1, 0, 0, 1] }, "default": [1, 1, 1, 1] },
+                "fill": { "height": 10, "walls": true }
+            }"#,
+        ]
+        .iter()
+        {
+            if let Err(e) = parse(layer) {
+                panic!("{}: {}", layer, e);
+            }
+        }
+    }
+
+    #[test]
+    fn invalid_layers_are_reported_by_index() {
+        let cases = [
+            (
+                r#"{ "source_layer": "road", "type": "line", "color": [0, 0, 0, 1] }"#,
+                "layers[1] (road): type \"line\" requires a \"line\" section",
+            ),
+            (
+                r#"{ "source_layer": "road", "type": "line", "shader": "glow", "color": [0, 0, 0, 1], "line": { "width": 2 } }"#,
+                "layers[1] (road): unknown shader \"glow\"",
+            ),
+            (
+                r#"{ "source_layer": "road", "type": "line", "line": { "width": 2 } }"#,
+                "layers[1] (road): either \"color\" or \"texture\" is required",
+            ),
+            (
+                r#"{ "source_layer": "road", "type": "line", "color": [0, 0, 0, 1], "line": { "width": { "stops": [[18, 6], [12, 1]] } } }"#,
+                "layers[1] (road): zoom \"stops\" must be sorted by zoom",
+            ),
+            (
+                r#"{ "source_layer": "road", "type": "line", "color": [0, 0, 0, 1], "line": { "width": { "stops": [] } } }"#,
+                "layers[1] (road): zoom \"stops\" must not be empty",
+            ),
+            (
+                r#"{ "source_layer": "road", "type": "line", "color": [0, 0, 0, 1], "line": { "width": 0 } }"#,
+                "layers[1] (road): line width must be positive",
+            ),
+            (
+                r#"{ "source_layer": "road", "type": "line", "color": [0, 0, 255, 1], "line": { "width": 2 } }"#,
+                "layers[1] (road): color components must be between 0 and 1",
+            ),
+            (
+                r#"{ "source_layer": "road", "type": "line", "color": [0, 0, 0, 1], "line": { "width": 2 }, "min_zoom": 14, "max_zoom": 12 }"#,
+                "layers[1] (road): \"min_zoom\" must be less than \"max_zoom\"",
+            ),
+        ];
+        for (layer, message) in cases.iter() {
+            match parse(layer) {
+                Ok(_) => panic!("{} was accepted", layer),
+                Err(e) => assert!(e.starts_with(message), "{}: {}", layer, e),
+            }
+        }
+    }
+
+    #[test]
+    fn unknown_layer_types_are_rejected() {
+        match parse(r#"{ "source_layer": "road", "type": "polygon", "color": [0, 0, 0, 1] }"#) {
+            Ok(_) => panic!("unknown layer type was accepted"),
+            Err(e) => assert!(e.contains("unknown variant `polygon`"), "{}", e),
+        }
+    }
+}
@@ -0,0 +1,431 @@
+use super::features::{get_filtered_features, FeatureOption};
+use super::properties::FeatureProperties;
+use super::protos::vector_tile;
+use super::tile_address::TileAddress;
+use std::collections::HashMap;
+use vector_tile_geometry::clip::ClipBox;
+use vector_tile_geometry::decoder::{
+    self, normalize_point, Geometry, GeometryType, Polygon, RingClassification,
+};
+use vector_tile_geometry::line_strings;
+use vector_tile_geometry::points;
+use vector_tile_geometry::polygons::{OutputMesh, PolygonBuilder, PolygonOptions};
+
+// The geometry of every feature matching one `FeatureOption` in a tile, merged into a single mesh.
+pub struct FeatureMesh {
+    pub option_index: usize,
+    pub mesh: OutputMesh,
+}
+
+pub struct BuiltTile {
+    pub meshes: Vec<FeatureMesh>,
+    // The sides of the tile, in the order of `side_neighbours`, with walls built along them. Without the
+    // neighbouring tile on that side some of them may be where a building was cut rather than real walls.
+    pub edge_walls: [bool; 4],
+}
+
+// The tiles sharing the left, right, top and bottom side of a tile, the order `ClipBox::side_along` numbers the
+// sides in. Sides `2 * n` and `2 * n + 1` are opposite each other.
+pub fn side_neighbours(address: &TileAddress) -> [Option<TileAddress>; 4] {
+    let mut neighbours = [None; 4];
+    for (side, neighbour) in neighbours.iter_mut().enumerate() {
+        let offset = ClipBox::outward(side);
+        *neighbour = TileAddress::wrapped(
+            address.x as i64 + offset.x as i64,
+            address.y as i64 + offset.y as i64,
+            address.z,
+        )
+        .filter(|neighbour| neighbour != address);
+    }
+    neighbours
+}
+
+// Decodes and tessellates a tile. This only touches CPU data, the meshes still have to be uploaded to the GPU.
+// The meshes span 0 to 1 across the tile, and all sizes in metres are converted to that unit on every axis for a
+// world that is `world_width` units wide. Scaling the tile uniformly by its width in that world then gives sizes in
+// world units, the same for every tile whatever its zoom or latitude. `neighbours` are the tiles of
+// `side_neighbours` that are loaded, which tell where buildings go on into the next tile.
+pub fn build_tile(
+    tile: &vector_tile::Tile,
+    neighbours: &[Option<&vector_tile::Tile>; 4],
+    options: &[FeatureOption],
+    address: &TileAddress,
+    world_width: f64,
+) -> BuiltTile {
+    let inverse_tile_scale = (1u64 << address.z) as f64 / world_width;
+    let zoom_level = address.z as f32;
+
+    let mut feature_meshes = vec![];
+    let mut edge_walls = [false; 4];
+    for layer in tile.get_layers() {
+        for (option_index, option) in options.iter().enumerate() {
+            if option.layer.as_str() != layer.get_name() || !option.visible_at(zoom_level) {
+                continue;
+            }
+
+            let mut merged = OutputMesh::new();
+            let features = get_filtered_features(layer, &option.filter);
+
+            if option.geo_type == vector_tile::Tile_GeomType::POLYGON {
+                let shapes = polygon_shapes(layer, option, zoom_level, inverse_tile_scale, |f| {
+                    match decode_feature(address, layer, f, GeometryType::Polygon) {
+                        Some(Geometry::Polygons(polygons)) => Some(polygons),
+                        _ => None,
+                    }
+                });
+
+                let beyond =
+                    neighbouring_shapes(layer, neighbours, option, zoom_level, inverse_tile_scale);
+                for shape in shapes {
+                    let mut polygon_builder = PolygonBuilder::new(shape.options);
+                    let beyond = beyond
+                        .get(&shape.key)
+                        .map_or(&[][..], |polygons| &polygons[..]);
+                    polygon_builder.add_polygons(&shape.polygons, beyond);
+                    for (side, walls) in polygon_builder.edge_walls.iter().enumerate() {
+                        edge_walls[side] |= *walls;
+                    }
+                    merged.add_colored(polygon_builder.output_mesh, shape.color);
+                }
+            } else if option.geo_type == vector_tile::Tile_GeomType::LINESTRING {
+                let line_string_options = option.line_options(zoom_level).unwrap();
+                for feature in features {
+                    if feature.get_field_type() != option.geo_type {
+                        continue;
+                    }
+
+                    let lines =
+                        match decode_feature(address, layer, feature, GeometryType::LineString) {
+                            Some(Geometry::LineStrings(lines)) => lines,
+                            _ => continue,
+                        };
+                    let thickness = (line_string_options.width as f64 * inverse_tile_scale) as f32;
+                    let height = (line_string_options.height as f64 * inverse_tile_scale) as f32;
+
+                    let buffer = (line_string_options.buffer as f64 * inverse_tile_scale) as f32;
+                    let m = line_strings::build_line_strings(
+                        &lines,
+                        thickness,
+                        height,
+                        Some(ClipBox::tile(buffer)),
+                    );
+                    if m.vertices.len() < 3 {
+                        continue;
+                    }
+                    let color = option
+                        .color
+                        .evaluate(&FeatureProperties::new(layer, feature), zoom_level);
+                    merged.add_colored(m, color);
+                }
+            } else if option.geo_type == vector_tile::Tile_GeomType::POINT {
+                // Marker sizes are given in meters.
+                let mut point_options = option.point_options(zoom_level).unwrap();
+                point_options.radius = (point_options.radius as f64 * inverse_tile_scale) as f32;
+                point_options.top_radius =
+                    (point_options.top_radius as f64 * inverse_tile_scale) as f32;
+                point_options.min_height =
+                    (point_options.min_height as f64 * inverse_tile_scale) as f32;
+                point_options.max_height =
+                    (point_options.max_height as f64 * inverse_tile_scale) as f32;
+
+                for feature in features {
+                    if feature.get_field_type() != option.geo_type {
+                        continue;
+                    }
+
+                    let points = match decode_feature(address, layer, feature, GeometryType::Point)
+                    {
+                        Some(Geometry::Points(points)) => points,
+                        _ => continue,
+                    };
+
+                    let m = points::generate_markers(&points, &point_options);
+                    let color = option
+                        .color
+                        .evaluate(&FeatureProperties::new(layer, feature), zoom_level);
+                    merged.add_colored(m, color);
+                }
+            }
+
+            if !merged.vertices.is_empty() {
+                feature_meshes.push(FeatureMesh {
+                    option_index,
+                    mesh: merged,
+                });
+            }
+        }
+    }
+
+    BuiltTile {
+        meshes: feature_meshes,
+        edge_walls,
+    }
+}
+
+// The polygons of the shapes in the neighbouring tiles, moved next to the tile being built. Their invalid features
+// were already reported when they were built themselves.
+fn neighbouring_shapes(
+    layer: &vector_tile::Tile_Layer,
+    neighbours: &[Option<&vector_tile::Tile>; 4],
+    option: &FeatureOption,
+    zoom_level: f32,
+    inverse_tile_scale: f64,
+) -> HashMap<ShapeKey, Vec<Polygon<glm::Vec2>>> {
+    let mut beyond: HashMap<ShapeKey, Vec<Polygon<glm::Vec2>>> = HashMap::new();
+    for (side, neighbour) in neighbours.iter().enumerate() {
+        let neighbour_layer = match neighbour.and_then(|tile| {
+            tile.get_layers()
+                .iter()
+                .find(|other| other.get_name() == layer.get_name())
+        }) {
+            Some(neighbour_layer) => neighbour_layer,
+            None => continue,
+        };
+        let offset = ClipBox::outward(side);
+        let extent = neighbour_layer.get_extent();
+        let classification = RingClassification::for_version(neighbour_layer.get_version());
+        let decode = |feature: &vector_tile::Tile_Feature| {
+            let geometry = feature.get_geometry();
+            match decoder::decode(GeometryType::Polygon, geometry, classification) {
+                Ok(Geometry::Polygons(polygons)) => Some(
+                    polygons
+                        .iter()
+                        .map(|polygon| {
+                            polygon.map(|point| normalize_point(*point, extent) + offset)
+                        })
+                        .collect(),
+                ),
+                _ => None,
+            }
+        };
+        let shapes = polygon_shapes(
+            neighbour_layer,
+            option,
+            zoom_level,
+            inverse_tile_scale,
+            decode,
+        );
+        for shape in shapes {
+            beyond.entry(shape.key).or_default().extend(shape.polygons);
+        }
+    }
+    beyond
+}
+
+// Features sharing an id, heights and colour are built as one shape, see `polygon_shapes`.
+type ShapeKey = (Option<u64>, [u32; 6]);
+
+struct Shape {
+    key: ShapeKey,
+    options: PolygonOptions,
+    color: glm::Vec4,
+    polygons: Vec<Polygon<glm::Vec2>>,
+}
+
+// The polygon features of `layer` matching `option`, decoded by `decode` and grouped into the shapes they are built
+// as. Features sharing an id, heights and colour are built as one shape, so there are no walls where the parts of a
+// building meet. Features without an id are built on their own, as are neighbouring buildings that only touch,
+// since nothing tells them apart from parts of one building.
+fn polygon_shapes<F>(
+    layer: &vector_tile::Tile_Layer,
+    option: &FeatureOption,
+    zoom_level: f32,
+    inverse_tile_scale: f64,
+    decode: F,
+) -> Vec<Shape>
+where
+    F: Fn(&vector_tile::Tile_Feature) -> Option<Vec<Polygon<glm::Vec2>>>,
+{
+    let polygon_options = option.polygon_options(zoom_level).unwrap();
+    let (height_property, min_height_property) = option.height_properties();
+    let mut shapes: Vec<Shape> = vec![];
+    let mut shape_of: HashMap<ShapeKey, usize> = HashMap::new();
+    for feature in get_filtered_features(layer, &option.filter) {
+        if feature.get_field_type() != option.geo_type {
+            continue;
+        }
+
+        let polygons = match decode(feature) {
+            Some(polygons) => polygons,
+            None => continue,
+        };
+
+        let mut options = polygon_options.clone();
+        let properties = FeatureProperties::new(layer, feature);
+        let height = height_property.and_then(|key| properties.get_f64(key));
+        let min_height = min_height_property.and_then(|key| properties.get_f64(key));
+        if let Some(min_height) = min_height {
+            options.min_height = min_height as f32;
+        }
+        if let Some(height) = height {
+            options.max_height = options.min_height + height as f32;
+        }
+        options.min_height = (options.min_height as f64 * inverse_tile_scale) as f32;
+        options.max_height = (options.max_height as f64 * inverse_tile_scale) as f32;
+
+        let color = option.color.evaluate(&properties, zoom_level);
+        let id = if feature.has_id() {
+            Some(feature.get_id())
+        } else {
+            None
+        };
+        let key = (
+            id,
+            [
+                options.min_height.to_bits(),
+                options.max_height.to_bits(),
+                color.x.to_bits(),
+                color.y.to_bits(),
+                color.z.to_bits(),
+                color.w.to_bits(),
+            ],
+        );
+        match shape_of.get(&key) {
+            Some(&shape) if id.is_some() => shapes[shape].polygons.extend(polygons),
+            _ => {
+                shape_of.insert(key, shapes.len());
+                shapes.push(Shape {
+                    key,
+                    options,
+                    color,
+                    polygons,
+                });
+            }
+        }
+    }
+    shapes
+}
+
+// The geometry of a feature normalized to the tile. Invalid geometry only costs its own feature, it is logged and
+// the rest of the tile is still built.
+fn decode_feature(
+    address: &TileAddress,
+    layer: &vector_tile::Tile_Layer,
+    feature: &vector_tile::Tile_Feature,
+    geometry_type: GeometryType,
+) -> Option<Geometry<glm::Vec2>> {
+    let classification = RingClassification::for_version(layer.get_version());
+    match decoder::decode(geometry_type, feature.get_geometry(), classification) {
+        Ok(geometry) => Some(geometry.normalize(layer.get_extent())),
+        Err(e) => {
+            println!(
+                "Skipping feature {} of layer {} in tile {}: {}",
+                feature.get_id(),
+                layer.get_name(),
+                address,
+                e
+            );
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::colors::ColorRule;
+    use super::super::features::GeometryOptions;
+    use super::super::material::Material;
+    use super::super::shader::Shader;
+    use super::super::zoom::ZoomValue;
+    use super::*;
+
+    const EXTENT: i32 = 4096;
+
+    fn buildings() -> FeatureOption {
+        FeatureOption {
+            layer: String::from("building"),
+            material: Material::new(Shader { program_id: 0 }),
+            color: ColorRule::Constant(glm::vec4(1.0, 1.0, 1.0, 1.0)),
+            filter: None,
+            geo_type: vector_tile::Tile_GeomType::POLYGON,
+            geometry: GeometryOptions::Fill {
+                height: ZoomValue::Constant(10.0),
+                min_height: ZoomValue::Constant(0.0),
+                height_property: None,
+                min_height_property: None,
+                walls: true,
+            },
+            texture: None,
+            min_zoom: None,
+            max_zoom: None,
+        }
+    }
+
+    // A tile with one rectangular building for every `(id, x0, y0, x1, y1)`.
+    fn tile(rectangles: &[(u64, i32, i32, i32, i32)]) -> vector_tile::Tile {
+        let zigzag = |n: i32| ((n << 1) ^ (n >> 31)) as u32;
+        let mut layer = vector_tile::Tile_Layer::new();
+        layer.set_name(String::from("building"));
+        layer.set_version(2);
+        layer.set_extent(EXTENT as u32);
+        for &(id, x0, y0, x1, y1) in rectangles {
+            let mut feature = vector_tile::Tile_Feature::new();
+            feature.set_id(id);
+            feature.set_field_type(vector_tile::Tile_GeomType::POLYGON);
+            // MoveTo the north west corner, LineTo the other three clockwise and ClosePath.
+            let geometry = feature.mut_geometry();
+            geometry.extend(&[9, zigzag(x0), zigzag(y0), 26]);
+            geometry.extend(&[
+                zigzag(x1 - x0),
+                0,
+                0,
+                zigzag(y1 - y0),
+                zigzag(x0 - x1),
+                0,
+                15,
+            ]);
+            layer.mut_features().push(feature);
+        }
+        let mut tile = vector_tile::Tile::new();
+        tile.mut_layers().push(layer);
+        tile
+    }
+
+    // The walls standing on the vertical line at `x`, or anywhere with no `x`. Every wall is two triangles.
+    fn walls(built: &BuiltTile, x: Option<f32>) -> usize {
+        let mesh = &built.meshes[0].mesh;
+        mesh.indices
+            .chunks(3)
+            .map(|t| [0, 1, 2].map(|i| mesh.vertices[t[i] as usize]))
+            .filter(|t| t.iter().any(|v| v.y == 0.0))
+            .filter(|t| x.is_none_or(|x| t.iter().all(|v| v.x == x)))
+            .count()
+            / 2
+    }
+
+    #[test]
+    fn buildings_split_across_tiles_have_no_walls_between_the_parts() {
+        let (west, east) = (TileAddress::new(0, 0, 1), TileAddress::new(1, 0, 1));
+        // Building 1 is cut in two by the edge between the tiles. Building 2 ends right at the edge, with nothing
+        // on the other side, and building 3 in the eastern tile starts there without anything of its own beyond.
+        let west_tile = tile(&[(1, 2048, 1024, EXTENT, 2048), (2, 3072, 3072, EXTENT, 3584)]);
+        let east_tile = tile(&[(1, 0, 1024, 1024, 2048), (3, 0, 2560, 512, 2816)]);
+        let options = [buildings()];
+
+        let alone = build_tile(&west_tile, &[None; 4], &options, &west, 20.0);
+        assert_eq!(walls(&alone, Some(1.0)), 2);
+        assert_eq!(alone.edge_walls, [false, true, false, false]);
+
+        let west_built = build_tile(
+            &west_tile,
+            &[None, Some(&east_tile), None, None],
+            &options,
+            &west,
+            20.0,
+        );
+        let east_built = build_tile(
+            &east_tile,
+            &[Some(&west_tile), None, None, None],
+            &options,
+            &east,
+            20.0,
+        );
+        assert_eq!(walls(&west_built, Some(1.0)), 1);
+        assert_eq!(walls(&east_built, Some(0.0)), 1);
+        assert_eq!(west_built.edge_walls, [false, true, false, false]);
+        assert_eq!(east_built.edge_walls, [true, false, false, false]);
+        // Each tile has three of the walls of building 1 and all four of the other building.
+        assert_eq!(walls(&west_built, None), 7);
+        assert_eq!(walls(&east_built, None), 7);
+    }
+}
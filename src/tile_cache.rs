@@ -0,0 +1,417 @@
+use super::error::Error;
+use super::tile_address::TileAddress;
+use super::tile_source::{FetchResult, TileSource};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+pub struct CacheEntry {
+    pub data: Vec<u8>,
+    pub fetched_at: u64,
+    pub etag: Option<String>,
+}
+
+// Stores raw tile payloads on disk as `{root}/{source hash}/{z}/{x}/{y}.pbf`, with the fetch time and ETag kept
+// next to it in `{y}.meta`.
+pub struct TileCache {
+    root: PathBuf,
+}
+
+impl TileCache {
+    pub fn new<P: AsRef<Path>>(root: P) -> Self {
+        TileCache {
+            root: root.as_ref().to_path_buf(),
+        }
+    }
+
+    fn tile_dir(&self, source: &str, address: &TileAddress) -> PathBuf {
+        // Source names contain characters that are not valid in file names on every platform, so the directory is
+        // named after a hash of the whole name instead.
+        self.root
+            .join(format!("{:016x}", source_hash(source)))
+            .join(address.z.to_string())
+            .join(address.x.to_string())
+    }
+
+    pub fn get(&self, source: &str, address: &TileAddress) -> Option<CacheEntry> {
+        let dir = self.tile_dir(source, address);
+        let data = std::fs::read(dir.join(format!("{}.pbf", address.y))).ok()?;
+        let meta = std::fs::read_to_string(dir.join(format!("{}.meta", address.y))).ok()?;
+
+        let mut fetched_at = 0;
+        let mut etag = None;
+        for line in meta.lines() {
+            if let Some(value) = line.strip_prefix("fetched_at=") {
+                fetched_at = value.parse::<u64>().unwrap_or(0);
+            } else if let Some(value) = line.strip_prefix("etag=") {
+                etag = Some(String::from(value));
+            }
+        }
+
+        Some(CacheEntry {
+            data,
+            fetched_at,
+            etag,
+        })
+    }
+
+    pub fn put(
+        &self,
+        source: &str,
+        address: &TileAddress,
+        data: &[u8],
+        etag: Option<&str>,
+    ) -> Result<(), Error> {
+        let dir = self.tile_dir(source, address);
+        std::fs::create_dir_all(&dir).map_err(|e| cache_error(&dir, e))?;
+        let path = dir.join(format!("{}.pbf", address.y));
+        write_atomically(&path, data)?;
+        self.touch(source, address, etag)
+    }
+
+    // Marks an entry as freshly fetched without rewriting its payload.
+    pub fn touch(&self, source: &str, address: &TileAddress, etag: Option<&str>) -> Result<(), Error> {
+        let dir = self.tile_dir(source, address);
+        let mut meta = format!("fetched_at={}\n", now_seconds());
+        if let Some(etag) = etag {
+            meta.push_str(&format!("etag={}\n", etag));
+        }
+        let path = dir.join(format!("{}.meta", address.y));
+        write_atomically(&path, meta.as_bytes())
+    }
+}
+
+// 64-bit FNV-1a. Unlike `DefaultHasher` its output is fixed, so the cache stays valid across builds.
+fn source_hash(source: &str) -> u64 {
+    source.bytes().fold(0xcbf29ce484222325, |hash, byte| {
+        (hash ^ byte as u64).wrapping_mul(0x100000001b3)
+    })
+}
+
+// Writes to a temporary file next to `path` and renames it into place, so a crash or a second process reading the
+// cache never sees a half written entry. Temporary names are unique per write, as workers of one process can store
+// the same tile at once.
+fn write_atomically(path: &Path, data: &[u8]) -> Result<(), Error> {
+    static WRITES: AtomicUsize = AtomicUsize::new(0);
+    let mut temp_name = path.file_name().unwrap_or_default().to_os_string();
+    temp_name.push(format!(
+        ".{}.{}.tmp",
+        std::process::id(),
+        WRITES.fetch_add(1, Ordering::Relaxed)
+    ));
+    let temp_path = path.with_file_name(temp_name);
+    std::fs::write(&temp_path, data).map_err(|e| cache_error(&temp_path, e))?;
+    std::fs::rename(&temp_path, path).map_err(|e| {
+        let _ = std::fs::remove_file(&temp_path);
+        cache_error(path, e)
+    })
+}
+
+// Wraps another tile source, serving tiles from the cache while they are younger than `max_age` and
+// revalidating stale ones with their ETag. In offline mode only the cache is used.
+pub struct CachedSource {
+    source: Box<dyn TileSource>,
+    cache: TileCache,
+    max_age: Duration,
+    offline: bool,
+}
+
+impl CachedSource {
+    pub fn new(source: Box<dyn TileSource>, cache: TileCache, max_age: Duration, offline: bool) -> Self {
+        CachedSource {
+            source,
+            cache,
+            max_age,
+            offline,
+        }
+    }
+}
+
+impl TileSource for CachedSource {
+    fn fetch(&self, address: &TileAddress) -> Result<Vec<u8>, Error> {
+        let source = self.source.name();
+        let entry = self.cache.get(&source, address);
+
+        if self.offline {
+            return match entry {
+                Some(entry) => Ok(entry.data),
+                None => Err(Error::Fetch {
+                    address: *address,
+                    message: String::from("not in the cache (offline)"),
+                }),
+            };
+        }
+
+        if let Some(entry) = &entry {
+            let age = now_seconds().saturating_sub(entry.fetched_at);
+            if age < self.max_age.as_secs() {
+                return Ok(entry.data.clone());
+            }
+        }
+
+        let etag = entry.as_ref().and_then(|entry| entry.etag.as_deref());
+        match self.source.fetch_conditional(address, etag) {
+            Ok(FetchResult::Data { bytes, etag }) => {
+                if let Err(e) = self.cache.put(&source, address, &bytes, etag.as_deref()) {
+                    eprintln!("Failed to cache tile: {}", e);
+                }
+                Ok(bytes)
+            }
+            Ok(FetchResult::NotModified) => match entry {
+                Some(entry) => {
+                    if let Err(e) = self.cache.touch(&source, address, entry.etag.as_deref()) {
+                        eprintln!("Failed to cache tile: {}", e);
+                    }
+                    Ok(entry.data)
+                }
+                None => Err(Error::Fetch {
+                    address: *address,
+                    message: String::from("not modified but missing from the cache"),
+                }),
+            },
+            // A stale tile is better than no tile.
+            Err(e) => match entry {
+                Some(entry) => {
+                    eprintln!("Using stale cached tile: {}", e);
+                    Ok(entry.data)
+                }
+                None => Err(e),
+            },
+        }
+    }
+
+    fn name(&self) -> String {
+        self.source.name()
+    }
+}
+
+fn cache_error(path: &Path, e: std::io::Error) -> Error {
+    Error::Cache {
+        path: path.display().to_string(),
+        message: e.to_string(),
+    }
+}
+
+fn now_seconds() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{Arc, Mutex};
+
+    const SOURCE: &str = "http:https://example.com/{z}/{x}/{y}.pbf";
+    const ADDRESS: TileAddress = TileAddress {
+        x: 8800,
+        y: 5400,
+        z: 14,
+    };
+
+    // What the fake server has for every tile, as a payload and its ETag, or `None` while the network is down.
+    // Every request is recorded with the ETag it was sent with.
+    #[derive(Default)]
+    struct Server {
+        tile: Mutex<Option<(Vec<u8>, String)>>,
+        requests: Mutex<Vec<Option<String>>>,
+    }
+
+    impl Server {
+        fn serve(&self, data: &[u8], etag: &str) {
+            *self.tile.lock().unwrap() = Some((data.to_vec(), String::from(etag)));
+        }
+
+        fn go_down(&self) {
+            *self.tile.lock().unwrap() = None;
+        }
+
+        fn requests(&self) -> Vec<Option<String>> {
+            self.requests.lock().unwrap().clone()
+        }
+    }
+
+    struct FakeSource(Arc<Server>);
+
+    impl TileSource for FakeSource {
+        fn fetch(&self, address: &TileAddress) -> Result<Vec<u8>, Error> {
+            match self.fetch_conditional(address, None)? {
+                FetchResult::Data { bytes, .. } => Ok(bytes),
+                FetchResult::NotModified => unreachable!(),
+            }
+        }
+
+        fn name(&self) -> String {
+            String::from(SOURCE)
+        }
+
+        fn fetch_conditional(
+            &self,
+            address: &TileAddress,
+            etag: Option<&str>,
+        ) -> Result<FetchResult, Error> {
+            self.0.requests.lock().unwrap().push(etag.map(String::from));
+            match &*self.0.tile.lock().unwrap() {
+                None => Err(Error::Fetch {
+                    address: *address,
+                    message: String::from("network is down"),
+                }),
+                Some((_, current)) if etag == Some(current.as_str()) => {
+                    Ok(FetchResult::NotModified)
+                }
+                Some((bytes, current)) => Ok(FetchResult::Data {
+                    bytes: bytes.clone(),
+                    etag: Some(current.clone()),
+                }),
+            }
+        }
+    }
+
+    fn cached_source(root: &Path, server: &Arc<Server>, offline: bool) -> CachedSource {
+        CachedSource::new(
+            Box::new(FakeSource(server.clone())),
+            TileCache::new(root),
+            Duration::from_secs(60 * 60),
+            offline,
+        )
+    }
+
+    // Makes the cached entry older than any maximum age, keeping its ETag.
+    fn expire(root: &Path) {
+        let cache = TileCache::new(root);
+        let etag = cache.get(SOURCE, &ADDRESS).unwrap().etag;
+        let mut meta = String::from("fetched_at=1\n");
+        if let Some(etag) = etag {
+            meta.push_str(&format!("etag={}\n", etag));
+        }
+        let dir = cache.tile_dir(SOURCE, &ADDRESS);
+        std::fs::write(dir.join(format!("{}.meta", ADDRESS.y)), meta).unwrap();
+    }
+
+    #[test]
+    fn fresh_tiles_are_served_from_the_cache() {
+        let root = tempfile::tempdir().unwrap();
+        let server = Arc::new(Server::default());
+        server.serve(b"v1", "\"1\"");
+        let source = cached_source(root.path(), &server, false);
+
+        assert_eq!(source.fetch(&ADDRESS).unwrap(), b"v1");
+        server.serve(b"v2", "\"2\"");
+        assert_eq!(source.fetch(&ADDRESS).unwrap(), b"v1");
+        assert_eq!(server.requests(), vec![None]);
+
+        let entry = TileCache::new(root.path()).get(SOURCE, &ADDRESS).unwrap();
+        assert_eq!(entry.data, b"v1");
+        assert_eq!(entry.etag.as_deref(), Some("\"1\""));
+    }
+
+    #[test]
+    fn expired_tiles_are_revalidated_with_their_etag() {
+        let root = tempfile::tempdir().unwrap();
+        let server = Arc::new(Server::default());
+        server.serve(b"v1", "\"1\"");
+        let source = cached_source(root.path(), &server, false);
+        source.fetch(&ADDRESS).unwrap();
+
+        // Unchanged on the server: the cached payload is kept and counts as fresh again.
+        expire(root.path());
+        assert_eq!(source.fetch(&ADDRESS).unwrap(), b"v1");
+        assert_eq!(server.requests(), vec![None, Some(String::from("\"1\""))]);
+        let entry = TileCache::new(root.path()).get(SOURCE, &ADDRESS).unwrap();
+        assert!(entry.fetched_at > 1);
+        assert_eq!(entry.etag.as_deref(), Some("\"1\""));
+        assert_eq!(source.fetch(&ADDRESS).unwrap(), b"v1");
+        assert_eq!(server.requests().len(), 2);
+
+        // Changed on the server: the new payload replaces the cached one.
+        server.serve(b"v2", "\"2\"");
+        expire(root.path());
+        assert_eq!(source.fetch(&ADDRESS).unwrap(), b"v2");
+        let entry = TileCache::new(root.path()).get(SOURCE, &ADDRESS).unwrap();
+        assert_eq!(entry.data, b"v2");
+        assert_eq!(entry.etag.as_deref(), Some("\"2\""));
+    }
+
+    #[test]
+    fn network_errors_fall_back_to_stale_tiles() {
+        let root = tempfile::tempdir().unwrap();
+        let server = Arc::new(Server::default());
+        let source = cached_source(root.path(), &server, false);
+
+        // Nothing to fall back to yet.
+        match source.fetch(&ADDRESS) {
+            Err(Error::Fetch { message, .. }) => assert_eq!(message, "network is down"),
+            other => panic!("unexpected {:?}", other),
+        }
+
+        server.serve(b"v1", "\"1\"");
+        source.fetch(&ADDRESS).unwrap();
+        server.go_down();
+        expire(root.path());
+        assert_eq!(source.fetch(&ADDRESS).unwrap(), b"v1");
+        assert_eq!(server.requests().len(), 3);
+    }
+
+    #[test]
+    fn offline_sources_never_touch_the_network() {
+        let root = tempfile::tempdir().unwrap();
+        let server = Arc::new(Server::default());
+        server.serve(b"v1", "\"1\"");
+
+        let offline = cached_source(root.path(), &server, true);
+        match offline.fetch(&ADDRESS) {
+            Err(Error::Fetch { address, message }) => {
+                assert_eq!(address, ADDRESS);
+                assert_eq!(message, "not in the cache (offline)");
+            }
+            other => panic!("unexpected {:?}", other),
+        }
+
+        cached_source(root.path(), &server, false)
+            .fetch(&ADDRESS)
+            .unwrap();
+        expire(root.path());
+        server.serve(b"v2", "\"2\"");
+        // Even expired entries are used as they are.
+        assert_eq!(offline.fetch(&ADDRESS).unwrap(), b"v1");
+        assert_eq!(server.requests(), vec![None]);
+    }
+
+    #[test]
+    fn sources_get_their_own_directories() {
+        let cache = TileCache::new("cache");
+        let dir = cache.tile_dir("http:a/b", &ADDRESS);
+        assert_ne!(dir, cache.tile_dir("http:a_b", &ADDRESS));
+        assert_ne!(dir, cache.tile_dir("dir:a/b", &ADDRESS));
+        assert_eq!(dir, cache.tile_dir("http:a/b", &ADDRESS));
+        assert!(dir.ends_with("14/8800"));
+    }
+
+    #[test]
+    fn concurrent_writes_leave_no_temporary_files() {
+        let root = tempfile::tempdir().unwrap();
+        let cache = Arc::new(TileCache::new(root.path()));
+        let writers: Vec<_> = (0..8u8)
+            .map(|i| {
+                let cache = cache.clone();
+                std::thread::spawn(move || cache.put(SOURCE, &ADDRESS, &[i; 1024], None))
+            })
+            .collect();
+        for writer in writers {
+            writer.join().unwrap().unwrap();
+        }
+
+        let data = cache.get(SOURCE, &ADDRESS).unwrap().data;
+        assert_eq!(data.len(), 1024);
+        assert!(data.iter().all(|byte| *byte == data[0]));
+        let dir = cache.tile_dir(SOURCE, &ADDRESS);
+        let mut names: Vec<String> = std::fs::read_dir(dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+            .collect();
+        names.sort();
+        assert_eq!(names, vec!["5400.meta", "5400.pbf"]);
+    }
+}
@@ -0,0 +1,749 @@
+use super::features::FeatureOption;
+use super::mesh;
+use super::model;
+use super::scene_graph::{Node, NodeType, SceneNode};
+use super::tile_address::TileAddress;
+use super::tile_builder::{self, FeatureMesh};
+use super::tile_worker::TileWorkerPool;
+use std::collections::{HashMap, HashSet};
+use std::mem::ManuallyDrop;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+struct LoadedTile {
+    group: Node,
+    models: Vec<Node>,
+    // The column the tile is placed at, in origin tiles from the origin. See `tile_offset`.
+    offset_x: i64,
+    // See `BuiltTile::edge_walls` and `TileResult::neighbours`.
+    edge_walls: [bool; 4],
+    neighbours: [bool; 4],
+}
+
+// A tile that could not be loaded. It is requested again once `retry_at` has passed, waiting twice as long after
+// every further failure.
+struct FailedTile {
+    attempts: u32,
+    retry_at: Instant,
+}
+
+// Keeps a quadtree of tiles around the camera loaded, picking finer tiles close to the camera and coarser ones
+// further away, see `TileGrid`.
+pub struct TileManager {
+    grid: TileGrid,
+    max_uploads_per_frame: usize,
+    workers: TileWorkerPool,
+    options: Arc<Vec<FeatureOption>>,
+    loaded: HashMap<TileAddress, LoadedTile>,
+    pending: HashSet<TileAddress>,
+    retry_delay: Duration,
+    max_retry_delay: Duration,
+    failed: HashMap<TileAddress, FailedTile>,
+}
+
+// Where tiles are placed and which of them the camera needs. The roots are the tiles within `radius` of the camera
+// at `levels` zooms below `origin`, and are split down to the origin zoom. Tiles are placed relative to `origin`,
+// one origin tile spanning `scale` units, which is the width in metres of the tile the view started on. The origin
+// follows the camera, see `rebase`, while the scale stays, as the tiles are built to it.
+struct TileGrid {
+    origin: TileAddress,
+    radius: u32,
+    levels: u32,
+    scale: f64,
+    rebase_distance: i64,
+    lod_factor: f32,
+}
+
+impl TileManager {
+    pub fn new(
+        origin: TileAddress,
+        radius: u32,
+        levels: u32,
+        scale: f64,
+        workers: TileWorkerPool,
+        options: Arc<Vec<FeatureOption>>,
+    ) -> TileManager {
+        TileManager {
+            grid: TileGrid::new(origin, radius, levels, scale),
+            max_uploads_per_frame: 4,
+            workers,
+            options,
+            loaded: HashMap::new(),
+            pending: HashSet::new(),
+            retry_delay: Duration::from_secs(2),
+            max_retry_delay: Duration::from_secs(5 * 60),
+            failed: HashMap::new(),
+        }
+    }
+
+    // The extent of everything that gets loaded, in tiles at the origin zoom from the centre tile.
+    pub fn view_radius(&self) -> u32 {
+        (self.grid.radius + 1) << self.grid.levels
+    }
+
+    // Call once per frame with the camera position. Must run on the thread owning the GL context.
+    pub fn update(&mut self, position: &glm::Vec3, root: &mut SceneNode) {
+        let selected = self.grid.select_tiles(position);
+        let selected_set: HashSet<TileAddress> = selected.iter().cloned().collect();
+
+        // Failures of tiles the camera has left are forgotten once they are due, they start over when selected again.
+        let now = Instant::now();
+        self.failed
+            .retain(|address, failed| failed.retry_at > now || selected_set.contains(address));
+
+        // Requests for tiles the camera has moved away from are dropped unless a worker is already on them.
+        let stale: Vec<TileAddress> = self
+            .pending
+            .iter()
+            .filter(|address| !selected_set.contains(address))
+            .cloned()
+            .collect();
+        for address in stale {
+            if self.workers.cancel(&address) {
+                self.pending.remove(&address);
+            }
+        }
+
+        for address in &selected {
+            let waiting = match self.failed.get(address) {
+                Some(failed) => failed.retry_at > now,
+                None => false,
+            };
+            if !self.loaded.contains_key(address) && !self.pending.contains(address) && !waiting {
+                self.pending.insert(*address);
+                self.workers.request(*address);
+            }
+        }
+
+        // Uploading is the only part that has to happen here, but it is still limited per frame to avoid stutter.
+        for _ in 0..self.max_uploads_per_frame {
+            let result = match self.workers.try_receive() {
+                Some(result) => result,
+                None => break,
+            };
+            self.pending.remove(&result.address);
+
+            match result.tile {
+                Ok(built) => {
+                    self.failed.remove(&result.address);
+                    // The camera may have moved on while the tile was being built. A tile that is still shown
+                    // keeps what it was built with before.
+                    if !selected_set.contains(&result.address) {
+                        if !self.loaded.contains_key(&result.address) {
+                            self.workers.forget(&result.address);
+                        }
+                        continue;
+                    }
+                    let mut tile = self.upload_tile(&result.address, built.meshes, position, root);
+                    tile.edge_walls = built.edge_walls;
+                    tile.neighbours = result.neighbours;
+                    if let Some(previous) = self.loaded.insert(result.address, tile) {
+                        unload_tile(previous, root);
+                    }
+                    self.rebuild_around(&result.address);
+                }
+                Err(e) => {
+                    self.workers.forget(&result.address);
+                    let attempts = match self.failed.get(&result.address) {
+                        Some(failed) => failed.attempts + 1,
+                        None => 1,
+                    };
+                    let delay = (self.retry_delay * 2u32.pow(attempts.min(16) - 1))
+                        .min(self.max_retry_delay);
+                    println!("Skipping {}, retrying in {}s", e, delay.as_secs());
+                    self.failed.insert(
+                        result.address,
+                        FailedTile {
+                            attempts,
+                            retry_at: Instant::now() + delay,
+                        },
+                    );
+                }
+            }
+        }
+
+        let loaded: HashSet<TileAddress> = self.loaded.keys().cloned().collect();
+        let failed: HashSet<TileAddress> = self.failed.keys().cloned().collect();
+        let visible = visible_tiles(&selected, &loaded, &failed);
+        let to_unload: Vec<TileAddress> = self
+            .loaded
+            .keys()
+            .filter(|address| !selected_set.contains(address) && !visible.contains(address))
+            .cloned()
+            .collect();
+        for address in to_unload {
+            if let Some(tile) = self.loaded.remove(&address) {
+                unload_tile(tile, root);
+            }
+            self.workers.forget(&address);
+        }
+
+        // Tiles jump to the nearest copy of the world once the camera has travelled far enough around it.
+        let moved: Vec<(TileAddress, (i64, i64))> = self
+            .loaded
+            .iter()
+            .map(|(address, tile)| (address, tile, self.grid.tile_offset(address, position)))
+            .filter(|(_, tile, offset)| offset.0 != tile.offset_x)
+            .map(|(address, _, offset)| (*address, offset))
+            .collect();
+        for (address, offset) in moved {
+            self.place_tile(&address, offset);
+        }
+
+        for (address, tile) in self.loaded.iter_mut() {
+            tile.group.disabled = !visible.contains(address);
+        }
+    }
+
+    // Whether requested tiles are still being built.
+    pub fn is_loading(&self) -> bool {
+        !self.pending.is_empty()
+    }
+
+    // Moves the origin to the tile under `position` once the camera is more than `rebase_distance` tiles away,
+    // so the geometry around the camera stays close to zero where f32 is precise. Returns how far the world moved,
+    // which has to be subtracted from everything else placed in it.
+    pub fn rebase(&mut self, position: &glm::Vec3) -> Option<glm::Vec3> {
+        let shift = self.grid.rebase(position)?;
+        let position = position - shift;
+        let placed: Vec<(TileAddress, (i64, i64))> = self
+            .loaded
+            .keys()
+            .map(|address| (*address, self.grid.tile_offset(address, &position)))
+            .collect();
+        for (address, offset) in placed {
+            self.place_tile(&address, offset);
+        }
+        Some(shift)
+    }
+
+    // Tiles are built with the neighbouring tiles that were loaded at the time. Once a tile has arrived, it and its
+    // neighbours are built again wherever one of them has walls along the side they share that were built without
+    // the other, as the other may show those walls to be where a building was cut.
+    fn rebuild_around(&mut self, address: &TileAddress) {
+        let mut rebuild = vec![];
+        for (side, neighbour) in tile_builder::side_neighbours(address).iter().enumerate() {
+            let neighbour = match neighbour {
+                Some(neighbour) => *neighbour,
+                None => continue,
+            };
+            let (tile, other) = match (self.loaded.get(address), self.loaded.get(&neighbour)) {
+                (Some(tile), Some(other)) => (tile, other),
+                _ => continue,
+            };
+            // Sides 2n and 2n + 1 face each other.
+            let opposite = side ^ 1;
+            if tile.edge_walls[side] && !tile.neighbours[side] {
+                rebuild.push(*address);
+            }
+            if other.edge_walls[opposite] && !other.neighbours[opposite] {
+                rebuild.push(neighbour);
+            }
+        }
+        for address in rebuild {
+            if self.pending.insert(address) {
+                self.workers.request(address);
+            }
+        }
+    }
+
+    fn place_tile(&mut self, address: &TileAddress, offset: (i64, i64)) {
+        let transform = self.grid.tile_transform(address, offset);
+        let tile = match self.loaded.get_mut(address) {
+            Some(tile) => tile,
+            None => return,
+        };
+        for node in &mut tile.models {
+            if let NodeType::Model(model) = &mut node.value {
+                model.set_transform(transform);
+            }
+        }
+        tile.offset_x = offset.0;
+    }
+
+    fn upload_tile(
+        &self,
+        address: &TileAddress,
+        feature_meshes: Vec<FeatureMesh>,
+        position: &glm::Vec3,
+        root: &mut SceneNode,
+    ) -> LoadedTile {
+        let offset = self.grid.tile_offset(address, position);
+        let tile_transform = self.grid.tile_transform(address, offset);
+        let mut group = SceneNode::new(NodeType::None);
+        let mut models = vec![];
+
+        for feature_mesh in feature_meshes {
+            let option = &self.options[feature_mesh.option_index];
+            let m = feature_mesh.mesh;
+            let mut mesh = mesh::Mesh::new_full(m.vertices, m.indices, m.normals, m.uvs);
+            mesh.set_colors(m.colors);
+
+            if let Some(texture) = &option.texture {
+                // The tile is still worth showing without its texture.
+                if let Err(e) = mesh.set_texture(texture.as_str()) {
+                    println!("{}", e);
+                }
+            }
+
+            let model = model::Model::new(
+                option.layer.clone(),
+                vec![mesh],
+                option.material.clone(),
+                tile_transform,
+                Some(Box::new(|transform, mat, s| {
+                    mat.set_vec3(
+                        "u_viewPos",
+                        s.camera_position.x,
+                        s.camera_position.y,
+                        s.camera_position.z,
+                    );
+                    *transform
+                })),
+            );
+            let model_node = SceneNode::new_model(model);
+            group.add_child(&model_node);
+            models.push(model_node);
+        }
+
+        root.add_child(&group);
+        LoadedTile {
+            group,
+            models,
+            offset_x: offset.0,
+            edge_walls: [false; 4],
+            neighbours: [false; 4],
+        }
+    }
+}
+
+impl TileGrid {
+    fn new(origin: TileAddress, radius: u32, levels: u32, scale: f64) -> TileGrid {
+        TileGrid {
+            origin,
+            radius,
+            levels: levels.min(origin.z),
+            scale,
+            rebase_distance: 4,
+            lod_factor: 2.0,
+        }
+    }
+
+    // The tile underneath a world space position. Positions past the antimeridian wrap around to the other side
+    // of the world.
+    fn tile_at(&self, position: &glm::Vec3) -> TileAddress {
+        let size = 1i64 << self.origin.z;
+        let offset_x = (position.x as f64 / self.scale).floor() as i64;
+        let offset_y = (position.z as f64 / self.scale).floor() as i64;
+        let x = (self.origin.x as i64 + offset_x).rem_euclid(size);
+        let y = (self.origin.y as i64 + offset_y).max(0).min(size - 1);
+        TileAddress::new(x as u32, y as u32, self.origin.z)
+    }
+
+    // The north west corner of the tile in origin tiles from the origin. The world repeats every `2^zoom` columns,
+    // so tiles are placed at the copy closest to `position` to line up with their neighbours across the
+    // antimeridian.
+    fn tile_offset(&self, address: &TileAddress, position: &glm::Vec3) -> (i64, i64) {
+        let span = 1i64 << (self.origin.z - address.z);
+        let world = 1i64 << self.origin.z;
+        let offset_x = address.x as i64 * span - self.origin.x as i64;
+        let offset_y = address.y as i64 * span - self.origin.y as i64;
+
+        let center_x = offset_x as f64 + span as f64 * 0.5;
+        let position_x = position.x as f64 / self.scale;
+        let copies = ((position_x - center_x) / world as f64).round() as i64;
+        (offset_x + copies * world, offset_y)
+    }
+
+    // Tile meshes are built in fractions of the tile width on every axis, see `tile_builder::build_tile`.
+    fn tile_transform(&self, address: &TileAddress, offset: (i64, i64)) -> glm::Mat4 {
+        let span = 1i64 << (self.origin.z - address.z);
+        let size = (span as f64 * self.scale) as f32;
+
+        // The offsets are exact, only the final position relative to the origin is rounded to f32.
+        let translation = glm::translate(
+            &glm::identity(),
+            &glm::vec3(
+                (offset.0 as f64 * self.scale) as f32,
+                0.0,
+                (offset.1 as f64 * self.scale) as f32,
+            ),
+        );
+        translation * glm::scale(&glm::identity(), &glm::vec3(size, size, size))
+    }
+
+    // The distance from `position` to the closest point of the tile on the ground.
+    fn tile_distance(&self, address: &TileAddress, position: &glm::Vec3) -> f32 {
+        let span = 1i64 << (self.origin.z - address.z);
+        let (offset_x, offset_y) = self.tile_offset(address, position);
+        let min_x = (offset_x as f64 * self.scale) as f32;
+        let min_z = (offset_y as f64 * self.scale) as f32;
+        let size = (span as f64 * self.scale) as f32;
+        let closest = glm::vec3(
+            position.x.max(min_x).min(min_x + size),
+            0.0,
+            position.z.max(min_z).min(min_z + size),
+        );
+        glm::distance(&closest, position)
+    }
+
+    // The leaves of the quadtree for a camera at `position`, closest first. A tile is split into its children
+    // while the camera is closer to it than `lod_factor` times its size, so every tile covers about the same
+    // part of the screen.
+    fn select_tiles(&self, position: &glm::Vec3) -> Vec<TileAddress> {
+        let root = self.tile_at(position).ancestor(self.origin.z - self.levels);
+        let mut stack = root.get_tiles(self.radius);
+        let mut selected = vec![];
+        while let Some(address) = stack.pop() {
+            let size = ((1u64 << (self.origin.z - address.z)) as f64 * self.scale) as f32;
+            if address.z < self.origin.z
+                && self.tile_distance(&address, position) < self.lod_factor * size
+            {
+                stack.extend(address.children().iter());
+            } else {
+                selected.push(address);
+            }
+        }
+
+        let mut distances: Vec<(f32, TileAddress)> = selected
+            .into_iter()
+            .map(|address| (self.tile_distance(&address, position), address))
+            .collect();
+        distances.sort_by(|a, b| a.0.total_cmp(&b.0));
+        distances.into_iter().map(|(_, address)| address).collect()
+    }
+
+    // Moves the origin to the tile under `position` once the camera is more than `rebase_distance` tiles away.
+    // Returns how far the world moved.
+    fn rebase(&mut self, position: &glm::Vec3) -> Option<glm::Vec3> {
+        let offset_x = (position.x as f64 / self.scale).floor() as i64;
+        let offset_y = (position.z as f64 / self.scale).floor() as i64;
+        if offset_x.abs().max(offset_y.abs()) <= self.rebase_distance {
+            return None;
+        }
+
+        let size = 1i64 << self.origin.z;
+        let y = (self.origin.y as i64 + offset_y).max(0).min(size - 1);
+        let offset_y = y - self.origin.y as i64;
+        if offset_x == 0 && offset_y == 0 {
+            return None;
+        }
+        let x = (self.origin.x as i64 + offset_x).rem_euclid(size);
+        self.origin = TileAddress::new(x as u32, y as u32, self.origin.z);
+
+        Some(glm::vec3(
+            (offset_x as f64 * self.scale) as f32,
+            0.0,
+            (offset_y as f64 * self.scale) as f32,
+        ))
+    }
+}
+
+// The loaded tiles to draw for `selected`. Selected tiles that are still loading are stood in for by their closest
+// loaded ancestor, or when there is none or the tile failed to load by their loaded descendants, so nothing
+// disappears while the replacements load. Tiles covered by a drawn ancestor are hidden to avoid overlapping geometry.
+fn visible_tiles(
+    selected: &[TileAddress],
+    loaded: &HashSet<TileAddress>,
+    failed: &HashSet<TileAddress>,
+) -> HashSet<TileAddress> {
+    let mut visible = HashSet::new();
+    for address in selected {
+        if loaded.contains(address) {
+            visible.insert(*address);
+            continue;
+        }
+
+        let ancestor = if failed.contains(address) {
+            None
+        } else {
+            loaded_ancestor(address, loaded)
+        };
+        match ancestor {
+            Some(ancestor) => {
+                visible.insert(ancestor);
+            }
+            None => visible.extend(
+                loaded
+                    .iter()
+                    .filter(|tile| tile.z > address.z && tile.ancestor(address.z) == *address),
+            ),
+        }
+    }
+
+    let covered: Vec<TileAddress> = visible
+        .iter()
+        .filter(|address| has_ancestor_in(address, &visible))
+        .cloned()
+        .collect();
+    for address in covered {
+        visible.remove(&address);
+    }
+    visible
+}
+
+fn loaded_ancestor(address: &TileAddress, loaded: &HashSet<TileAddress>) -> Option<TileAddress> {
+    let mut ancestor = address.parent();
+    while let Some(parent) = ancestor {
+        if loaded.contains(&parent) {
+            return Some(parent);
+        }
+        ancestor = parent.parent();
+    }
+    None
+}
+
+fn unload_tile(mut tile: LoadedTile, root: &mut SceneNode) {
+    root.remove_child(&tile.group);
+    for mut node in tile.models.drain(..) {
+        if let NodeType::Model(model) = &mut node.value {
+            for mesh in &mut model.meshes {
+                unsafe { mesh.delete() };
+            }
+        }
+        unsafe { ManuallyDrop::drop(&mut node) };
+    }
+    unsafe { ManuallyDrop::drop(&mut tile.group) };
+}
+
+fn has_ancestor_in(address: &TileAddress, tiles: &HashSet<TileAddress>) -> bool {
+    let mut ancestor = address.parent();
+    while let Some(parent) = ancestor {
+        if tiles.contains(&parent) {
+            return true;
+        }
+        ancestor = parent.parent();
+    }
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ORIGIN: TileAddress = TileAddress {
+        x: 8800,
+        y: 5400,
+        z: 14,
+    };
+
+    fn grid() -> TileGrid {
+        TileGrid::new(ORIGIN, 3, 2, 100.0)
+    }
+
+    fn tiles(addresses: &[(u32, u32, u32)]) -> HashSet<TileAddress> {
+        addresses
+            .iter()
+            .map(|(x, y, z)| TileAddress::new(*x, *y, *z))
+            .collect()
+    }
+
+    #[test]
+    fn tiles_get_coarser_away_from_the_camera() {
+        let grid = grid();
+        let position = glm::vec3(50.0, 10.0, 50.0);
+        let selected = grid.select_tiles(&position);
+
+        // The camera's own tile is at full detail and comes first.
+        assert_eq!(selected[0], ORIGIN);
+        let zooms: HashSet<u32> = selected.iter().map(|address| address.z).collect();
+        assert_eq!(zooms, [12, 13, 14].iter().cloned().collect());
+
+        for address in &selected {
+            // Every tile was split exactly as far as its distance asks for: coarser ones are too far to split,
+            // and their parents were close enough to be split.
+            let distance = grid.tile_distance(address, &position);
+            let size = |z: u32| ((1u64 << (ORIGIN.z - z)) as f64 * grid.scale) as f32;
+            if address.z < ORIGIN.z {
+                assert!(
+                    distance >= grid.lod_factor * size(address.z),
+                    "{:?}",
+                    address
+                );
+            }
+            if address.z > ORIGIN.z - grid.levels {
+                let parent = address.parent().unwrap();
+                assert!(grid.tile_distance(&parent, &position) < grid.lod_factor * size(parent.z));
+            }
+        }
+        assert!(selected
+            .windows(2)
+            .all(|pair| grid.tile_distance(&pair[0], &position)
+                <= grid.tile_distance(&pair[1], &position)));
+
+        // Leaves never overlap, and together they cover the 7x7 roots around the camera.
+        let set: HashSet<TileAddress> = selected.iter().cloned().collect();
+        assert_eq!(set.len(), selected.len());
+        assert!(selected
+            .iter()
+            .all(|address| !has_ancestor_in(address, &set)));
+        let area: u64 = selected
+            .iter()
+            .map(|a| 1u64 << (2 * (ORIGIN.z - a.z)))
+            .sum();
+        assert_eq!(area, 49 * 16);
+    }
+
+    #[test]
+    fn cameras_high_above_keep_the_roots() {
+        let grid = grid();
+        let selected = grid.select_tiles(&glm::vec3(50.0, 100_000.0, 50.0));
+        assert_eq!(selected.len(), 49);
+        assert!(selected.iter().all(|address| address.z == 12));
+        assert_eq!(selected[0], ORIGIN.ancestor(12));
+    }
+
+    #[test]
+    fn selection_survives_a_nan_position() {
+        let grid = grid();
+        let selected = grid.select_tiles(&glm::vec3(f32::NAN, 10.0, 50.0));
+        assert!(!selected.is_empty());
+    }
+
+    #[test]
+    fn tiles_across_the_antimeridian_are_placed_next_to_the_camera() {
+        let camera = glm::vec3(50.0, 10.0, 50.0);
+
+        // Looking east from the last column, the first column of the world is the next one over.
+        let east = TileGrid::new(TileAddress::new(7, 3, 3), 1, 0, 100.0);
+        assert_eq!(
+            east.tile_offset(&TileAddress::new(0, 3, 3), &camera),
+            (1, 0)
+        );
+        assert_eq!(
+            east.tile_offset(&TileAddress::new(6, 4, 3), &camera),
+            (-1, 1)
+        );
+
+        // And looking west from the first column, the last one is.
+        let west = TileGrid::new(TileAddress::new(0, 3, 3), 1, 0, 100.0);
+        assert_eq!(
+            west.tile_offset(&TileAddress::new(7, 2, 3), &camera),
+            (-1, -1)
+        );
+        assert_eq!(
+            west.tile_offset(&TileAddress::new(1, 3, 3), &camera),
+            (1, 0)
+        );
+        assert_eq!(
+            west.tile_offset(&TileAddress::new(3, 1, 2), &camera),
+            (-2, -1)
+        );
+        assert_eq!(
+            west.tile_at(&glm::vec3(-50.0, 0.0, 50.0)),
+            TileAddress::new(7, 3, 3)
+        );
+
+        // A camera that travelled once around the world sees the copies next to it.
+        let around = glm::vec3(850.0, 10.0, 50.0);
+        assert_eq!(
+            west.tile_offset(&TileAddress::new(0, 3, 3), &around),
+            (8, 0)
+        );
+        assert_eq!(
+            west.tile_offset(&TileAddress::new(7, 3, 3), &around),
+            (7, 0)
+        );
+        assert_eq!(west.tile_at(&around), TileAddress::new(0, 3, 3));
+    }
+
+    // Where the north west corner of a tile ends up in the world.
+    fn corner(grid: &TileGrid, address: &TileAddress, position: &glm::Vec3) -> glm::Vec3 {
+        let transform = grid.tile_transform(address, grid.tile_offset(address, position));
+        glm::vec3(transform[(0, 3)], transform[(1, 3)], transform[(2, 3)])
+    }
+
+    #[test]
+    fn rebasing_keeps_the_world_in_place() {
+        let addresses = [
+            TileAddress::new(0, 3, 4),
+            TileAddress::new(14, 2, 4),
+            TileAddress::new(15, 4, 4),
+            TileAddress::new(7, 1, 3),
+        ];
+        for (origin, position) in [
+            // Eastwards and southwards within the world.
+            (TileAddress::new(1, 1, 4), glm::vec3(550.0, 20.0, 650.0)),
+            // Westwards across the antimeridian.
+            (TileAddress::new(1, 3, 4), glm::vec3(-450.0, 20.0, 50.0)),
+        ]
+        .iter()
+        {
+            let mut grid = TileGrid::new(*origin, 1, 1, 100.0);
+            let before: Vec<glm::Vec3> = addresses
+                .iter()
+                .map(|address| corner(&grid, address, position))
+                .collect();
+            let camera_tile = grid.tile_at(position);
+
+            let shift = grid.rebase(position).unwrap();
+            assert_ne!(grid.origin, *origin);
+            let position = position - shift;
+            assert_eq!(grid.origin, camera_tile);
+            assert_eq!(grid.tile_at(&position), camera_tile);
+            for (address, before) in addresses.iter().zip(before) {
+                let after = corner(&grid, address, &position);
+                assert!(
+                    glm::distance(&(after + shift), &before) < 1e-3,
+                    "{:?} moved from {:?} to {:?}",
+                    address,
+                    before,
+                    after + shift
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn rebasing_waits_for_the_camera_to_leave() {
+        let mut grid = TileGrid::new(TileAddress::new(4, 4, 4), 1, 1, 100.0);
+        assert!(grid.rebase(&glm::vec3(450.0, 20.0, -350.0)).is_none());
+        assert_eq!(grid.origin, TileAddress::new(4, 4, 4));
+
+        // Past the pole the origin only moves as far as the last row.
+        let mut grid = TileGrid::new(TileAddress::new(4, 14, 4), 1, 1, 100.0);
+        let shift = grid.rebase(&glm::vec3(50.0, 20.0, 950.0)).unwrap();
+        assert_eq!(grid.origin, TileAddress::new(4, 15, 4));
+        assert_eq!(shift, glm::vec3(0.0, 0.0, 100.0));
+    }
+
+    #[test]
+    fn loaded_tiles_are_drawn_as_they_are() {
+        let selected = [TileAddress::new(4, 4, 3), TileAddress::new(5, 4, 3)];
+        let loaded = tiles(&[(4, 4, 3), (5, 4, 3), (9, 9, 4)]);
+        let visible = visible_tiles(&selected, &loaded, &HashSet::new());
+        assert_eq!(visible, tiles(&[(4, 4, 3), (5, 4, 3)]));
+    }
+
+    #[test]
+    fn loading_tiles_fall_back_to_their_closest_loaded_ancestor() {
+        let selected = [TileAddress::new(8, 8, 4), TileAddress::new(9, 8, 4)];
+        let loaded = tiles(&[(2, 2, 2), (4, 4, 3), (9, 8, 4)]);
+        // (9, 8, 4) is loaded but covered by (4, 4, 3) standing in for its sibling, so only the parent is drawn.
+        let visible = visible_tiles(&selected, &loaded, &HashSet::new());
+        assert_eq!(visible, tiles(&[(4, 4, 3)]));
+    }
+
+    #[test]
+    fn loading_tiles_without_an_ancestor_fall_back_to_their_descendants() {
+        let selected = [TileAddress::new(4, 4, 3)];
+        let loaded = tiles(&[(8, 8, 4), (9, 9, 4), (18, 18, 5), (10, 10, 4)]);
+        let visible = visible_tiles(&selected, &loaded, &HashSet::new());
+        assert_eq!(visible, tiles(&[(8, 8, 4), (9, 9, 4)]));
+    }
+
+    #[test]
+    fn failed_tiles_are_not_stood_in_for_by_their_ancestor() {
+        let selected = [TileAddress::new(8, 8, 4)];
+        let loaded = tiles(&[(4, 4, 3), (16, 16, 5)]);
+        let failed = tiles(&[(8, 8, 4)]);
+        assert_eq!(
+            visible_tiles(&selected, &loaded, &failed),
+            tiles(&[(16, 16, 5)])
+        );
+        assert_eq!(
+            visible_tiles(&selected, &loaded, &HashSet::new()),
+            tiles(&[(4, 4, 3)])
+        );
+    }
+}
@@ -0,0 +1,389 @@
+use super::error::Error;
+use super::protos::vector_tile;
+use super::tile_address::TileAddress;
+use flate2::read::GzDecoder;
+use protobuf::Message;
+use rusqlite::{params, Connection, OpenFlags};
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+pub const MAPBOX_STREETS_URL: &str =
+    "https://api.mapbox.com/v4/mapbox.mapbox-streets-v8/{z}/{x}/{y}.vector.pbf?access_token={api_key}";
+
+pub enum FetchResult {
+    Data {
+        bytes: Vec<u8>,
+        etag: Option<String>,
+    },
+    NotModified,
+}
+
+// A backend that tile addresses can be resolved against. Returns the raw tile payload, which may still be gzipped.
+pub trait TileSource: Send + Sync {
+    fn fetch(&self, address: &TileAddress) -> Result<Vec<u8>, Error>;
+    fn name(&self) -> String;
+
+    // Sources that support revalidation override this to skip the download when `etag` still matches.
+    fn fetch_conditional(
+        &self,
+        address: &TileAddress,
+        _etag: Option<&str>,
+    ) -> Result<FetchResult, Error> {
+        Ok(FetchResult::Data {
+            bytes: self.fetch(address)?,
+            etag: None,
+        })
+    }
+}
+
+// Reads tiles laid out as `{root}/{z}/{x}/{y}.pbf`.
+pub struct DirectorySource {
+    root: PathBuf,
+}
+
+impl DirectorySource {
+    pub fn new<P: AsRef<Path>>(root: P) -> Self {
+        DirectorySource {
+            root: root.as_ref().to_path_buf(),
+        }
+    }
+
+    fn tile_path(&self, address: &TileAddress) -> PathBuf {
+        self.root
+            .join(address.z.to_string())
+            .join(address.x.to_string())
+            .join(format!("{}.pbf", address.y))
+    }
+}
+
+impl TileSource for DirectorySource {
+    fn fetch(&self, address: &TileAddress) -> Result<Vec<u8>, Error> {
+        let path = self.tile_path(address);
+        std::fs::read(&path).map_err(|e| Error::Fetch {
+            address: *address,
+            message: format!("{}: {}", path.display(), e),
+        })
+    }
+
+    fn name(&self) -> String {
+        format!("dir:{}", self.root.display())
+    }
+}
+
+// Reads tiles from an MBTiles (SQLite) file.
+pub struct MbTilesSource {
+    path: PathBuf,
+    connection: Mutex<Connection>,
+}
+
+impl MbTilesSource {
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, Error> {
+        let path = path.as_ref().to_path_buf();
+        let connection = Connection::open_with_flags(&path, OpenFlags::SQLITE_OPEN_READ_ONLY)
+            .map_err(|e| Error::Source {
+                name: path.display().to_string(),
+                message: e.to_string(),
+            })?;
+        Ok(MbTilesSource::with_connection(path, connection))
+    }
+
+    fn with_connection(path: PathBuf, connection: Connection) -> Self {
+        MbTilesSource {
+            path,
+            connection: Mutex::new(connection),
+        }
+    }
+}
+
+impl TileSource for MbTilesSource {
+    fn fetch(&self, address: &TileAddress) -> Result<Vec<u8>, Error> {
+        // MBTiles rows are stored in the TMS scheme, so y is flipped.
+        let tile_row = address.flip_y().y;
+        let connection = self.connection.lock().map_err(|e| Error::Fetch {
+            address: *address,
+            message: e.to_string(),
+        })?;
+        connection
+            .query_row(
+                "SELECT tile_data FROM tiles WHERE zoom_level = ?1 AND tile_column = ?2 AND tile_row = ?3",
+                params![address.z, address.x, tile_row],
+                |row| row.get(0),
+            )
+            .map_err(|e| Error::Fetch {
+                address: *address,
+                message: format!("{}: {}", self.path.display(), e),
+            })
+    }
+
+    fn name(&self) -> String {
+        format!("mbtiles:{}", self.path.display())
+    }
+}
+
+// Fetches tiles over HTTP from a URL template containing `{z}`, `{x}` and `{y}` placeholders.
+pub struct HttpSource {
+    url_template: String,
+}
+
+impl HttpSource {
+    pub fn new(url_template: &str, api_key: Option<&str>) -> Self {
+        let url_template = match api_key {
+            Some(api_key) => url_template.replace("{api_key}", api_key),
+            None => String::from(url_template),
+        };
+        HttpSource { url_template }
+    }
+
+    fn tile_url(&self, address: &TileAddress) -> String {
+        self.url_template
+            .replace("{z}", &address.z.to_string())
+            .replace("{x}", &address.x.to_string())
+            .replace("{y}", &address.y.to_string())
+    }
+}
+
+impl TileSource for HttpSource {
+    fn fetch(&self, address: &TileAddress) -> Result<Vec<u8>, Error> {
+        match self.fetch_conditional(address, None)? {
+            FetchResult::Data { bytes, .. } => Ok(bytes),
+            FetchResult::NotModified => Err(Error::Fetch {
+                address: *address,
+                message: String::from("unexpected 304 Not Modified"),
+            }),
+        }
+    }
+
+    fn fetch_conditional(
+        &self,
+        address: &TileAddress,
+        etag: Option<&str>,
+    ) -> Result<FetchResult, Error> {
+        let url = self.tile_url(address);
+        let mut request = ureq::get(&url);
+        if let Some(etag) = etag {
+            request.set("If-None-Match", etag);
+        }
+
+        let response = request.call();
+        if response.status() == 304 {
+            return Ok(FetchResult::NotModified);
+        }
+        if !response.ok() {
+            return Err(Error::Fetch {
+                address: *address,
+                message: format!("HTTP {} {}", response.status(), response.status_text()),
+            });
+        }
+
+        let etag = response.header("ETag").map(String::from);
+        let mut bytes: Vec<u8> = Vec::new();
+        response
+            .into_reader()
+            .read_to_end(&mut bytes)
+            .map_err(|e| Error::Fetch {
+                address: *address,
+                message: e.to_string(),
+            })?;
+        Ok(FetchResult::Data { bytes, etag })
+    }
+
+    fn name(&self) -> String {
+        // Strip the query string so access tokens don't end up in logs or cache paths.
+        let template = self.url_template.split('?').next().unwrap_or("");
+        format!("http:{}", template)
+    }
+}
+
+// Picks a backend from a command line argument: URL templates go over HTTP, `.mbtiles` files are opened
+// with SQLite and anything else is treated as a tile directory. Offline, remote sources are only read from the
+// cache and never contacted, so they need no API key.
+pub fn open_tile_source(
+    source: &str,
+    api_key: Option<&str>,
+    offline: bool,
+) -> Result<Box<dyn TileSource>, Error> {
+    if is_remote(source) {
+        if source.contains("{api_key}") && api_key.is_none() && !offline {
+            return Err(Error::Source {
+                name: String::from(source),
+                message: String::from("--api_key is required for this tile source"),
+            });
+        }
+        return Ok(Box::new(HttpSource::new(source, api_key)));
+    }
+
+    if source.ends_with(".mbtiles") {
+        return Ok(Box::new(MbTilesSource::open(source)?));
+    }
+
+    let path = Path::new(source);
+    if !path.is_dir() {
+        return Err(Error::Source {
+            name: String::from(source),
+            message: String::from("no such tile directory"),
+        });
+    }
+    Ok(Box::new(DirectorySource::new(path)))
+}
+
+pub fn is_remote(source: &str) -> bool {
+    source.starts_with("http://") || source.starts_with("https://")
+}
+
+// Parses the payload of the tile at `address`, decompressing it first if it is gzipped.
+pub fn decode_tile(address: &TileAddress, bytes: &[u8]) -> Result<vector_tile::Tile, Error> {
+    let parse = |bytes: &[u8]| {
+        vector_tile::Tile::parse_from_bytes(bytes).map_err(|e| Error::Parse {
+            address: *address,
+            message: e.to_string(),
+        })
+    };
+
+    let is_gzipped = bytes.len() >= 2 && bytes[0] == 0x1f && bytes[1] == 0x8b;
+    if is_gzipped {
+        let mut decompressor = GzDecoder::new(bytes);
+        let mut decompressed: Vec<u8> = Vec::new();
+        decompressor
+            .read_to_end(&mut decompressed)
+            .map_err(|e| Error::Decompress {
+                address: *address,
+                message: e.to_string(),
+            })?;
+        return parse(&decompressed);
+    }
+
+    parse(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flate2::write::GzEncoder;
+    use flate2::Compression;
+    use std::io::Write;
+
+    fn tile_bytes(layer_name: &str) -> Vec<u8> {
+        let mut layer = vector_tile::Tile_Layer::new();
+        layer.set_name(String::from(layer_name));
+        layer.set_version(2);
+        let mut tile = vector_tile::Tile::new();
+        tile.mut_layers().push(layer);
+        tile.write_to_bytes().unwrap()
+    }
+
+    fn gzip(bytes: &[u8]) -> Vec<u8> {
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(bytes).unwrap();
+        encoder.finish().unwrap()
+    }
+
+    fn create_mbtiles(connection: &Connection, tiles: &[(u32, u32, u32, &[u8])]) {
+        connection
+            .execute_batch(
+                "CREATE TABLE tiles (zoom_level INTEGER, tile_column INTEGER, tile_row INTEGER, tile_data BLOB);",
+            )
+            .unwrap();
+        for (z, x, row, data) in tiles {
+            connection
+                .execute(
+                    "INSERT INTO tiles VALUES (?1, ?2, ?3, ?4)",
+                    params![z, x, row, data.to_vec()],
+                )
+                .unwrap();
+        }
+    }
+
+    #[test]
+    fn directories_are_read_as_z_x_y() {
+        let root = tempfile::tempdir().unwrap();
+        std::fs::create_dir_all(root.path().join("14/8800")).unwrap();
+        std::fs::write(root.path().join("14/8800/5400.pbf"), b"tile").unwrap();
+
+        let source = DirectorySource::new(root.path());
+        assert_eq!(
+            source.fetch(&TileAddress::new(8800, 5400, 14)).unwrap(),
+            b"tile"
+        );
+        match source.fetch(&TileAddress::new(5400, 8800, 14)) {
+            Err(Error::Fetch { address, message }) => {
+                assert_eq!(address, TileAddress::new(5400, 8800, 14));
+                assert!(message.contains("5400/8800.pbf"), "{}", message);
+            }
+            other => panic!("unexpected {:?}", other.map(|_| ())),
+        }
+        assert_eq!(source.name(), format!("dir:{}", root.path().display()));
+    }
+
+    #[test]
+    fn mbtiles_rows_are_flipped() {
+        let connection = Connection::open_in_memory().unwrap();
+        // At zoom 2 the XYZ row 0 is the TMS row 3.
+        create_mbtiles(&connection, &[(2, 1, 3, b"north"), (2, 1, 0, b"south")]);
+        let source = MbTilesSource::with_connection(PathBuf::from("memory.mbtiles"), connection);
+
+        assert_eq!(source.fetch(&TileAddress::new(1, 0, 2)).unwrap(), b"north");
+        assert_eq!(source.fetch(&TileAddress::new(1, 3, 2)).unwrap(), b"south");
+        match source.fetch(&TileAddress::new(1, 1, 2)) {
+            Err(Error::Fetch { message, .. }) => {
+                assert!(message.starts_with("memory.mbtiles: "), "{}", message)
+            }
+            other => panic!("unexpected {:?}", other.map(|_| ())),
+        }
+    }
+
+    #[test]
+    fn backends_are_chosen_by_the_source() {
+        let root = tempfile::tempdir().unwrap();
+        let mbtiles = root.path().join("tiles.mbtiles");
+        create_mbtiles(&Connection::open(&mbtiles).unwrap(), &[]);
+        let mbtiles = mbtiles.to_str().unwrap();
+        let directory = root.path().to_str().unwrap();
+
+        let cases = [
+            ("https://example.com/{z}/{x}/{y}.pbf", "http:"),
+            ("http://example.com/{z}/{x}/{y}.pbf?key={api_key}", "http:"),
+            (mbtiles, "mbtiles:"),
+            (directory, "dir:"),
+        ];
+        for (source, prefix) in cases.iter() {
+            let opened = open_tile_source(source, Some("key"), false)
+                .unwrap_or_else(|e| panic!("{}: {}", source, e));
+            assert!(opened.name().starts_with(prefix), "{}", opened.name());
+        }
+
+        // Keys are left out of the name, and only required when the source is actually contacted.
+        let keyed = "https://example.com/{z}/{x}/{y}.pbf?access_token={api_key}";
+        let opened = open_tile_source(keyed, Some("secret"), false).unwrap();
+        assert_eq!(opened.name(), "http:https://example.com/{z}/{x}/{y}.pbf");
+        assert!(open_tile_source(keyed, None, false).is_err());
+        assert!(open_tile_source(keyed, None, true).is_ok());
+
+        let missing = root.path().join("missing");
+        assert!(open_tile_source(missing.to_str().unwrap(), None, false).is_err());
+        let missing = root.path().join("missing.mbtiles");
+        assert!(open_tile_source(missing.to_str().unwrap(), None, false).is_err());
+    }
+
+    #[test]
+    fn tiles_are_decompressed_when_gzipped() {
+        let address = TileAddress::new(1, 2, 3);
+        let bytes = tile_bytes("water");
+        for payload in [bytes.clone(), gzip(&bytes)].iter() {
+            let tile = decode_tile(&address, payload).unwrap();
+            assert_eq!(tile.get_layers()[0].get_name(), "water");
+        }
+
+        // A gzip header on a broken stream fails to decompress rather than to parse.
+        let mut broken = gzip(&bytes);
+        broken.truncate(12);
+        assert!(matches!(
+            decode_tile(&address, &broken),
+            Err(Error::Decompress { .. })
+        ));
+        assert!(matches!(
+            decode_tile(&address, &[0x1f, 0x0a, 0xff]),
+            Err(Error::Parse { .. })
+        ));
+    }
+}
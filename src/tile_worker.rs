@@ -0,0 +1,304 @@
+use super::error::Error;
+use super::features::FeatureOption;
+use super::protos::vector_tile;
+use super::tile_address::TileAddress;
+use super::tile_builder::{self, BuiltTile};
+use super::tile_source::{self, TileSource};
+use std::collections::{HashMap, HashSet};
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::mpsc::{channel, Receiver, Sender, TryRecvError};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+pub struct TileResult {
+    pub address: TileAddress,
+    pub tile: Result<BuiltTile, Error>,
+    // Which of the tiles in `tile_builder::side_neighbours` the tile was built with.
+    pub neighbours: [bool; 4],
+}
+
+type DecodedTiles = Mutex<HashMap<TileAddress, Arc<vector_tile::Tile>>>;
+
+// Fetches, decodes and tessellates tiles on background threads. Finished meshes are sent back over a channel
+// so that the thread owning the GL context only has to upload them. `queued` holds the requests no worker has
+// started on yet; workers skip anything that was cancelled in the meantime. `decoded` keeps every tile until it is
+// forgotten, so tiles can be built with their neighbours and built again without fetching them.
+pub struct TileWorkerPool {
+    requests: Sender<TileAddress>,
+    results: Receiver<TileResult>,
+    queued: Arc<Mutex<HashSet<TileAddress>>>,
+    decoded: Arc<DecodedTiles>,
+}
+
+impl TileWorkerPool {
+    pub fn new(
+        num_workers: usize,
+        source: Arc<dyn TileSource>,
+        options: Arc<Vec<FeatureOption>>,
+        world_width: f64,
+    ) -> TileWorkerPool {
+        let (request_sender, request_receiver) = channel::<TileAddress>();
+        let (result_sender, result_receiver) = channel::<TileResult>();
+        let request_receiver = Arc::new(Mutex::new(request_receiver));
+        let queued = Arc::new(Mutex::new(HashSet::new()));
+        let decoded = Arc::new(Mutex::new(HashMap::new()));
+
+        for i in 0..num_workers.max(1) {
+            let requests = Arc::clone(&request_receiver);
+            let queued = Arc::clone(&queued);
+            let decoded = Arc::clone(&decoded);
+            let results = result_sender.clone();
+            let source = Arc::clone(&source);
+            let options = Arc::clone(&options);
+            thread::Builder::new()
+                .name(format!("tile-worker-{}", i))
+                .spawn(move || loop {
+                    let address = {
+                        let requests = match requests.lock() {
+                            Ok(requests) => requests,
+                            Err(_) => return,
+                        };
+                        match requests.recv() {
+                            Ok(address) => address,
+                            // The pool was dropped.
+                            Err(_) => return,
+                        }
+                    };
+                    // Taking the address out of the queue marks it as started, so it can no longer be cancelled.
+                    let cancelled = match queued.lock() {
+                        Ok(mut queued) => !queued.remove(&address),
+                        Err(_) => return,
+                    };
+                    if cancelled {
+                        continue;
+                    }
+
+                    let result =
+                        load_tile(&address, source.as_ref(), &decoded, &options, world_width);
+                    if results.send(result).is_err() {
+                        return;
+                    }
+                })
+                .expect("Failed to spawn tile worker");
+        }
+
+        TileWorkerPool {
+            requests: request_sender,
+            results: result_receiver,
+            queued,
+            decoded,
+        }
+    }
+
+    pub fn request(&self, address: TileAddress) {
+        if let Ok(mut queued) = self.queued.lock() {
+            queued.insert(address);
+        }
+        // Sending only fails once every worker has exited, in which case there is nobody to report to.
+        let _ = self.requests.send(address);
+    }
+
+    // Drops a request no worker has started on. Returns false if the tile is already being loaded, in which case
+    // its result still arrives.
+    pub fn cancel(&self, address: &TileAddress) -> bool {
+        match self.queued.lock() {
+            Ok(mut queued) => queued.remove(address),
+            Err(_) => false,
+        }
+    }
+
+    // Drops the decoded tile, once it is no longer shown and so no longer needed to build its neighbours.
+    pub fn forget(&self, address: &TileAddress) {
+        if let Ok(mut decoded) = self.decoded.lock() {
+            decoded.remove(address);
+        }
+    }
+
+    // Returns a finished tile if one is ready, without blocking.
+    pub fn try_receive(&self) -> Option<TileResult> {
+        match self.results.try_recv() {
+            Ok(result) => Some(result),
+            Err(TryRecvError::Empty) | Err(TryRecvError::Disconnected) => None,
+        }
+    }
+}
+
+fn load_tile(
+    address: &TileAddress,
+    source: &dyn TileSource,
+    decoded: &DecodedTiles,
+    options: &[FeatureOption],
+    world_width: f64,
+) -> TileResult {
+    let stored = |address: &TileAddress| match decoded.lock() {
+        Ok(decoded) => decoded.get(address).cloned(),
+        Err(_) => None,
+    };
+    let mut neighbours = [false; 4];
+    // A panic anywhere in loading, e.g. on geometry that trips up the tessellation, must not take the worker down
+    // with it, or the tile would never be reported back and no further tiles would load.
+    let tile = panic::catch_unwind(AssertUnwindSafe(|| {
+        let tile = match stored(address) {
+            Some(tile) => tile,
+            None => {
+                let bytes = source.fetch(address)?;
+                let tile = Arc::new(tile_source::decode_tile(address, &bytes)?);
+                if let Ok(mut decoded) = decoded.lock() {
+                    decoded.insert(*address, Arc::clone(&tile));
+                }
+                tile
+            }
+        };
+        let neighbour_tiles = tile_builder::side_neighbours(address)
+            .map(|neighbour| neighbour.and_then(|neighbour| stored(&neighbour)));
+        for (side, neighbour) in neighbour_tiles.iter().enumerate() {
+            neighbours[side] = neighbour.is_some();
+        }
+        Ok(tile_builder::build_tile(
+            &tile,
+            &neighbour_tiles
+                .each_ref()
+                .map(|neighbour| neighbour.as_deref()),
+            options,
+            address,
+            world_width,
+        ))
+    }))
+    .unwrap_or_else(|_| {
+        Err(Error::Fetch {
+            address: *address,
+            message: String::from("the worker loading it panicked"),
+        })
+    });
+    TileResult {
+        address: *address,
+        tile,
+        neighbours,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::mpsc::SyncSender;
+    use std::time::Duration;
+
+    // Reports every tile it starts on and then waits to be released before returning an empty tile.
+    struct BlockingSource {
+        started: Mutex<Sender<TileAddress>>,
+        release: Mutex<Receiver<()>>,
+    }
+
+    impl TileSource for BlockingSource {
+        fn fetch(&self, address: &TileAddress) -> Result<Vec<u8>, Error> {
+            self.started.lock().unwrap().send(*address).unwrap();
+            self.release.lock().unwrap().recv().unwrap();
+            Ok(vec![])
+        }
+
+        fn name(&self) -> String {
+            String::from("blocking")
+        }
+    }
+
+    fn pool() -> (TileWorkerPool, Receiver<TileAddress>, SyncSender<()>) {
+        let (started_sender, started) = channel();
+        let (release, release_receiver) = std::sync::mpsc::sync_channel(0);
+        let source = BlockingSource {
+            started: Mutex::new(started_sender),
+            release: Mutex::new(release_receiver),
+        };
+        let pool = TileWorkerPool::new(1, Arc::new(source), Arc::new(vec![]), 1000.0);
+        (pool, started, release)
+    }
+
+    fn receive_result(pool: &TileWorkerPool) -> TileResult {
+        for _ in 0..500 {
+            if let Some(result) = pool.try_receive() {
+                assert!(result.tile.is_ok());
+                return result;
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+        panic!("no tile arrived");
+    }
+
+    fn receive(pool: &TileWorkerPool) -> TileAddress {
+        receive_result(pool).address
+    }
+
+    #[test]
+    fn cancelled_requests_are_skipped() {
+        let (pool, started, release) = pool();
+        let (a, b, c) = (
+            TileAddress::new(0, 0, 1),
+            TileAddress::new(1, 0, 1),
+            TileAddress::new(0, 1, 1),
+        );
+        pool.request(a);
+        pool.request(b);
+        pool.request(c);
+        assert_eq!(started.recv().unwrap(), a);
+
+        // The only worker is busy with `a`, so `b` is still queued and can be dropped, `a` can not.
+        assert!(pool.cancel(&b));
+        assert!(!pool.cancel(&a));
+        assert!(!pool.cancel(&b));
+
+        release.send(()).unwrap();
+        assert_eq!(receive(&pool), a);
+        assert_eq!(started.recv().unwrap(), c);
+        release.send(()).unwrap();
+        assert_eq!(receive(&pool), c);
+        assert!(pool.try_receive().is_none());
+    }
+
+    #[test]
+    fn requests_made_again_after_a_cancel_load_once() {
+        let (pool, started, release) = pool();
+        let (a, b) = (TileAddress::new(0, 0, 1), TileAddress::new(1, 0, 1));
+        pool.request(a);
+        assert_eq!(started.recv().unwrap(), a);
+
+        // `b` is queued twice, but cancelled in between, so only one of the two is loaded.
+        pool.request(b);
+        assert!(pool.cancel(&b));
+        pool.request(b);
+        release.send(()).unwrap();
+        assert_eq!(receive(&pool), a);
+        assert_eq!(started.recv().unwrap(), b);
+        release.send(()).unwrap();
+        assert_eq!(receive(&pool), b);
+
+        thread::sleep(Duration::from_millis(50));
+        assert!(started.try_recv().is_err());
+        assert!(pool.try_receive().is_none());
+    }
+
+    #[test]
+    fn decoded_tiles_are_built_again_without_fetching_until_forgotten() {
+        let (pool, started, release) = pool();
+        let (a, b) = (TileAddress::new(0, 0, 1), TileAddress::new(1, 0, 1));
+        pool.request(a);
+        assert_eq!(started.recv().unwrap(), a);
+        release.send(()).unwrap();
+        assert_eq!(receive(&pool), a);
+
+        // `b` is built with `a` on its left, which is not fetched again when it is built a second time.
+        pool.request(b);
+        assert_eq!(started.recv().unwrap(), b);
+        release.send(()).unwrap();
+        let result = receive_result(&pool);
+        assert_eq!(result.address, b);
+        assert_eq!(result.neighbours, [true, true, false, false]);
+        pool.request(a);
+        assert_eq!(receive(&pool), a);
+        assert!(started.try_recv().is_err());
+
+        pool.forget(&a);
+        pool.request(a);
+        assert_eq!(started.recv().unwrap(), a);
+        release.send(()).unwrap();
+        assert_eq!(receive(&pool), a);
+    }
+}
@@ -101,34 +101,3 @@ impl VecExt for Vec<f32> {
         (self[i * 3], self[i * 3 + 1], self[i * 3 + 2])
     }
 }
-
-pub fn calculate_normals(vertices: &Vec<glm::Vec3>, indices: &Vec<u32>) -> Vec<glm::Vec3> {
-    let mut vertex_normals: Vec<glm::Vec3> = vec![glm::vec3(0.0, 0.0, 0.0); vertices.len()];
-    let mut index = 0;
-    if vertices.len() < 3 {
-        return vertex_normals;
-    }
-    while index < indices.len() {
-        let vertex_1 = indices[index];
-        let vertex_2 = indices[index + 1];
-        let vertex_3 = indices[index + 2];
-
-        let edge_12 = vertices[vertex_2 as usize] - vertices[vertex_1 as usize];
-        let edge_13 = vertices[vertex_3 as usize] - vertices[vertex_1 as usize];
-
-        let area_weighted_normal = glm::cross(&edge_12, &edge_13);
-
-        vertex_normals[vertex_1 as usize] += area_weighted_normal;
-        vertex_normals[vertex_2 as usize] += area_weighted_normal;
-        vertex_normals[vertex_3 as usize] += area_weighted_normal;
-        index += 3;
-    }
-
-    index = 0;
-    while index < vertex_normals.len() {
-        vertex_normals[index] = vertex_normals[index].normalize();
-        index += 1;
-    }
-
-    vertex_normals
-}
@@ -0,0 +1,243 @@
+use serde::Deserialize;
+
+// A style value that is either constant or interpolated between `[zoom, value]` stops, e.g.
+// `{ "stops": [[12, 1.0], [18, 6.0]] }`. Zooms outside the stops use the first or last value. With a `base` other
+// than 1 the value changes exponentially like in Mapbox GL, by a factor of `base` for every zoom level, so higher
+// bases put more of the change towards the upper stop.
+#[derive(Deserialize, Debug, Clone)]
+#[serde(untagged)]
+pub enum ZoomValue<T> {
+    Constant(T),
+    Stops {
+        stops: Vec<(f32, T)>,
+        #[serde(default = "linear_base")]
+        base: f32,
+    },
+}
+
+fn linear_base() -> f32 {
+    1.0
+}
+
+pub trait Interpolate: Copy {
+    fn interpolate(a: Self, b: Self, t: f32) -> Self;
+}
+
+impl Interpolate for f32 {
+    fn interpolate(a: f32, b: f32, t: f32) -> f32 {
+        a + (b - a) * t
+    }
+}
+
+impl Interpolate for [f32; 4] {
+    fn interpolate(a: [f32; 4], b: [f32; 4], t: f32) -> [f32; 4] {
+        let mut c = a;
+        for i in 0..4 {
+            c[i] = f32::interpolate(a[i], b[i], t);
+        }
+        c
+    }
+}
+
+impl Interpolate for glm::Vec4 {
+    fn interpolate(a: glm::Vec4, b: glm::Vec4, t: f32) -> glm::Vec4 {
+        glm::lerp(&a, &b, t)
+    }
+}
+
+impl<T: Interpolate> ZoomValue<T> {
+    pub fn at(&self, zoom: f32) -> T {
+        let (stops, base) = match self {
+            ZoomValue::Constant(value) => return *value,
+            ZoomValue::Stops { stops, base } => (stops, *base),
+        };
+        // Validated styles never have empty stops.
+        let (first, last) = (stops[0], stops[stops.len() - 1]);
+        if zoom <= first.0 {
+            return first.1;
+        }
+        for pair in stops.windows(2) {
+            let ((z0, v0), (z1, v1)) = (pair[0], pair[1]);
+            if zoom <= z1 {
+                return T::interpolate(v0, v1, interpolation_factor(zoom - z0, z1 - z0, base));
+            }
+        }
+        last.1
+    }
+
+    pub fn values(&self) -> Vec<T> {
+        match self {
+            ZoomValue::Constant(value) => vec![*value],
+            ZoomValue::Stops { stops, .. } => stops.iter().map(|(_, value)| *value).collect(),
+        }
+    }
+
+    pub fn map<U, F: Fn(T) -> U>(&self, f: F) -> ZoomValue<U> {
+        match self {
+            ZoomValue::Constant(value) => ZoomValue::Constant(f(*value)),
+            ZoomValue::Stops { stops, base } => ZoomValue::Stops {
+                stops: stops
+                    .iter()
+                    .map(|(zoom, value)| (*zoom, f(*value)))
+                    .collect(),
+                base: *base,
+            },
+        }
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        if let ZoomValue::Stops { stops, base } = self {
+            if base.is_nan() || *base <= 0.0 {
+                return Err(format!("zoom \"base\" must be positive, found {}", base));
+            }
+            if stops.is_empty() {
+                return Err(String::from("zoom \"stops\" must not be empty"));
+            }
+            if stops.windows(2).any(|pair| pair[0].0 > pair[1].0) {
+                return Err(String::from("zoom \"stops\" must be sorted by zoom"));
+            }
+        }
+        Ok(())
+    }
+}
+
+// How far from 0 to 1 the value has moved `progress` zoom levels into a pair of stops `span` levels apart.
+fn interpolation_factor(progress: f32, span: f32, base: f32) -> f32 {
+    if span <= 0.0 {
+        1.0
+    } else if base == 1.0 {
+        progress / span
+    } else {
+        (base.powf(progress) - 1.0) / (base.powf(span) - 1.0)
+    }
+}
+
+impl<T: Default> Default for ZoomValue<T> {
+    fn default() -> Self {
+        ZoomValue::Constant(T::default())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn stops(stops: &[(f32, f32)], base: f32) -> ZoomValue<f32> {
+        ZoomValue::Stops {
+            stops: stops.to_vec(),
+            base,
+        }
+    }
+
+    fn check(value: &ZoomValue<f32>, cases: &[(f32, f32)]) {
+        for (zoom, expected) in cases {
+            let actual = value.at(*zoom);
+            assert!(
+                (actual - expected).abs() < 1e-5,
+                "at zoom {}: {} != {}",
+                zoom,
+                actual,
+                expected
+            );
+        }
+    }
+
+    #[test]
+    fn constants_ignore_the_zoom() {
+        check(
+            &ZoomValue::Constant(3.0),
+            &[(0.0, 3.0), (14.5, 3.0), (22.0, 3.0)],
+        );
+    }
+
+    #[test]
+    fn linear_stops() {
+        let value = stops(&[(10.0, 0.0), (20.0, 10.0)], 1.0);
+        check(
+            &value,
+            &[
+                (5.0, 0.0),
+                (10.0, 0.0),
+                (12.5, 2.5),
+                (15.0, 5.0),
+                (20.0, 10.0),
+                (25.0, 10.0),
+            ],
+        );
+
+        let value = stops(&[(10.0, 0.0), (12.0, 4.0), (16.0, 4.0), (18.0, 0.0)], 1.0);
+        check(
+            &value,
+            &[
+                (11.0, 2.0),
+                (12.0, 4.0),
+                (14.0, 4.0),
+                (17.0, 2.0),
+                (19.0, 0.0),
+            ],
+        );
+    }
+
+    #[test]
+    fn stops_at_the_same_zoom_step() {
+        let value = stops(&[(10.0, 1.0), (10.0, 5.0)], 1.0);
+        check(&value, &[(9.0, 1.0), (10.0, 1.0), (10.5, 5.0)]);
+    }
+
+    #[test]
+    fn exponential_stops() {
+        // With base 2 every zoom level covers twice the change of the one before: 1/3 of it, then 2/3.
+        let value = stops(&[(10.0, 0.0), (12.0, 3.0)], 2.0);
+        check(
+            &value,
+            &[
+                (9.0, 0.0),
+                (10.0, 0.0),
+                (11.0, 1.0),
+                (12.0, 3.0),
+                (13.0, 3.0),
+            ],
+        );
+
+        // Bases below 1 put the change towards the lower stop.
+        let value = stops(&[(10.0, 0.0), (12.0, 3.0)], 0.5);
+        check(&value, &[(11.0, 2.0)]);
+
+        let value = stops(&[(0.0, 0.0), (10.0, 100.0), (20.0, 200.0)], 1.5);
+        let t = (1.5f32.powf(5.0) - 1.0) / (1.5f32.powf(10.0) - 1.0);
+        check(&value, &[(5.0, 100.0 * t), (15.0, 100.0 + 100.0 * t)]);
+        assert!(value.at(5.0) < 50.0);
+    }
+
+    #[test]
+    fn colors_interpolate_per_channel() {
+        let value = ZoomValue::Stops {
+            stops: vec![(10.0, [0.0, 0.2, 1.0, 1.0]), (14.0, [1.0, 0.2, 0.0, 0.0])],
+            base: 1.0,
+        };
+        assert_eq!(value.at(8.0), [0.0, 0.2, 1.0, 1.0]);
+        assert_eq!(value.at(11.0), [0.25, 0.2, 0.75, 0.75]);
+        assert_eq!(value.at(16.0), [1.0, 0.2, 0.0, 0.0]);
+    }
+
+    #[test]
+    fn stops_deserialize_with_a_linear_default() {
+        let value: ZoomValue<f32> =
+            serde_json::from_str(r#"{ "stops": [[12, 1.0], [18, 6.0]] }"#).unwrap();
+        check(&value, &[(15.0, 3.5)]);
+        let value: ZoomValue<f32> =
+            serde_json::from_str(r#"{ "stops": [[10, 0.0], [12, 3.0]], "base": 2 }"#).unwrap();
+        check(&value, &[(11.0, 1.0)]);
+        let value: ZoomValue<f32> = serde_json::from_str("4.5").unwrap();
+        check(&value, &[(0.0, 4.5)]);
+    }
+
+    #[test]
+    fn invalid_stops_are_rejected() {
+        assert!(stops(&[], 1.0).validate().is_err());
+        assert!(stops(&[(12.0, 1.0), (10.0, 2.0)], 1.0).validate().is_err());
+        assert!(stops(&[(10.0, 1.0)], 0.0).validate().is_err());
+        assert!(stops(&[(10.0, 1.0)], f32::NAN).validate().is_err());
+        assert!(stops(&[(10.0, 1.0), (10.0, 2.0)], 1.2).validate().is_ok());
+    }
+}
@@ -0,0 +1,180 @@
+// Renders the fixture tile in `tests/fixtures` with the `render` subcommand and compares the frames against the
+// reference images in `tests/golden`. Run with `UPDATE_GOLDEN=1` to write new references after an intended visual
+// change, and look at `target/golden` for the rendered frames and diff images when a comparison fails. Rendering
+// needs OSMesa, so the tests only run with the `golden` feature, where a frame that fails to render fails the test.
+use image::{Rgb, RgbImage};
+use std::env;
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+
+const WIDTH: u32 = 320;
+const HEIGHT: u32 = 240;
+
+// How far apart two colours may be, in the YIQ colour space, before a pixel counts as different. 0.1 is roughly the
+// smallest difference that is visible, which leaves room for rounding differences between GL drivers.
+const PIXEL_THRESHOLD: f64 = 0.1;
+// How many pixels, as a fraction of the image, may differ before the frame no longer matches.
+const MAX_DIFFERENT_PIXELS: f64 = 0.005;
+
+struct Camera {
+    yaw: f32,
+    pitch: f32,
+    distance: f32,
+}
+
+#[test]
+#[cfg_attr(not(feature = "golden"), ignore)]
+fn top_down() {
+    check_golden(
+        "top_down",
+        Camera {
+            yaw: -90.0,
+            pitch: -89.0,
+            distance: 900.0,
+        },
+    );
+}
+
+#[test]
+#[cfg_attr(not(feature = "golden"), ignore)]
+fn oblique() {
+    check_golden(
+        "oblique",
+        Camera {
+            yaw: -60.0,
+            pitch: -40.0,
+            distance: 600.0,
+        },
+    );
+}
+
+#[test]
+#[cfg_attr(not(feature = "golden"), ignore)]
+fn street_level() {
+    check_golden(
+        "street_level",
+        Camera {
+            yaw: -30.0,
+            pitch: -12.0,
+            distance: 400.0,
+        },
+    );
+}
+
+fn check_golden(name: &str, camera: Camera) {
+    let root = Path::new(env!("CARGO_MANIFEST_DIR"));
+    let output_dir = root.join("target").join("golden");
+    fs::create_dir_all(&output_dir).unwrap();
+    let actual_path = output_dir.join(format!("{}.png", name));
+
+    render(root, &actual_path, &camera);
+    let actual = image::open(&actual_path).unwrap().to_rgb8();
+
+    let reference_path = root
+        .join("tests")
+        .join("golden")
+        .join(format!("{}.png", name));
+    if env::var_os("UPDATE_GOLDEN").is_some() {
+        fs::create_dir_all(reference_path.parent().unwrap()).unwrap();
+        actual.save(&reference_path).unwrap();
+        return;
+    }
+    let reference = match image::open(&reference_path) {
+        Ok(reference) => reference.to_rgb8(),
+        Err(e) => panic!(
+            "{}: {}, run the tests with UPDATE_GOLDEN=1 to create it",
+            reference_path.display(),
+            e
+        ),
+    };
+    assert_eq!(
+        reference.dimensions(),
+        actual.dimensions(),
+        "{} has a different size than the rendered frame",
+        reference_path.display()
+    );
+
+    let (diff, different_pixels) = compare(&reference, &actual);
+    let fraction = different_pixels as f64 / (WIDTH * HEIGHT) as f64;
+    if fraction > MAX_DIFFERENT_PIXELS {
+        let diff_path = output_dir.join(format!("{}_diff.png", name));
+        diff.save(&diff_path).unwrap();
+        panic!(
+            "{} differs from {} in {} pixels ({:.2}%), see {}",
+            actual_path.display(),
+            reference_path.display(),
+            different_pixels,
+            fraction * 100.0,
+            diff_path.display()
+        );
+    }
+}
+
+// Renders the fixture tile seen from `camera` to `output`.
+fn render(root: &Path, output: &Path, camera: &Camera) {
+    let result = Command::new(env!("CARGO_BIN_EXE_vector-tile-renderer-rs"))
+        .current_dir(root)
+        .arg("render")
+        .arg(format!("--output={}", output.display()))
+        .arg(format!("--width={}", WIDTH))
+        .arg(format!("--height={}", HEIGHT))
+        .arg("--source=tests/fixtures/tiles")
+        .arg("--style=tests/fixtures/style.json")
+        // The centre of tile 16/19294/24641, the only tile in the fixture.
+        .arg("--latitude=40.70771")
+        .arg("--longitude=-74.012146")
+        .arg("--zoom=16")
+        .arg("--tile_radius=1")
+        .arg("--lod_levels=0")
+        .arg("--workers=1")
+        .arg(format!("--yaw={}", camera.yaw))
+        .arg(format!("--pitch={}", camera.pitch))
+        .arg(format!("--distance={}", camera.distance))
+        .output()
+        .unwrap();
+
+    assert!(
+        result.status.success(),
+        "rendering failed:\n{}\n{}",
+        String::from_utf8_lossy(&result.stdout),
+        String::from_utf8_lossy(&result.stderr)
+    );
+}
+
+// Counts the pixels that differ noticeably, and draws them red on a faded copy of the reference.
+fn compare(reference: &RgbImage, actual: &RgbImage) -> (RgbImage, u32) {
+    let mut diff = RgbImage::new(reference.width(), reference.height());
+    let mut different_pixels = 0;
+    for (x, y, expected) in reference.enumerate_pixels() {
+        if color_delta(expected, actual.get_pixel(x, y)) > PIXEL_THRESHOLD {
+            different_pixels += 1;
+            diff.put_pixel(x, y, Rgb([255, 0, 0]));
+        } else {
+            let (luma, _, _) = yiq(expected);
+            let faded = (255.0 - (255.0 - luma) * 0.1) as u8;
+            diff.put_pixel(x, y, Rgb([faded, faded, faded]));
+        }
+    }
+    (diff, different_pixels)
+}
+
+// The perceived difference between two colours from 0 to 1, weighing brightness more than hue like the eye does.
+fn color_delta(a: &Rgb<u8>, b: &Rgb<u8>) -> f64 {
+    let (y0, i0, q0) = yiq(a);
+    let (y1, i1, q1) = yiq(b);
+    let (dy, di, dq) = (y0 - y1, i0 - i1, q0 - q1);
+    // The largest possible delta is between black and white.
+    let delta = 0.5053 * dy * dy + 0.299 * di * di + 0.1957 * dq * dq;
+    (delta / 35215.0).sqrt()
+}
+
+fn yiq(color: &Rgb<u8>) -> (f64, f64, f64) {
+    let [r, g, b] = color.0;
+    let (r, g, b) = (r as f64, g as f64, b as f64);
+    (
+        r * 0.29889531 + g * 0.58662247 + b * 0.11448223,
+        r * 0.59597799 - g * 0.27417610 - b * 0.32180189,
+        r * 0.21147017 - g * 0.52261711 + b * 0.31114694,
+    )
+}